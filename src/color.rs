@@ -0,0 +1,166 @@
+use crate::config::CbType;
+use image::Rgba;
+
+// D65 reference white used for CIELAB conversions
+const WHITE_X: f32 = 0.950_47;
+const WHITE_Y: f32 = 1.0;
+const WHITE_Z: f32 = 1.088_83;
+
+/// Converts an sRGB channel value (0-255) to linear light (0.0 to 1.0)
+pub fn srgb_to_linear(channel: u8) -> f32 {
+    let c = channel as f32 / 255.0;
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Converts a linear light value (0.0 to 1.0) back to an sRGB channel value
+pub fn linear_to_srgb(value: f32) -> u8 {
+    let v = value.clamp(0.0, 1.0);
+    let c = if v <= 0.003_130_8 {
+        v * 12.92
+    } else {
+        1.055 * v.powf(1.0 / 2.4) - 0.055
+    };
+    (c * 255.0).round() as u8
+}
+
+/// Converts an sRGB color to CIELAB `[L*, a*, b*]` (alpha is ignored)
+pub fn rgb_to_lab(color: &Rgba<u8>) -> [f32; 3] {
+    let r = srgb_to_linear(color[0]);
+    let g = srgb_to_linear(color[1]);
+    let b = srgb_to_linear(color[2]);
+
+    let x = 0.412_456_4 * r + 0.357_576_1 * g + 0.180_437_5 * b;
+    let y = 0.212_672_9 * r + 0.715_152_2 * g + 0.072_175 * b;
+    let z = 0.019_333_9 * r + 0.119_192 * g + 0.950_304_1 * b;
+
+    let fx = lab_f(x / WHITE_X);
+    let fy = lab_f(y / WHITE_Y);
+    let fz = lab_f(z / WHITE_Z);
+
+    [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+}
+
+/// Converts a CIELAB `[L*, a*, b*]` value back to an opaque sRGB color
+/// Out-of-gamut values are clamped to the sRGB cube
+pub fn lab_to_rgb(lab: [f32; 3]) -> Rgba<u8> {
+    let fy = (lab[0] + 16.0) / 116.0;
+    let fx = fy + lab[1] / 500.0;
+    let fz = fy - lab[2] / 200.0;
+
+    let x = lab_f_inv(fx) * WHITE_X;
+    let y = lab_f_inv(fy) * WHITE_Y;
+    let z = lab_f_inv(fz) * WHITE_Z;
+
+    let r = 3.240_454_2 * x - 1.537_138_5 * y - 0.498_531_4 * z;
+    let g = -0.969_266 * x + 1.876_010_8 * y + 0.041_556 * z;
+    let b = 0.055_643_4 * x - 0.204_025_9 * y + 1.057_225_2 * z;
+
+    Rgba([linear_to_srgb(r), linear_to_srgb(g), linear_to_srgb(b), 255])
+}
+
+/// Perceptual distance between two colors (CIE76 delta E)
+pub fn delta_e(a: &Rgba<u8>, b: &Rgba<u8>) -> f32 {
+    let la = rgb_to_lab(a);
+    let lb = rgb_to_lab(b);
+    ((la[0] - lb[0]).powi(2) + (la[1] - lb[1]).powi(2) + (la[2] - lb[2]).powi(2)).sqrt()
+}
+
+fn lab_f(t: f32) -> f32 {
+    const DELTA: f32 = 6.0 / 29.0;
+    if t > DELTA * DELTA * DELTA {
+        t.cbrt()
+    } else {
+        t / (3.0 * DELTA * DELTA) + 4.0 / 29.0
+    }
+}
+
+fn lab_f_inv(t: f32) -> f32 {
+    const DELTA: f32 = 6.0 / 29.0;
+    if t > DELTA {
+        t * t * t
+    } else {
+        3.0 * DELTA * DELTA * (t - 4.0 / 29.0)
+    }
+}
+
+/// Simulates how a color appears with the given color vision deficiency
+/// Uses full-severity linear RGB projection matrices
+pub fn simulate_color_blindness(color: &Rgba<u8>, cb_type: CbType) -> Rgba<u8> {
+    let matrix: [[f32; 3]; 3] = match cb_type {
+        CbType::Protanopia => [
+            [0.112_38, 0.887_62, 0.0],
+            [0.112_38, 0.887_62, 0.0],
+            [0.004_01, -0.004_01, 1.0],
+        ],
+        CbType::Deuteranopia => [
+            [0.292_75, 0.707_25, 0.0],
+            [0.292_75, 0.707_25, 0.0],
+            [-0.022_34, 0.022_34, 1.0],
+        ],
+        CbType::Tritanopia => [
+            [1.255_528, -0.076_749, -0.178_779],
+            [-0.078_411, 0.930_809, 0.147_602],
+            [0.004_733, 0.691_367, 0.303_9],
+        ],
+    };
+
+    let linear = [srgb_to_linear(color[0]), srgb_to_linear(color[1]), srgb_to_linear(color[2])];
+    let project = |row: [f32; 3]| linear_to_srgb(row[0] * linear[0] + row[1] * linear[1] + row[2] * linear[2]);
+
+    Rgba([project(matrix[0]), project(matrix[1]), project(matrix[2]), color[3]])
+}
+
+/// Converts an RGB color to HSL `[hue in degrees, saturation, lightness]`
+/// with saturation and lightness in the range 0.0 to 1.0
+pub fn rgb_to_hsl(color: &Rgba<u8>) -> [f32; 3] {
+    let r = color[0] as f32 / 255.0;
+    let g = color[1] as f32 / 255.0;
+    let b = color[2] as f32 / 255.0;
+
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let lightness = (max + min) / 2.0;
+    let delta = max - min;
+
+    if delta == 0.0 {
+        return [0.0, 0.0, lightness];
+    }
+
+    let saturation = delta / (1.0 - (2.0 * lightness - 1.0).abs());
+    let hue = if max == r {
+        60.0 * ((g - b) / delta).rem_euclid(6.0)
+    } else if max == g {
+        60.0 * ((b - r) / delta + 2.0)
+    } else {
+        60.0 * ((r - g) / delta + 4.0)
+    };
+
+    [hue, saturation.min(1.0), lightness]
+}
+
+/// Converts an HSL value back to an RGB color with the given alpha
+pub fn hsl_to_rgb(hsl: [f32; 3], alpha: u8) -> Rgba<u8> {
+    let [hue, saturation, lightness] = hsl;
+    let saturation = saturation.clamp(0.0, 1.0);
+    let lightness = lightness.clamp(0.0, 1.0);
+
+    let chroma = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation;
+    let sector = hue.rem_euclid(360.0) / 60.0;
+    let x = chroma * (1.0 - (sector.rem_euclid(2.0) - 1.0).abs());
+    let (r, g, b) = match sector as u32 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    let m = lightness - chroma / 2.0;
+    let channel = |v: f32| ((v + m) * 255.0).round().clamp(0.0, 255.0) as u8;
+
+    Rgba([channel(r), channel(g), channel(b), alpha])
+}
@@ -1,7 +1,19 @@
 use crate::error::{PixelatorError, Result};
+use crate::processor::HEXAGONAL_ROW_HEIGHT_FACTOR;
+use image::Rgba;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+// Millimeters in one inch, for screen frequencies given per inch
+const MM_PER_INCH: f32 = 25.4;
+
+// Relative difference between the output and image aspect ratios that is
+// still treated as matching, to absorb rounding in hand-entered dimensions
+const ASPECT_RATIO_TOLERANCE: f32 = 0.01;
 
 /// Configuration for the Pixelator image processor
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct PixelatorConfig {
     pub circle_diameter: f32,
     pub circle_spacing: f32,
@@ -12,6 +24,74 @@ pub struct PixelatorConfig {
     pub render_mode: RenderMode,
     pub max_dot_size: f32,  // Maximum dot size for halftone mode
     pub min_dot_size: f32,  // Minimum dot size for halftone mode
+    pub supersample: u8,    // Sub-samples per axis when averaging each dot's color
+    pub path_merge_by_color: bool,  // Emit one <path> per color instead of individual circles
+    pub color_merge_tolerance: u8,  // Emit colors within this RGB distance as one fill (0 disables)
+    pub palette_size: Option<usize>,  // Quantize dot colors to this many palette entries
+    pub palette_dither: Option<Vec<[u8; 4]>>,  // Fixed RGBA palette dots are dithered onto
+    pub quantize_space: QuantizeSpace,
+    pub halftone_curve: HalftoneCurve,  // Response curve applied before dot size interpolation
+    pub origin: Origin,  // Where (0,0) lands in the emitted coordinates
+    pub colorblind_safe: Option<CbType>,  // Remap the quantized palette to colorblind-safe colors
+    pub brightness_metric: BrightnessMetric,  // How sampled colors are reduced to a tone value
+    pub size_source: SizeSource,  // Channel halftone dot sizes follow
+    pub max_elements: Option<usize>,  // Widen spacing so the dot count stays under this cap
+    pub directional_sample: Option<DirectionalSample>,  // Average along a streak instead of a circle
+    pub grid_fit: GridFit,  // How grid mode rounds the column/row count to the image size
+    pub background_rect: bool,  // Draw the background as a <rect> instead of a CSS style
+    pub threads: usize,  // Worker threads for sampling (0 uses the global pool)
+    pub parallel_chunk_rows: Option<usize>,  // Rows each parallel sampling task processes (None lets rayon split)
+    pub dither: DitherMode,  // Binarize brightness before halftone dot sizing
+    pub margin_mm: f32,  // Blank margin added around the dot field on every side
+    pub scale_bar_mm: Option<f32>,  // Physical length of a scale bar drawn in the bottom margin
+    pub sharpen: f32,  // Unsharp mask amount applied before sampling (0 disables)
+    pub pixel_art: bool,  // Point-sample exact source pixels, snapped to pixel centers
+    pub gradient_dots: bool,  // Fill color dots with a radial gradient toward their neighbors
+    pub y_up: bool,  // Flip Y so +Y points up in machine (G-code) output
+    pub background_pattern: Option<PatternKind>,  // Repeating texture drawn over the background
+    pub size_mask: Option<PathBuf>,  // Grayscale image scaling dot sizes per position
+    pub normalized_coordinates: bool,  // Emit SVG coordinates in 0..1 relative to the longer image side
+    pub clip_mask: Option<PathBuf>,  // Black/white image; dots are kept only on white areas
+    pub paint_order: PaintOrder,  // Order dots are emitted in, by brightness
+    pub dot_size_levels: Option<u8>,  // Snap dot sizes to this many discrete steps
+    pub bead_sizes: Option<Vec<f32>>,  // Snap dot sizes to the nearest of these diameters, ascending
+    pub alpha_in_brightness: bool,  // Composite brightness over the background tone by alpha
+    pub min_gap: Option<f32>,  // Minimum edge-to-edge gap between neighboring dots
+    pub seed: Option<u64>,  // Global seed for every randomized feature (None uses entropy)
+    pub jitter: f32,  // Maximum random offset of each dot center in pixels
+    pub jitter_seed: Option<u64>,  // Seed for jitter only, overriding the global seed
+    pub assume_srgb: bool,  // Ignore embedded ICC profiles instead of converting to sRGB
+    pub coverage_sampling: bool,  // Weight source pixels by the fraction of their area inside each dot
+    pub scanline_grouping: bool,  // Emit dots in a <g> per row, alternating direction row by row
+    pub mm_decimals: usize,  // Decimal places kept in the SVG's mm width and height
+    pub lpi: Option<f32>,  // Screen frequency in lines per inch of output, overriding the spacing
+    pub normalize_dot_sizes: bool,  // Stretch halftone dot sizes to span the full size range
+    pub tile_image: Option<PathBuf>,  // Sprite drawn tinted in place of each color dot
+    pub edge_mode: EdgeMode,  // How sampling windows that cross the image border are handled
+    pub svg_y_up: bool,  // Emit SVG content in +Y-up coordinates under a flipping wrapper group
+    pub min_visible_radius: Option<f32>,  // Smallest radius emitted for color dots, in pixels
+    pub glow: Option<f32>,  // Gaussian blur radius of a glow around the dots
+    pub page_mm: Option<(f32, f32)>,  // Page the output is fitted and centered on, in millimeters
+    pub background_gradient: Option<BackgroundGradient>,  // Linear gradient filling the background
+    pub saturation_scale: f32,  // Multiplier for the HSL saturation of sampled colors
+    pub hex_orientation: HexOrientation,  // Whether hexagonal mode staggers rows or columns
+    pub respect_source_dpi: bool,  // Convert the physical dot size to pixels at the image's stored DPI
+    pub physical_dot_mm: Option<(f32, f32)>,  // Circle diameter and spacing in millimeters of the output or source image
+    pub dot_gain: Option<f32>,  // Expected print dot gain at 50% coverage, in percent, compensated in halftone sizes
+    pub outline_only: Option<f32>,  // Stroke width of unfilled dot outlines, in pixels
+    pub edge_fade: Option<f32>,  // Width in pixels of the border band where dots taper off
+    pub distortion_policy: DistortionPolicy,  // What happens when output mm distort the image aspect ratio
+    pub overlap_policy: OverlapPolicy,  // What happens when the largest dots overlap their neighbors
+    pub guide_grid: Option<(usize, String)>,  // Guide line interval in cells and the line color
+    pub importance_dots: Option<usize>,  // Keep this many dots, chosen where the image has the most detail
+    pub reference_layer: Option<f32>,  // Opacity of the source image embedded behind the dots
+    pub snap_to_grid_mm: Option<f32>,  // Pitch in output millimeters dot centers are rounded to
+    pub reveal_animation: Option<f32>,  // Seconds over which dots animate in one after another
+    pub registration_marks: bool,  // Draw crosshair registration marks at the corners of the canvas
+    pub svg_input_dpi: f32,  // Resolution SVG input files are rasterized at before sampling
+    pub premultiplied_alpha: bool,  // Weight colors by alpha when averaging, so transparent pixels don't bleed in
+    pub fill_interstices: bool,  // Add smaller dots in the gaps between four neighboring grid dots
+    pub spot_color_bands: Option<Vec<String>>,  // Spot ink per brightness band, darkest band first
 }
 
 impl Default for PixelatorConfig {
@@ -26,12 +106,80 @@ impl Default for PixelatorConfig {
             render_mode: RenderMode::Color,
             max_dot_size: 10.0,
             min_dot_size: 1.0,
+            supersample: 1,
+            path_merge_by_color: false,
+            color_merge_tolerance: 0,
+            palette_size: None,
+            palette_dither: None,
+            quantize_space: QuantizeSpace::Rgb,
+            halftone_curve: HalftoneCurve::Linear,
+            origin: Origin::TopLeft,
+            colorblind_safe: None,
+            brightness_metric: BrightnessMetric::Luminance,
+            size_source: SizeSource::Brightness,
+            max_elements: None,
+            directional_sample: None,
+            grid_fit: GridFit::Floor,
+            background_rect: false,
+            threads: 0,
+            parallel_chunk_rows: None,
+            dither: DitherMode::None,
+            margin_mm: 0.0,
+            scale_bar_mm: None,
+            sharpen: 0.0,
+            pixel_art: false,
+            gradient_dots: false,
+            y_up: false,
+            background_pattern: None,
+            size_mask: None,
+            normalized_coordinates: false,
+            clip_mask: None,
+            paint_order: PaintOrder::AsSampled,
+            dot_size_levels: None,
+            bead_sizes: None,
+            alpha_in_brightness: false,
+            min_gap: None,
+            seed: None,
+            jitter: 0.0,
+            jitter_seed: None,
+            assume_srgb: false,
+            coverage_sampling: false,
+            scanline_grouping: false,
+            mm_decimals: 2,
+            lpi: None,
+            normalize_dot_sizes: false,
+            tile_image: None,
+            edge_mode: EdgeMode::Clamp,
+            svg_y_up: false,
+            min_visible_radius: None,
+            glow: None,
+            page_mm: None,
+            background_gradient: None,
+            saturation_scale: 1.0,
+            hex_orientation: HexOrientation::PointyTop,
+            respect_source_dpi: false,
+            physical_dot_mm: None,
+            dot_gain: None,
+            outline_only: None,
+            edge_fade: None,
+            distortion_policy: DistortionPolicy::Allow,
+            overlap_policy: OverlapPolicy::Allow,
+            guide_grid: None,
+            importance_dots: None,
+            reference_layer: None,
+            snap_to_grid_mm: None,
+            reveal_animation: None,
+            registration_marks: false,
+            svg_input_dpi: crate::vector::CSS_DPI,
+            premultiplied_alpha: true,
+            fill_interstices: false,
+            spot_color_bands: None,
         }
     }
 }
 
 /// Sampling mode for pixel extraction
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum SampleMode {
     /// Regular grid pattern
     Grid,
@@ -39,17 +187,63 @@ pub enum SampleMode {
     Hexagonal,
 }
 
+/// Orientation of the hexagons in the hexagonal sampling pattern
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum HexOrientation {
+    /// Horizontal rows, every other row shifted right by half a cell
+    PointyTop,
+    /// Vertical columns, every other column shifted down by half a cell
+    FlatTop,
+}
+
 /// Rendering style for the output
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum RenderMode {
     /// Full color circles (original mode)
     Color,
     /// Halftone effect with variable dot sizes
     Halftone(HalftoneStyle),
+    /// Full size circles keeping each color's hue, with saturation driven by brightness
+    SaturationMap,
+    /// Each sample point's Voronoi cell filled with its sampled color
+    Voronoi,
+    /// Stochastic screen: fixed-size black dots whose density per cell follows darkness
+    FmHalftone { dot_size: f32 },
+    /// Parallel black lines at `angle` degrees, `frequency` lines per dot cell,
+    /// whose stroke width follows darkness
+    LineScreen { angle: f32, frequency: f32 },
+    /// Randomly scattered fixed-size black dots whose density per cell follows
+    /// darkness; `seed` overrides the global seed for this mode
+    Stipple { dot_size: f32, seed: Option<u64> },
+    /// Parallel black strokes in each cell, more of them in darker cells
+    Hatch(HatchOrientation),
+    /// Bare dot centers as tiny unstyled markers, for tools that only need coordinates
+    PointCloud,
+    /// Printable coloring worksheet: outlined cells labeled with palette
+    /// numbers, plus a legend mapping each number to its color
+    ColorByNumber,
+    /// Nested filled contours of `levels` darkness bands, one path per level
+    /// like a topographic map, for cutting and stacking paper layers
+    Contours { levels: u8 },
+    /// Two-tone silhouette: a full-size black dot in every cell darker than
+    /// `level` (0.0 to 1.0 brightness) and nothing elsewhere, e.g. for vinyl
+    Threshold { level: f32 },
+    /// Full size squares in the sampled colors whose corners round off with
+    /// brightness: sharp squares in the shadows, circles in the highlights
+    MorphShape,
+}
+
+/// Direction of the strokes in hatch rendering
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum HatchOrientation {
+    /// All strokes at the same angle in degrees
+    Fixed(f32),
+    /// Strokes run along local edges, perpendicular to the brightness gradient
+    FollowStructure,
 }
 
 /// Halftone rendering style options
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum HalftoneStyle {
     /// Black dots on white background
     BlackOnWhite,
@@ -57,6 +251,176 @@ pub enum HalftoneStyle {
     WhiteOnBlack,
 }
 
+/// Anchor point used as the (0,0) origin of the emitted coordinates
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Origin {
+    /// Image top-left corner (standard SVG coordinates)
+    TopLeft,
+    /// Image center
+    Center,
+    /// Image bottom-left corner
+    BottomLeft,
+}
+
+/// How the grid column and row counts are fitted to the image size
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum GridFit {
+    /// Only whole cells that fit inside the image (may leave a blank strip)
+    Floor,
+    /// Nearest whole number of cells
+    Round,
+    /// Cover the image fully; the last dot may overhang and is clamped to the edge
+    Ceil,
+    /// Recompute spacing so dots fill the image exactly edge to edge
+    Stretch,
+}
+
+/// Handling of dot sampling windows that extend past the image border
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum EdgeMode {
+    /// Average only the part of the window inside the image
+    Clamp,
+    /// Reflect the window at the border so edge dots see a full neighborhood
+    Mirror,
+    /// Drop dots whose window would extend beyond the image
+    Skip,
+}
+
+/// Reaction to output dimensions whose aspect ratio differs from the image's
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum DistortionPolicy {
+    /// Stretch the output silently
+    Allow,
+    /// Print a warning to stderr and continue
+    Warn,
+    /// Fail with an `InvalidConfig` error
+    Reject,
+}
+
+/// Reaction to dots large enough to overlap their neighbors
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum OverlapPolicy {
+    /// Let dots overlap silently
+    Allow,
+    /// Print a warning to stderr and continue
+    Warn,
+    /// Fail with an `InvalidConfig` error
+    Reject,
+}
+
+/// Linear color gradient drawn as the background
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BackgroundGradient {
+    /// Color stops as `(offset, color)`, offsets sorted within 0..=1
+    pub stops: Vec<(f32, String)>,
+    /// Gradient direction in degrees, clockwise from the positive x axis
+    pub angle: f32,
+}
+
+/// Line segment used for motion-blur-style directional sampling
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct DirectionalSample {
+    /// Streak direction in degrees, clockwise from the positive x axis
+    pub angle: f32,
+    /// Streak length in pixels
+    pub length: f32,
+}
+
+/// Metric used to reduce a sampled color to a brightness value
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum BrightnessMetric {
+    /// Weighted luminance of the RGB channels (ITU-R BT.709)
+    Luminance,
+    /// CIE L* perceived lightness, for perceptually uniform tonal steps
+    CieLStar,
+}
+
+/// Channel that drives halftone dot sizes
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum SizeSource {
+    /// Tone from the brightness metric, inverted for black-on-white dots
+    Brightness,
+    /// Opacity: opaque areas get the largest dots, transparent ones the smallest
+    Alpha,
+    /// HSL saturation: vivid areas get the largest dots, grays the smallest
+    Saturation,
+}
+
+/// Dithering applied to brightness before halftone dot sizing
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum DitherMode {
+    /// Continuous tone, no dithering
+    None,
+    /// Floyd-Steinberg error diffusion across the dot lattice
+    FloydSteinberg,
+    /// Ordered dithering with a tiled blue-noise threshold matrix
+    BlueNoise,
+}
+
+/// Order in which dots are painted, which decides what shows where dots overlap
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum PaintOrder {
+    /// Keep the sampling order (row by row)
+    AsSampled,
+    /// Paint from brightest to darkest so dark dots end up on top
+    DarkestLast,
+    /// Paint from darkest to brightest so light dots end up on top
+    LightestLast,
+}
+
+/// Repeating texture drawn behind the dots
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum PatternKind {
+    /// Small dots centered in each tile
+    Dots,
+    /// Thin horizontal and vertical grid lines
+    Grid,
+    /// Diagonal hatching
+    Diagonal,
+}
+
+/// Response curve mapping tone to halftone dot size
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum HalftoneCurve {
+    /// Dot size grows linearly with tone
+    Linear,
+    /// Tone is raised to the given power before sizing
+    Gamma(f32),
+    /// S-shaped curve that softens highlights and shadows
+    Sine,
+}
+
+impl HalftoneCurve {
+    /// Applies the curve to a tone value in the range 0.0 to 1.0
+    pub fn apply(&self, tone: f32) -> f32 {
+        match self {
+            HalftoneCurve::Linear => tone,
+            HalftoneCurve::Gamma(gamma) => tone.powf(*gamma),
+            HalftoneCurve::Sine => 0.5 - 0.5 * (std::f32::consts::PI * tone).cos(),
+        }
+    }
+}
+
+/// Color space used when clustering colors for palette quantization
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum QuantizeSpace {
+    /// Cluster directly on RGB channel values
+    Rgb,
+    /// Cluster in CIELAB for perceptually even palettes
+    Lab,
+}
+
+/// Color vision deficiency targeted by colorblind-safe palette remapping
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum CbType {
+    /// Red-green deficiency with missing green cones
+    Deuteranopia,
+    /// Red-green deficiency with missing red cones
+    Protanopia,
+    /// Blue-yellow deficiency with missing blue cones
+    Tritanopia,
+}
+
 impl PixelatorConfig {
     /// Creates a new configuration with the specified circle dimensions
     /// 
@@ -79,16 +443,112 @@ impl PixelatorConfig {
         Ok(Self {
             circle_diameter,
             circle_spacing,
-            output_width_mm: None,
-            output_height_mm: None,
-            background_color: None,
-            sample_mode: SampleMode::Grid,
-            render_mode: RenderMode::Color,
             max_dot_size: circle_diameter,
             min_dot_size: circle_diameter * 0.1,
+            ..Self::default()
         })
     }
     
+    /// Newspaper look: black-on-white halftone on a hexagonal grid
+    /// 
+    /// The largest dots just touch at the 6 pixel pitch.
+    pub fn preset_newspaper() -> Self {
+        Self::new(6.0, 0.0)
+            .and_then(|c| c.with_halftone_range(0.3, 6.0))
+            .expect("newspaper preset is valid")
+            .with_sample_mode(SampleMode::Hexagonal)
+            .with_render_mode(RenderMode::Halftone(HalftoneStyle::BlackOnWhite))
+    }
+    
+    /// Pen plotter look: halftone circles with a capped dot count so plots
+    /// finish in reasonable time
+    pub fn preset_pen_plotter() -> Self {
+        Self::new(3.0, 1.0)
+            .and_then(|c| c.with_halftone_range(0.5, 3.0))
+            .and_then(|c| c.with_max_elements(20_000))
+            .expect("pen plotter preset is valid")
+            .with_render_mode(RenderMode::Halftone(HalftoneStyle::BlackOnWhite))
+    }
+    
+    /// Poster look: large anti-aliased color dots with a reduced palette on white
+    pub fn preset_poster() -> Self {
+        Self::new(12.0, 2.0)
+            .and_then(|c| c.with_supersample(3))
+            .and_then(|c| c.with_palette_size(12))
+            .expect("poster preset is valid")
+            .with_sample_mode(SampleMode::Hexagonal)
+            .with_quantize_space(QuantizeSpace::Lab)
+            .with_background_color("white".to_string())
+            .with_background_rect(true)
+    }
+    
+    /// Changes the circle diameter and spacing, as validated by `new`
+    /// Unlike `new`, the halftone dot range is left unchanged
+    pub fn with_circle_size(mut self, circle_diameter: f32, circle_spacing: f32) -> Result<Self> {
+        let sized = Self::new(circle_diameter, circle_spacing)?;
+        self.circle_diameter = sized.circle_diameter;
+        self.circle_spacing = sized.circle_spacing;
+        Ok(self)
+    }
+    
+    /// Sizes dots from the resolution stored in the source image
+    /// 
+    /// With a physical dot size set by `with_physical_dot_size`, the circle
+    /// diameter and spacing are computed per image from its PNG or JPEG DPI
+    /// metadata. Images without DPI metadata keep the configured pixel
+    /// sizes, with a warning printed to stderr.
+    pub fn with_respect_source_dpi(mut self, enabled: bool) -> Self {
+        self.respect_source_dpi = enabled;
+        self
+    }
+    
+    /// Sets the circle diameter and spacing in millimeters
+    /// 
+    /// The sizes are millimeters of the output, converted to pixels per image
+    /// from the output dimensions (or the fitted page) and the image width.
+    /// With `with_respect_source_dpi` they are millimeters of the source
+    /// image instead, converted at its DPI. Either may be configured before
+    /// or after this call; sampling fails with an `InvalidConfig` error if
+    /// neither is.
+    pub fn with_physical_dot_size(mut self, diameter_mm: f32, spacing_mm: f32) -> Result<Self> {
+        if diameter_mm <= 0.0 || spacing_mm < 0.0 {
+            return Err(PixelatorError::InvalidConfig(
+                "Physical dot diameter must be positive and spacing non-negative".to_string(),
+            ));
+        }
+        self.physical_dot_mm = Some((diameter_mm, spacing_mm));
+        Ok(self)
+    }
+    
+    /// Checks that a physical dot size has output dimensions or source DPI
+    /// to be converted with
+    pub fn check_physical_dot_size(&self) -> Result<()> {
+        if self.physical_dot_mm.is_some()
+            && self.output_width_mm.is_none()
+            && self.page_mm.is_none()
+            && !self.respect_source_dpi
+        {
+            return Err(PixelatorError::InvalidConfig(
+                "Physical dot size requires output dimensions in millimeters or source DPI".to_string(),
+            ));
+        }
+        Ok(())
+    }
+    
+    /// Returns the configuration with the physical dot size converted to
+    /// pixels at `dpi`, or an unchanged copy if no physical size is set
+    /// 
+    /// As with `with_circle_size`, the halftone dot range is left unchanged.
+    pub fn at_source_dpi(&self, dpi: f32) -> PixelatorConfig {
+        let mut config = self.clone();
+        if let Some((diameter_mm, spacing_mm)) = self.physical_dot_mm {
+            let pixels_per_mm = dpi / MM_PER_INCH;
+            config.circle_diameter = diameter_mm * pixels_per_mm;
+            config.circle_spacing = spacing_mm * pixels_per_mm;
+        }
+        config
+    }
+    
     /// Sets the output dimensions in millimeters for printing
     pub fn with_output_dimensions(mut self, width_mm: f32, height_mm: f32) -> Result<Self> {
         if width_mm <= 0.0 || height_mm <= 0.0 {
@@ -101,6 +561,37 @@ impl PixelatorConfig {
         Ok(self)
     }
     
+    /// Sets the dot pitch as a print screen frequency in lines per inch
+    /// 
+    /// The circle spacing is computed per image from the output width in mm
+    /// and the image width in pixels, so output dimensions or a page to fit
+    /// must be set first.
+    /// If the pitch is smaller than the circle diameter, the diameter is
+    /// reduced to the pitch and a warning is printed to stderr.
+    pub fn with_lpi(mut self, lines_per_inch: f32) -> Result<Self> {
+        if lines_per_inch <= 0.0 {
+            return Err(PixelatorError::InvalidConfig(
+                "Lines per inch must be positive".to_string(),
+            ));
+        }
+        if (self.output_width_mm.is_none() || self.output_height_mm.is_none()) && self.page_mm.is_none() {
+            return Err(PixelatorError::InvalidConfig(
+                "Lines per inch requires output dimensions in millimeters".to_string(),
+            ));
+        }
+        self.lpi = Some(lines_per_inch);
+        Ok(self)
+    }
+    
+    /// Sets how many decimals the SVG's mm `width` and `height` are rounded to
+    /// 
+    /// Dimensions derived from an aspect ratio can otherwise carry float
+    /// noise such as `149.99997mm`. Trailing zeros are dropped either way.
+    pub fn with_mm_decimals(mut self, decimals: usize) -> Self {
+        self.mm_decimals = decimals;
+        self
+    }
+    
     /// Sets the background color of the SVG
     pub fn with_background_color(mut self, color: String) -> Self {
         self.background_color = Some(color);
@@ -113,9 +604,243 @@ impl PixelatorConfig {
         self
     }
     
+    /// Sets whether the hexagonal pattern staggers rows (pointy-top hexagons,
+    /// the default) or columns (flat-top hexagons)
+    pub fn with_hex_orientation(mut self, orientation: HexOrientation) -> Self {
+        self.hex_orientation = orientation;
+        self
+    }
+    
     /// Returns the total spacing between circle centers
+    /// 
+    /// With a minimum gap set, this is widened when needed so the largest
+    /// dot still leaves at least that gap to its neighbors.
     pub fn get_total_spacing(&self) -> f32 {
-        self.circle_diameter + self.circle_spacing
+        let spacing = self.circle_diameter + self.circle_spacing;
+        match self.min_gap {
+            Some(gap) => spacing.max(self.largest_dot_size() + gap),
+            None => spacing,
+        }
+    }
+    
+    /// Returns the largest diameter a dot can be drawn at
+    pub fn largest_dot_size(&self) -> f32 {
+        match self.render_mode {
+            RenderMode::Halftone(_) => self.max_dot_size.max(self.min_dot_size),
+            _ => self.circle_diameter,
+        }
+    }
+    
+    /// Returns the number of dot columns and rows produced for an image of the given size
+    /// 
+    /// In hexagonal mode the column count is the length of the longest row,
+    /// since odd rows are offset and may hold one dot fewer; with flat-top
+    /// hexagons the same holds for the row count and columns. The grid fit
+    /// only applies to grid mode.
+    pub fn grid_dimensions(&self, img_width: u32, img_height: u32) -> (usize, usize) {
+        let total_spacing = self.get_total_spacing();
+        match self.sample_mode {
+            SampleMode::Grid => {
+                let fit = |extent: u32| {
+                    let cells = extent as f32 / total_spacing;
+                    match self.grid_fit {
+                        GridFit::Floor => cells.floor() as usize,
+                        GridFit::Round => cells.round() as usize,
+                        GridFit::Ceil => cells.ceil() as usize,
+                        GridFit::Stretch => (cells.round() as usize).max(1),
+                    }
+                };
+                (fit(img_width), fit(img_height))
+            }
+            SampleMode::Hexagonal => {
+                let lines = self.hex_line_count(img_width, img_height);
+                let longest = (0..lines.min(2))
+                    .map(|line| self.hex_line_dots(img_width, img_height, line))
+                    .max()
+                    .unwrap_or(0);
+                match self.hex_orientation {
+                    HexOrientation::PointyTop => (longest, lines),
+                    HexOrientation::FlatTop => (lines, longest),
+                }
+            }
+        }
+    }
+    
+    /// Returns the center-to-center step between grid columns and rows
+    /// 
+    /// This is the total spacing unless `GridFit::Stretch` widens or narrows
+    /// it so the outer dot centers sit half a diameter from the image edges.
+    pub fn grid_steps(&self, img_width: u32, img_height: u32) -> (f32, f32) {
+        let total_spacing = self.get_total_spacing();
+        if self.grid_fit != GridFit::Stretch {
+            return (total_spacing, total_spacing);
+        }
+        
+        let (cols, rows) = self.grid_dimensions(img_width, img_height);
+        // Stretching never narrows the step below the minimum gap
+        let min_step = self.min_gap.map_or(0.0, |gap| self.largest_dot_size() + gap);
+        let stretch = |extent: u32, count: usize| {
+            if count > 1 {
+                ((extent as f32 - self.circle_diameter).max(0.0) / (count - 1) as f32).max(min_step)
+            } else {
+                total_spacing
+            }
+        };
+        (stretch(img_width, cols), stretch(img_height, rows))
+    }
+    
+    /// Returns the number of dots in the given row of the pointy-top
+    /// hexagonal pattern, for an image `img_width` pixels wide
+    /// 
+    /// Flat-top columns are laid out the same way along the image height.
+    pub fn hex_row_columns(&self, img_width: u32, row: usize) -> usize {
+        let total_spacing = self.get_total_spacing();
+        let offset = if row % 2 == 0 { 0.0 } else { total_spacing / 2.0 };
+        
+        let mut cols = 0;
+        while (cols as f32) * total_spacing + offset + self.circle_diameter / 2.0 < img_width as f32 {
+            cols += 1;
+        }
+        cols
+    }
+    
+    /// Returns the number of staggered lines in the hexagonal pattern: rows
+    /// for pointy-top hexagons, columns for flat-top ones
+    pub fn hex_line_count(&self, img_width: u32, img_height: u32) -> usize {
+        let across = match self.hex_orientation {
+            HexOrientation::PointyTop => img_height,
+            HexOrientation::FlatTop => img_width,
+        };
+        // Every line whose centers fall inside the image, as for the dots
+        // along a line, so a partial last line still covers the far edge
+        let line_spacing = self.get_total_spacing() * HEXAGONAL_ROW_HEIGHT_FACTOR;
+        let span = across as f32 - self.circle_diameter / 2.0;
+        if span <= 0.0 {
+            return 0;
+        }
+        (span / line_spacing).ceil() as usize
+    }
+    
+    /// Returns the number of dots in the given line of the hexagonal pattern
+    pub fn hex_line_dots(&self, img_width: u32, img_height: u32, line: usize) -> usize {
+        match self.hex_orientation {
+            HexOrientation::PointyTop => self.hex_row_columns(img_width, line),
+            HexOrientation::FlatTop => self.hex_row_columns(img_height, line),
+        }
+    }
+    
+    /// Returns the dot pitch of the printed output in dots per inch
+    /// 
+    /// `source_px` pixels of the image, spanning `output_mm` millimeters of
+    /// output, hold one dot per total spacing. This is the inverse of
+    /// `with_lpi`, so a configuration resolved for a screen frequency reports
+    /// that frequency back.
+    pub fn output_dpi(&self, source_px: u32, output_mm: f32) -> f32 {
+        let dots = source_px as f32 / self.get_total_spacing();
+        dots * MM_PER_INCH / output_mm
+    }
+    
+    /// Returns the total number of dots produced for an image of the given size
+    pub fn dot_count(&self, img_width: u32, img_height: u32) -> usize {
+        let (cols, rows) = self.grid_dimensions(img_width, img_height);
+        match self.sample_mode {
+            SampleMode::Grid => cols * rows,
+            SampleMode::Hexagonal => (0..self.hex_line_count(img_width, img_height))
+                .map(|line| self.hex_line_dots(img_width, img_height, line))
+                .sum(),
+        }
+    }
+    
+    /// Returns a copy with every length given in image pixels multiplied by `factor`
+    /// 
+    /// Used for previews and normalized coordinates, so new pixel-length
+    /// settings only need scaling here.
+    pub(crate) fn scale_pixel_lengths(&self, factor: f32) -> PixelatorConfig {
+        let mut scaled = self.clone();
+        scaled.circle_diameter *= factor;
+        scaled.circle_spacing *= factor;
+        scaled.min_dot_size *= factor;
+        scaled.max_dot_size *= factor;
+        if let Some(sizes) = &mut scaled.bead_sizes {
+            sizes.iter_mut().for_each(|size| *size *= factor);
+        }
+        scaled.min_gap = scaled.min_gap.map(|gap| gap * factor);
+        scaled.jitter *= factor;
+        scaled.min_visible_radius = scaled.min_visible_radius.map(|radius| radius * factor);
+        scaled.glow = scaled.glow.map(|radius| radius * factor);
+        scaled.outline_only = scaled.outline_only.map(|width| width * factor);
+        scaled.edge_fade = scaled.edge_fade.map(|width| width * factor);
+        if let Some(streak) = &mut scaled.directional_sample {
+            streak.length *= factor;
+        }
+        if let RenderMode::FmHalftone { dot_size } | RenderMode::Stipple { dot_size, .. } = &mut scaled.render_mode {
+            *dot_size *= factor;
+        }
+        scaled
+    }
+    
+    /// Returns the configuration to use for an image of the given size
+    /// 
+    /// If the projected dot count exceeds the element cap, the circle spacing
+    /// is widened until the dots fit; `resolve_with_warnings` also reports the
+    /// adjusted spacing.
+    pub fn resolve_for_image(&self, img_width: u32, img_height: u32) -> PixelatorConfig {
+        self.resolve_with_warnings(img_width, img_height).0
+    }
+    
+    /// Like `resolve_for_image`, also returning a message for each adjustment
+    /// made to fit the image
+    pub fn resolve_with_warnings(&self, img_width: u32, img_height: u32) -> (PixelatorConfig, Vec<String>) {
+        let mut resolved = self.clone();
+        let mut warnings = Vec::new();
+        
+        if let Some((page_width, page_height)) = self.page_mm {
+            // Largest size with the image's aspect ratio inside the margins
+            let scale = ((page_width - 2.0 * self.margin_mm) / img_width.max(1) as f32)
+                .min((page_height - 2.0 * self.margin_mm) / img_height.max(1) as f32);
+            resolved.output_width_mm = Some(img_width as f32 * scale);
+            resolved.output_height_mm = Some(img_height as f32 * scale);
+        }
+        
+        if let (Some((diameter_mm, spacing_mm)), Some(width_mm), false) =
+            (self.physical_dot_mm, resolved.output_width_mm, self.respect_source_dpi) {
+            let pixels_per_mm = img_width as f32 / width_mm;
+            resolved.circle_diameter = diameter_mm * pixels_per_mm;
+            resolved.circle_spacing = spacing_mm * pixels_per_mm;
+        }
+        
+        if let (Some(lpi), Some(width_mm)) = (self.lpi, resolved.output_width_mm) {
+            let total_spacing = MM_PER_INCH * img_width as f32 / (lpi * width_mm);
+            if total_spacing < resolved.circle_diameter {
+                warnings.push(format!(
+                    "{} LPI gives a {:.2} pixel pitch; circle diameter reduced from {:.2}",
+                    lpi, total_spacing, resolved.circle_diameter
+                ));
+                resolved.circle_diameter = total_spacing;
+            }
+            resolved.circle_spacing = total_spacing - resolved.circle_diameter;
+        }
+        
+        if let Some(max) = self.max_elements {
+            let projected = resolved.dot_count(img_width, img_height);
+            if projected > max {
+                // Dot count scales with the inverse square of the spacing
+                let mut total_spacing = resolved.get_total_spacing() * (projected as f32 / max as f32).sqrt();
+                loop {
+                    resolved.circle_spacing = total_spacing - resolved.circle_diameter;
+                    if resolved.dot_count(img_width, img_height) <= max {
+                        break;
+                    }
+                    total_spacing *= 1.01;
+                }
+                warnings.push(format!(
+                    "{} dots exceed the limit of {}; circle spacing increased to {:.2} pixels",
+                    projected, max, resolved.circle_spacing
+                ));
+            }
+        }
+        
+        (resolved, warnings)
     }
     
     /// Sets the rendering mode
@@ -140,4 +865,877 @@ impl PixelatorConfig {
         self.max_dot_size = max_size;
         Ok(self)
     }
+    
+    /// Shrinks halftone dots to compensate for ink spreading on paper
+    /// 
+    /// `percent` is the dot gain measured at 50% coverage, so 15 means a 50%
+    /// dot prints as 65%. Gain follows the standard parabolic curve, which
+    /// is largest in the midtones and vanishes at 0% and 100%; each dot's
+    /// cell coverage is mapped through the inverse of that curve, so
+    /// midtone dots shrink the most.
+    pub fn with_dot_gain(mut self, percent: f32) -> Result<Self> {
+        if !(0.0..50.0).contains(&percent) {
+            return Err(PixelatorError::InvalidConfig(
+                "Dot gain must be at least 0% and below 50%".to_string(),
+            ));
+        }
+        self.dot_gain = Some(percent);
+        Ok(self)
+    }
+    
+    /// Sets the supersampling factor used when averaging dot colors
+    /// 
+    /// Each dot's cell is sampled at `factor x factor` sub-positions and the
+    /// results are averaged, which reduces aliasing on fine textures at the
+    /// cost of speed. A factor of 1 keeps the single circular sample.
+    pub fn with_supersample(mut self, factor: u8) -> Result<Self> {
+        if factor == 0 {
+            return Err(PixelatorError::InvalidConfig(
+                "Supersample factor must be at least 1".to_string(),
+            ));
+        }
+        self.supersample = factor;
+        Ok(self)
+    }
+    
+    /// Weights each source pixel by how much of its area lies inside the dot
+    /// 
+    /// The default average includes a pixel fully or not at all depending on
+    /// whether its center is within the dot's integer radius, which skews
+    /// colors at small or fractional diameters. Coverage weighting blends
+    /// partially covered edge pixels in proportion, at some extra cost.
+    pub fn with_coverage_sampling(mut self, enabled: bool) -> Self {
+        self.coverage_sampling = enabled;
+        self
+    }
+    
+    /// Groups dots into one `<g class="scanline">` per row for raster plotters
+    /// 
+    /// Rows are emitted top to bottom and traversed boustrophedon style:
+    /// left to right, then right to left, and so on, so the pen never travels
+    /// back across the page between rows. This replaces the paint order and
+    /// applies to circle output in color and halftone modes.
+    pub fn with_scanline_grouping(mut self, enabled: bool) -> Self {
+        self.scanline_grouping = enabled;
+        self
+    }
+    
+    /// Stretches halftone dot sizes so the smallest and largest dots produced
+    /// span the full `[min_dot_size, max_dot_size]` range
+    /// 
+    /// This is histogram stretching on dot sizes: flat, low-contrast images
+    /// otherwise come out as uniformly mid-sized dots. Images of a single
+    /// tone are left unchanged.
+    pub fn with_dot_size_normalization(mut self, enabled: bool) -> Self {
+        self.normalize_dot_sizes = enabled;
+        self
+    }
+    
+    /// Enables merging all dots of the same color into a single `<path>`
+    /// 
+    /// Each dot becomes a subpath of arcs sharing the color's fill, which is
+    /// the most compact representation for palette art in color mode.
+    pub fn with_path_merge_by_color(mut self, enabled: bool) -> Self {
+        self.path_merge_by_color = enabled;
+        self
+    }
+    
+    /// Emits colors within Euclidean RGB distance `tolerance` as a shared fill
+    /// 
+    /// Colors are merged onto the first similar color seen during generation,
+    /// which cuts the number of distinct fills (and file size) for photos
+    /// without a full palette quantization. Applies to color output; 0 keeps
+    /// exact colors.
+    pub fn with_color_merge_tolerance(mut self, tolerance: u8) -> Self {
+        self.color_merge_tolerance = tolerance;
+        self
+    }
+    
+    /// Quantizes dot colors to a palette of at most `size` colors
+    pub fn with_palette_size(mut self, size: usize) -> Result<Self> {
+        if size == 0 {
+            return Err(PixelatorError::InvalidConfig(
+                "Palette size must be at least 1".to_string(),
+            ));
+        }
+        self.palette_size = Some(size);
+        Ok(self)
+    }
+    
+    /// Snaps dot colors to a fixed palette using Floyd–Steinberg error diffusion
+    /// 
+    /// Each dot takes its nearest palette color and passes the color error on
+    /// to its unvisited lattice neighbors, so gradients are rendered as a mix
+    /// of palette colors instead of flat bands. Nearness is measured in the
+    /// quantize space. Takes precedence over `palette_size`.
+    pub fn with_palette_dither(mut self, palette: Vec<Rgba<u8>>) -> Result<Self> {
+        if palette.is_empty() {
+            return Err(PixelatorError::InvalidConfig(
+                "Dither palette must have at least one color".to_string(),
+            ));
+        }
+        self.palette_dither = Some(palette.into_iter().map(|color| color.0).collect());
+        Ok(self)
+    }
+    
+    /// Sets the color space used to cluster colors during quantization
+    pub fn with_quantize_space(mut self, space: QuantizeSpace) -> Self {
+        self.quantize_space = space;
+        self
+    }
+    
+    /// Sets the response curve used to map tone to halftone dot size
+    pub fn with_halftone_curve(mut self, curve: HalftoneCurve) -> Result<Self> {
+        if let HalftoneCurve::Gamma(gamma) = curve {
+            if gamma <= 0.0 {
+                return Err(PixelatorError::InvalidConfig(
+                    "Halftone gamma must be positive".to_string(),
+                ));
+            }
+        }
+        self.halftone_curve = curve;
+        Ok(self)
+    }
+    
+    /// Sets the anchor used as the origin of emitted coordinates
+    /// The viewBox is shifted to match so the rendered image is unchanged
+    pub fn with_origin(mut self, origin: Origin) -> Self {
+        self.origin = origin;
+        self
+    }
+    
+    /// Remaps the quantized palette onto colors distinguishable under the given deficiency
+    /// 
+    /// Without an explicit palette size, colors are quantized to the size of
+    /// the safe palette first.
+    pub fn with_colorblind_safe(mut self, cb_type: CbType) -> Self {
+        self.colorblind_safe = Some(cb_type);
+        self
+    }
+    
+    /// Sets the metric used to compute brightness for halftone sizing
+    pub fn with_brightness_metric(mut self, metric: BrightnessMetric) -> Self {
+        self.brightness_metric = metric;
+        self
+    }
+    
+    /// Sets the channel halftone dot sizes follow
+    /// 
+    /// Alpha and saturation size dots the same way for both halftone styles:
+    /// the more opaque or saturated, the larger. Dithering still works from
+    /// brightness.
+    pub fn with_size_source(mut self, source: SizeSource) -> Self {
+        self.size_source = source;
+        self
+    }
+    
+    /// Guarantees at least `min_gap` pixels between the edges of neighboring dots
+    /// 
+    /// The spacing between dot centers is widened past `circle_spacing` when
+    /// the largest possible dot would otherwise come closer than this.
+    pub fn with_min_gap(mut self, min_gap: f32) -> Result<Self> {
+        if min_gap < 0.0 {
+            return Err(PixelatorError::InvalidConfig(
+                "Minimum gap must be non-negative".to_string(),
+            ));
+        }
+        self.min_gap = Some(min_gap);
+        Ok(self)
+    }
+    
+    /// Raises the emitted radius of color dots to at least `radius` pixels
+    /// 
+    /// Keeps tiny diameters, or dots shrunk by scaling down, from vanishing in
+    /// the output. Only the drawn radius changes; sampling still uses
+    /// `circle_diameter`.
+    pub fn with_min_visible_radius(mut self, radius: f32) -> Result<Self> {
+        if !(radius >= 0.0 && radius.is_finite()) {
+            return Err(PixelatorError::InvalidConfig(
+                "Minimum visible radius must be a non-negative number".to_string(),
+            ));
+        }
+        self.min_visible_radius = Some(radius);
+        Ok(self)
+    }
+    
+    /// Surrounds the dots with a soft glow of `radius` pixels, for a neon look
+    /// 
+    /// One shared `feGaussianBlur` filter is applied to the group holding all
+    /// dots, merged under the sharp dots. Applies to color, saturation-map
+    /// and white-on-black halftone output; pair color modes with a black
+    /// background for the full effect.
+    pub fn with_glow(mut self, radius: f32) -> Result<Self> {
+        if !(radius > 0.0 && radius.is_finite()) {
+            return Err(PixelatorError::InvalidConfig(
+                "Glow radius must be positive".to_string(),
+            ));
+        }
+        self.glow = Some(radius);
+        Ok(self)
+    }
+    
+    /// Draws dots as unfilled outlines stroked `stroke_width` pixels wide
+    /// 
+    /// Each dot keeps its color and size but loses its fill, leaving closed
+    /// outlines for cutting stencils. Applies to color, saturation-map and
+    /// halftone dots.
+    pub fn with_outline_only(mut self, stroke_width: f32) -> Result<Self> {
+        if !(stroke_width > 0.0 && stroke_width.is_finite()) {
+            return Err(PixelatorError::InvalidConfig(
+                "Outline stroke width must be positive".to_string(),
+            ));
+        }
+        self.outline_only = Some(stroke_width);
+        Ok(self)
+    }
+    
+    /// Tapers dots off linearly within `margin_px` pixels of the image border
+    /// 
+    /// A dot's size (or opacity, in color and saturation-map modes) is scaled
+    /// by its distance to the nearest edge over the margin, fading the print
+    /// out like a vignette.
+    pub fn with_edge_fade(mut self, margin_px: f32) -> Result<Self> {
+        if !(margin_px > 0.0 && margin_px.is_finite()) {
+            return Err(PixelatorError::InvalidConfig(
+                "Edge fade margin must be positive".to_string(),
+            ));
+        }
+        self.edge_fade = Some(margin_px);
+        Ok(self)
+    }
+    
+    /// Treats every image as sRGB, ignoring any embedded ICC profile
+    /// 
+    /// By default images tagged as Display P3 or Adobe RGB are converted to
+    /// sRGB before sampling so wide-gamut photos don't come out oversaturated.
+    pub fn with_assume_srgb(mut self, assume_srgb: bool) -> Self {
+        self.assume_srgb = assume_srgb;
+        self
+    }
+    
+    /// Seeds every randomized feature, so one seed reproduces the whole output
+    /// 
+    /// Each feature draws from its own stream derived from this seed.
+    /// Per-feature seeds such as `with_jitter_seed` or the stipple mode's
+    /// `seed` take precedence over it for that feature. Without any seed
+    /// randomness comes from system entropy and differs between runs.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+    
+    /// Scales the HSL saturation of every sampled color
+    /// 
+    /// Values above 1 make colors pop, values below 1 mute them and 0 gives
+    /// grayscale. Hue and lightness are kept, and brightness-driven dot
+    /// sizes are computed from the original color.
+    pub fn with_saturation_scale(mut self, scale: f32) -> Result<Self> {
+        if !(scale >= 0.0 && scale.is_finite()) {
+            return Err(PixelatorError::InvalidConfig(
+                "Saturation scale must be a non-negative number".to_string(),
+            ));
+        }
+        self.saturation_scale = scale;
+        Ok(self)
+    }
+    
+    /// Offsets each dot center by a random amount of up to `jitter` pixels per axis
+    pub fn with_jitter(mut self, jitter: f32) -> Result<Self> {
+        if jitter < 0.0 {
+            return Err(PixelatorError::InvalidConfig(
+                "Jitter must be non-negative".to_string(),
+            ));
+        }
+        self.jitter = jitter;
+        Ok(self)
+    }
+    
+    /// Seeds the jitter offsets, overriding the global seed
+    pub fn with_jitter_seed(mut self, seed: u64) -> Self {
+        self.jitter_seed = Some(seed);
+        self
+    }
+    
+    /// Caps the number of emitted dots by automatically widening the spacing
+    pub fn with_max_elements(mut self, max: usize) -> Result<Self> {
+        if max == 0 {
+            return Err(PixelatorError::InvalidConfig(
+                "Maximum element count must be at least 1".to_string(),
+            ));
+        }
+        self.max_elements = Some(max);
+        Ok(self)
+    }
+    
+    /// Averages each dot's color along a streak through its center instead of a circle
+    /// 
+    /// # Arguments
+    /// * `angle` - Streak direction in degrees (0 is horizontal)
+    /// * `length` - Streak length in pixels (must be positive)
+    pub fn with_directional_sample(mut self, angle: f32, length: f32) -> Result<Self> {
+        if length <= 0.0 {
+            return Err(PixelatorError::InvalidConfig(
+                "Directional sample length must be positive".to_string(),
+            ));
+        }
+        self.directional_sample = Some(DirectionalSample { angle, length });
+        Ok(self)
+    }
+    
+    /// Sets how grid mode fits the number of columns and rows to the image
+    pub fn with_grid_fit(mut self, fit: GridFit) -> Self {
+        self.grid_fit = fit;
+        self
+    }
+    
+    /// Draws the background as a full-size `<rect>` instead of a CSS style
+    /// 
+    /// Some SVG consumers ignore the `background-color` style; a rect covering
+    /// the viewBox renders everywhere.
+    pub fn with_background_rect(mut self, enabled: bool) -> Self {
+        self.background_rect = enabled;
+        self
+    }
+    
+    /// Samples `rows` whole grid rows (or hexagonal lines) per parallel task
+    /// 
+    /// By default rayon splits the work down to single dots, which can hurt
+    /// cache locality on large images. Larger chunks keep neighboring rows
+    /// on one thread; the output is the same for any chunk size.
+    pub fn with_parallel_chunk_rows(mut self, rows: usize) -> Result<Self> {
+        if rows == 0 {
+            return Err(PixelatorError::InvalidConfig(
+                "Parallel chunk size must be at least 1 row".to_string(),
+            ));
+        }
+        self.parallel_chunk_rows = Some(rows);
+        Ok(self)
+    }
+    
+    /// Limits sampling to `threads` worker threads using a dedicated pool
+    /// A value of 0 uses the global rayon pool with all available cores
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads;
+        self
+    }
+    
+    /// Sets the dithering applied to brightness before halftone dot sizing
+    /// 
+    /// Dithered dots snap to the minimum or maximum dot size. Blue noise
+    /// avoids the worm artifacts of error diffusion.
+    pub fn with_dither(mut self, mode: DitherMode) -> Self {
+        self.dither = mode;
+        self
+    }
+    
+    /// Adds a blank margin in millimeters around the dot field on every side
+    pub fn with_margin(mut self, margin_mm: f32) -> Result<Self> {
+        if margin_mm < 0.0 {
+            return Err(PixelatorError::InvalidConfig(
+                "Margin cannot be negative".to_string(),
+            ));
+        }
+        self.margin_mm = margin_mm;
+        Ok(self)
+    }
+    
+    /// Fits the output onto a page of the given size in millimeters
+    /// 
+    /// The output dimensions are computed per image as the largest size
+    /// with the image's aspect ratio that fits inside the page minus
+    /// `margin_mm` on every side, replacing any set dimensions. The document
+    /// is the full page, with the dot field centered: the margin grows on
+    /// the axis the image doesn't fill.
+    pub fn with_fit_to_page(mut self, page_width_mm: f32, page_height_mm: f32, margin_mm: f32) -> Result<Self> {
+        if page_width_mm <= 0.0 || page_height_mm <= 0.0 {
+            return Err(PixelatorError::InvalidConfig(
+                "Page dimensions must be positive".to_string(),
+            ));
+        }
+        if margin_mm < 0.0 || 2.0 * margin_mm >= page_width_mm.min(page_height_mm) {
+            return Err(PixelatorError::InvalidConfig(
+                "Margins must be non-negative and leave room on the page".to_string(),
+            ));
+        }
+        self.page_mm = Some((page_width_mm, page_height_mm));
+        self.margin_mm = margin_mm;
+        Ok(self)
+    }
+    
+    /// Draws a labeled scale bar of the given physical length in the bottom margin
+    /// 
+    /// Rendering requires output dimensions and a margin to be set; otherwise
+    /// SVG generation returns an `InvalidConfig` error.
+    pub fn with_scale_bar(mut self, length_mm: f32) -> Result<Self> {
+        if length_mm <= 0.0 {
+            return Err(PixelatorError::InvalidConfig(
+                "Scale bar length must be positive".to_string(),
+            ));
+        }
+        self.scale_bar_mm = Some(length_mm);
+        Ok(self)
+    }
+    
+    /// Applies an unsharp mask of the given amount to the image before sampling
+    /// 
+    /// Area averaging softens edges; an amount around 0.5 to 1.5 recovers
+    /// detail, especially in halftone mode. An amount of 0 disables sharpening.
+    pub fn with_sharpen(mut self, amount: f32) -> Result<Self> {
+        if amount < 0.0 {
+            return Err(PixelatorError::InvalidConfig(
+                "Sharpen amount cannot be negative".to_string(),
+            ));
+        }
+        self.sharpen = amount;
+        Ok(self)
+    }
+    
+    /// Preserves exact source colors for sprites and pixel art
+    /// 
+    /// Enabling this point-samples the single source pixel under each dot,
+    /// snaps dots to source pixel centers, and turns off supersampling,
+    /// directional streaks and sharpening, which would all blend colors.
+    pub fn with_pixel_art_mode(mut self, enabled: bool) -> Self {
+        self.pixel_art = enabled;
+        if enabled {
+            self.supersample = 1;
+            self.directional_sample = None;
+            self.sharpen = 0.0;
+        }
+        self
+    }
+    
+    /// Fills each color dot with a radial gradient from its own color to the
+    /// average color of its grid neighbors, for a smoother look
+    /// 
+    /// Gradient defs are shared per color pair but still add noticeably to
+    /// the file size. Ignored when merging paths by color.
+    pub fn with_gradient_dots(mut self, enabled: bool) -> Self {
+        self.gradient_dots = enabled;
+        self
+    }
+    
+    /// Flips the Y axis of machine output so the origin sits at the bottom-left
+    /// with +Y pointing up, as on most laser and CNC beds
+    /// 
+    /// Applies to G-code output only. CSV and JSON exports keep image
+    /// coordinates, as does SVG output unless `with_svg_y_up` is set.
+    pub fn with_y_up(mut self, enabled: bool) -> Self {
+        self.y_up = enabled;
+        self
+    }
+    
+    /// Writes SVG content in +Y-up coordinates for math-convention tools
+    /// 
+    /// Content coordinates are mirrored vertically and wrapped in a
+    /// `<g transform="translate(0,H) scale(1,-1)">` group that flips them
+    /// back, so the file still displays upright. With the default top-left
+    /// origin, y = 0 is the bottom edge of the image. Not available for the
+    /// color-by-number worksheet, whose labels would render mirrored.
+    pub fn with_svg_y_up(mut self, enabled: bool) -> Self {
+        self.svg_y_up = enabled;
+        self
+    }
+    
+    /// Fills the area behind the dots with a repeating pattern
+    /// 
+    /// The pattern is tiled at the dot spacing and drawn over the background
+    /// color, if any.
+    pub fn with_background_pattern(mut self, kind: PatternKind) -> Self {
+        self.background_pattern = Some(kind);
+        self
+    }
+    
+    /// Fills the background with a linear gradient, e.g. a sky-to-ground backdrop
+    /// 
+    /// Stops are `(offset, color)` pairs with offsets in 0..=1 in ascending
+    /// order; `angle` is the direction in degrees, clockwise from the x axis,
+    /// so 90 runs top to bottom. The gradient is drawn over the background
+    /// color and under any background pattern.
+    pub fn with_background_gradient(mut self, stops: Vec<(f32, String)>, angle: f32) -> Result<Self> {
+        if stops.len() < 2 {
+            return Err(PixelatorError::InvalidConfig(
+                "Background gradient needs at least two stops".to_string(),
+            ));
+        }
+        if stops.iter().any(|(offset, _)| !(0.0..=1.0).contains(offset)) {
+            return Err(PixelatorError::InvalidConfig(
+                "Gradient stop offsets must be between 0 and 1".to_string(),
+            ));
+        }
+        if stops.windows(2).any(|pair| pair[1].0 < pair[0].0) {
+            return Err(PixelatorError::InvalidConfig(
+                "Gradient stop offsets must be in ascending order".to_string(),
+            ));
+        }
+        self.background_gradient = Some(BackgroundGradient { stops, angle });
+        Ok(self)
+    }
+    
+    /// Scales dot sizes by a grayscale mask image, e.g. for a vignette
+    /// 
+    /// The mask is loaded when sampling and resized to the source image.
+    /// White keeps the full dot size and black shrinks dots to nothing, in
+    /// both color and halftone modes.
+    pub fn with_size_mask<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.size_mask = Some(path.as_ref().to_path_buf());
+        self
+    }
+    
+    /// Replaces each color dot with a tinted copy of a sprite image, for a
+    /// photo-mosaic look
+    /// 
+    /// The sprite is referenced once by path from an SVG `<image>` and placed in
+    /// every cell with `<use>`, sized to the circle diameter. A `feColorMatrix`
+    /// filter per distinct color maps the sprite's luminance onto that color.
+    /// Applies to the color and saturation map modes.
+    pub fn with_tile_image<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.tile_image = Some(path.as_ref().to_path_buf());
+        self
+    }
+    
+    /// Embeds the source image behind the dots at `opacity`, as a reference layer
+    /// 
+    /// The image fills the dot field in its own layer group, so it can be
+    /// hidden or deleted in an editor after review. Output produced through
+    /// `Pixelator` includes it automatically; with `SvgGenerator` directly,
+    /// supply the image with `with_reference_image`.
+    pub fn with_reference_layer(mut self, opacity: f32) -> Result<Self> {
+        if !(opacity > 0.0 && opacity <= 1.0) {
+            return Err(PixelatorError::InvalidConfig(
+                "Reference layer opacity must be in (0, 1]".to_string(),
+            ));
+        }
+        self.reference_layer = Some(opacity);
+        Ok(self)
+    }
+    
+    /// Rounds every emitted dot center to the nearest multiple of `pitch_mm`
+    /// 
+    /// Positions are snapped in output millimeters (image pixels count as
+    /// millimeters without output dimensions), so dots line up with the holes
+    /// of a pegboard or bead board of that pitch.
+    pub fn with_snap_to_grid(mut self, pitch_mm: f32) -> Result<Self> {
+        if pitch_mm <= 0.0 || !pitch_mm.is_finite() {
+            return Err(PixelatorError::InvalidConfig(
+                "Snap grid pitch must be positive".to_string(),
+            ));
+        }
+        self.snap_to_grid_mm = Some(pitch_mm);
+        Ok(self)
+    }
+    
+    /// Animates color and halftone dots growing in one after another over
+    /// `total_seconds`, following the order they are emitted in
+    /// 
+    /// Every dot gets its own `<animate>` elements, so the animation is only
+    /// emitted for outputs of up to 10,000 dots; larger ones stay static with a
+    /// warning.
+    pub fn with_reveal_animation(mut self, total_seconds: f32) -> Result<Self> {
+        if total_seconds <= 0.0 || !total_seconds.is_finite() {
+            return Err(PixelatorError::InvalidConfig(
+                "Reveal animation duration must be positive".to_string(),
+            ));
+        }
+        self.reveal_animation = Some(total_seconds);
+        Ok(self)
+    }
+    
+    /// Reduces the output to `count` dots placed where the image has the most detail
+    /// 
+    /// Dots are drawn from the sampled grid with probability proportional to
+    /// the local brightness gradient, like weighted stippling, so edges and
+    /// texture keep their dots while flat areas are left sparse. The choice
+    /// follows the global seed.
+    pub fn with_importance_dots(mut self, count: usize) -> Result<Self> {
+        if count == 0 {
+            return Err(PixelatorError::InvalidConfig(
+                "Importance sampling needs at least 1 dot".to_string(),
+            ));
+        }
+        self.importance_dots = Some(count);
+        Ok(self)
+    }
+    
+    /// Draws thin guide lines in `color` along the cell borders every `every` cells
+    /// 
+    /// The lines run across the whole dot field behind the dots, like the
+    /// markings of a bead pegboard. Only grid sampling has rows and columns
+    /// to guide, so hexagonal output returns an error.
+    pub fn with_guide_grid(mut self, every: usize, color: String) -> Result<Self> {
+        if every == 0 {
+            return Err(PixelatorError::InvalidConfig(
+                "Guide grid interval must be at least 1 cell".to_string(),
+            ));
+        }
+        self.guide_grid = Some((every, color));
+        Ok(self)
+    }
+    
+    /// Draws `+` crosshairs at the four corners of the canvas for aligning
+    /// separate plotter or print passes
+    /// 
+    /// The marks sit in the margin when it is at least one cell wide, and
+    /// over the corners of the dot field otherwise.
+    pub fn with_registration_marks(mut self, enabled: bool) -> Self {
+        self.registration_marks = enabled;
+        self
+    }
+    
+    /// Sets how output dimensions that don't match the image's aspect ratio are handled
+    /// 
+    /// `DistortionPolicy::Allow` (the default) stretches silently. With `Warn`
+    /// a mismatch of more than 1% prints a warning; `Reject` turns it into an
+    /// error.
+    pub fn with_distortion_policy(mut self, policy: DistortionPolicy) -> Self {
+        self.distortion_policy = policy;
+        self
+    }
+    
+    /// Splits brightness into one equal band per ink and draws each band's
+    /// dots in that ink, in its own layer group
+    /// 
+    /// The first ink takes the darkest band and the last the lightest, as
+    /// separations for screen printing with spot colors. Applies to color and
+    /// halftone rendering.
+    pub fn with_spot_color_bands(mut self, inks: Vec<String>) -> Result<Self> {
+        if inks.is_empty() {
+            return Err(PixelatorError::InvalidConfig(
+                "Spot color bands need at least one ink".to_string(),
+            ));
+        }
+        self.spot_color_bands = Some(inks);
+        Ok(self)
+    }
+    
+    /// Fills the cusp gaps between every four neighboring grid dots with a
+    /// smaller dot
+    /// 
+    /// Meant for tightly packed grids, where touching circles leave gaps
+    /// about 0.41 of the diameter wide; wider spacing gets larger filler
+    /// dots. Hexagonal sampling returns an error, and output that overlaps
+    /// at the gaps gets no filler dots.
+    pub fn with_fill_interstices(mut self, enabled: bool) -> Self {
+        self.fill_interstices = enabled;
+        self
+    }
+    
+    /// Sets whether sampled colors are averaged with premultiplied alpha
+    /// 
+    /// Enabled by default: each pixel's color counts in proportion to its
+    /// opacity, so the transparent black around a logo doesn't darken the
+    /// dots along its edges. Disabling it averages the stored RGB values
+    /// directly, as straight alpha.
+    pub fn with_premultiplied_alpha(mut self, enabled: bool) -> Self {
+        self.premultiplied_alpha = enabled;
+        self
+    }
+    
+    /// Sets the resolution SVG input files are rasterized at
+    /// 
+    /// At the default of 96 DPI one SVG user unit becomes one pixel; doubling
+    /// it doubles the raster's width and height, and so the dot count along
+    /// each axis. SVG input needs the `svg-input` feature.
+    pub fn with_svg_input_dpi(mut self, dpi: f32) -> Result<Self> {
+        if dpi <= 0.0 || !dpi.is_finite() {
+            return Err(PixelatorError::InvalidConfig(
+                "SVG input DPI must be positive".to_string(),
+            ));
+        }
+        self.svg_input_dpi = dpi;
+        Ok(self)
+    }
+    
+    /// Sets how dots that overlap their neighbors are handled
+    /// 
+    /// `OverlapPolicy::Allow` (the default) renders overlapping dots silently.
+    /// With `Warn` rendering prints a warning when the largest dots are wider
+    /// than the dot pitch; `Reject` turns it into an error. Touching dots
+    /// don't count as overlapping.
+    pub fn with_overlap_policy(mut self, policy: OverlapPolicy) -> Self {
+        self.overlap_policy = policy;
+        self
+    }
+    
+    /// Returns whether dots `dot_size` pixels across overlap their neighbors
+    /// 
+    /// Nearest neighbors are one total spacing apart in both grid and
+    /// hexagonal layouts.
+    pub fn dots_overlap(&self, dot_size: f32) -> bool {
+        dot_size > self.get_total_spacing() + f32::EPSILON * dot_size.max(1.0)
+    }
+    
+    /// Checks whether the largest dots overlap their neighbors
+    /// 
+    /// Applies the overlap policy when they do. The largest dot is the
+    /// halftone maximum in halftone mode, or the circle diameter otherwise.
+    pub fn check_overlap(&self) -> Result<()> {
+        let largest = self.largest_dot_size();
+        if !self.dots_overlap(largest) {
+            return Ok(());
+        }
+        let message = format!(
+            "Dots up to {:.2} pixels across overlap at a {:.2} pixel pitch; increase the spacing or reduce the dot size",
+            largest,
+            self.get_total_spacing()
+        );
+        match self.overlap_policy {
+            OverlapPolicy::Allow => Ok(()),
+            OverlapPolicy::Warn => {
+                eprintln!("Warning: {}", message);
+                Ok(())
+            }
+            OverlapPolicy::Reject => Err(PixelatorError::InvalidConfig(message)),
+        }
+    }
+    
+    /// Rounds a position in output millimeters to the snap grid, if one is set
+    pub fn snap_mm(&self, value_mm: f32) -> f32 {
+        match self.snap_to_grid_mm {
+            Some(pitch) => (value_mm / pitch).round() * pitch,
+            None => value_mm,
+        }
+    }
+    
+    /// Checks the output dimensions against the image's aspect ratio
+    /// 
+    /// Applies the distortion policy when the millimeter aspect ratio differs
+    /// from `img_width / img_height` beyond the tolerance. Without output
+    /// dimensions there is nothing to check.
+    pub fn check_aspect_ratio(&self, img_width: u32, img_height: u32) -> Result<()> {
+        let (Some(width_mm), Some(height_mm)) = (self.output_width_mm, self.output_height_mm) else {
+            return Ok(());
+        };
+        if img_width == 0 || img_height == 0 {
+            return Ok(());
+        }
+        
+        let image_ratio = img_width as f32 / img_height as f32;
+        let output_ratio = width_mm / height_mm;
+        if ((output_ratio - image_ratio) / image_ratio).abs() <= ASPECT_RATIO_TOLERANCE {
+            return Ok(());
+        }
+        let message = format!(
+            "Output size {}x{}mm (aspect ratio {:.3}) distorts the {}x{} pixel image (aspect ratio {:.3})",
+            width_mm, height_mm, output_ratio, img_width, img_height, image_ratio
+        );
+        match self.distortion_policy {
+            DistortionPolicy::Allow => Ok(()),
+            DistortionPolicy::Warn => {
+                eprintln!("Warning: {}", message);
+                Ok(())
+            }
+            DistortionPolicy::Reject => Err(PixelatorError::InvalidConfig(message)),
+        }
+    }
+    
+    /// Sets how dots near the border sample the image
+    /// 
+    /// With `EdgeMode::Clamp` (the default) a dot's window is cut at the
+    /// border, which over-weights the colors next to it. `Mirror` reflects
+    /// the window back into the image and `Skip` removes such dots.
+    pub fn with_edge_mode(mut self, mode: EdgeMode) -> Self {
+        self.edge_mode = mode;
+        self
+    }
+    
+    /// Emits SVG positions and radii normalized to the image size, for
+    /// responsive embedding where CSS controls the rendered size
+    /// 
+    /// Coordinates are divided by the longer image side so dots stay round;
+    /// a square image gets `viewBox="0 0 1 1"`.
+    pub fn with_normalized_coordinates(mut self, enabled: bool) -> Self {
+        self.normalized_coordinates = enabled;
+        self
+    }
+    
+    /// Confines dots to a shape given by a black and white mask image
+    /// 
+    /// The mask is resized to the source image. Dots whose center falls on a
+    /// black or transparent mask pixel are dropped rather than clipped, which
+    /// keeps the output plotter-friendly.
+    pub fn with_clip_mask<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.clip_mask = Some(path.as_ref().to_path_buf());
+        self
+    }
+    
+    /// Sets the order dots are painted in; only visible where dots overlap
+    pub fn with_paint_order(mut self, order: PaintOrder) -> Self {
+        self.paint_order = order;
+        self
+    }
+    
+    /// Snaps dot sizes to `levels` evenly spaced steps across the halftone range
+    /// 
+    /// Real screens have a limited number of dot gradations, and fewer
+    /// distinct radii also compress better. A single level uses the maximum
+    /// dot size throughout.
+    pub fn with_quantized_dot_sizes(mut self, levels: u8) -> Result<Self> {
+        if levels == 0 {
+            return Err(PixelatorError::InvalidConfig(
+                "Dot size levels must be at least 1".to_string(),
+            ));
+        }
+        self.dot_size_levels = Some(levels);
+        Ok(self)
+    }
+    
+    /// Snaps dot sizes to the nearest of a fixed set of diameters, like a bead kit
+    /// 
+    /// In halftone mode every dot, including the lightest, takes one of the
+    /// given sizes in pixels. Takes precedence over `with_quantized_dot_sizes`.
+    pub fn with_bead_sizes(mut self, mut sizes: Vec<f32>) -> Result<Self> {
+        if sizes.is_empty() || sizes.iter().any(|size| !(*size > 0.0 && size.is_finite())) {
+            return Err(PixelatorError::InvalidConfig(
+                "Bead sizes must be a non-empty list of positive diameters".to_string(),
+            ));
+        }
+        sizes.sort_by(f32::total_cmp);
+        sizes.dedup();
+        self.bead_sizes = Some(sizes);
+        Ok(self)
+    }
+    
+    /// Whether dot sizes are snapped to discrete levels or bead sizes
+    /// 
+    /// Only halftone dots are snapped; other render modes keep their sizes.
+    pub fn quantizes_dot_sizes(&self) -> bool {
+        matches!(self.render_mode, RenderMode::Halftone(_))
+            && (self.dot_size_levels.is_some() || self.bead_sizes.is_some())
+    }
+    
+    /// Snaps a dot size to the nearest bead size or configured level, if any
+    pub fn quantize_dot_size(&self, size: f32) -> f32 {
+        if let Some(sizes) = &self.bead_sizes {
+            return sizes
+                .iter()
+                .copied()
+                .min_by(|a, b| (a - size).abs().total_cmp(&(b - size).abs()))
+                .unwrap_or(size);
+        }
+        let Some(levels) = self.dot_size_levels else {
+            return size;
+        };
+        if levels == 1 {
+            return self.max_dot_size;
+        }
+        
+        let range = self.max_dot_size - self.min_dot_size;
+        if range <= 0.0 {
+            return self.max_dot_size;
+        }
+        let steps = (levels - 1) as f32;
+        let step = ((size - self.min_dot_size) / range * steps).round().clamp(0.0, steps);
+        self.min_dot_size + range * step / steps
+    }
+    
+    /// Makes transparent pixels read as background tone when computing brightness
+    /// 
+    /// Brightness is composited by alpha over the background: white paper,
+    /// or black for white-on-black halftones. Without this, a transparent
+    /// dark pixel counts as dark even though it is invisible.
+    pub fn with_alpha_in_brightness(mut self, enabled: bool) -> Self {
+        self.alpha_in_brightness = enabled;
+        self
+    }
 }
\ No newline at end of file
@@ -0,0 +1,171 @@
+use crate::processor::PixelData;
+use std::collections::HashMap;
+
+// Value of the padding ring around the grid, below every threshold so each
+// contour closes inside the padded grid
+const OUTSIDE: f32 = -1.0;
+
+/// A closed contour ring as a list of points; the last point joins the first
+pub type Ring = Vec<(f32, f32)>;
+
+// Crossing point on a grid edge: the edge's start corner and whether it runs
+// horizontally (to the next column) or vertically (to the next row)
+type EdgeKey = (i64, i64, bool);
+
+/// Traces nested contours of darkness across the dot grid
+///
+/// Darkness (one minus brightness) is split into `levels` bands. Level `k`
+/// covers every cell at least `k / levels` dark, so level 0 is the whole
+/// grid and each following level lies inside the previous one. Each level
+/// is returned as closed rings found with marching squares, using the dot
+/// centers as grid points and interpolating crossings between them. Outer
+/// boundaries and holes are both rings, so they are meant to be filled with
+/// the even-odd rule. Only dots with a grid position take part.
+pub fn contour_rings(pixels: &[PixelData], levels: u8, cell_size: f32) -> Vec<Vec<Ring>> {
+    let grid = DarknessGrid::new(pixels, cell_size);
+    (0..levels)
+        .map(|k| match &grid {
+            Some(grid) => grid.rings(k as f32 / levels as f32),
+            None => Vec::new(),
+        })
+        .collect()
+}
+
+/// Dot darkness and positions on the grid, padded by one outside cell
+struct DarknessGrid {
+    cols: i64,
+    rows: i64,
+    values: HashMap<(i64, i64), f32>,
+    positions: HashMap<(i64, i64), (f32, f32)>,
+    cell_size: f32,
+}
+
+impl DarknessGrid {
+    fn new(pixels: &[PixelData], cell_size: f32) -> Option<Self> {
+        let mut values = HashMap::new();
+        let mut positions = HashMap::new();
+        let (mut cols, mut rows) = (0, 0);
+        for pixel in pixels {
+            let (col, row) = pixel.grid_position?;
+            let key = (col as i64, row as i64);
+            values.insert(key, 1.0 - pixel.brightness);
+            positions.insert(key, (pixel.x, pixel.y));
+            cols = cols.max(key.0 + 1);
+            rows = rows.max(key.1 + 1);
+        }
+        if values.is_empty() {
+            return None;
+        }
+        Some(Self { cols, rows, values, positions, cell_size })
+    }
+
+    fn value(&self, col: i64, row: i64) -> f32 {
+        self.values.get(&(col, row)).copied().unwrap_or(OUTSIDE)
+    }
+
+    // Position of a grid point; padding points sit one cell beyond the
+    // nearest real dot
+    fn position(&self, col: i64, row: i64) -> (f32, f32) {
+        if let Some(&position) = self.positions.get(&(col, row)) {
+            return position;
+        }
+        let (near_col, near_row) = (col.clamp(0, self.cols - 1), row.clamp(0, self.rows - 1));
+        let (x, y) = self
+            .positions
+            .get(&(near_col, near_row))
+            .copied()
+            .unwrap_or((near_col as f32 * self.cell_size, near_row as f32 * self.cell_size));
+        (x + (col - near_col) as f32 * self.cell_size, y + (row - near_row) as f32 * self.cell_size)
+    }
+
+    fn inside(&self, col: i64, row: i64, threshold: f32) -> bool {
+        self.value(col, row) >= threshold
+    }
+
+    // Interpolated point where the contour crosses an edge
+    fn crossing(&self, (col, row, horizontal): EdgeKey, threshold: f32) -> (f32, f32) {
+        let (end_col, end_row) = if horizontal { (col + 1, row) } else { (col, row + 1) };
+        let (start, end) = (self.value(col, row), self.value(end_col, end_row));
+        let t = if (end - start).abs() > f32::EPSILON { ((threshold - start) / (end - start)).clamp(0.0, 1.0) } else { 0.5 };
+        let (x1, y1) = self.position(col, row);
+        let (x2, y2) = self.position(end_col, end_row);
+        (x1 + (x2 - x1) * t, y1 + (y2 - y1) * t)
+    }
+
+    fn rings(&self, threshold: f32) -> Vec<Ring> {
+        // Link edge crossings into segments, one square of four dots at a time
+        let mut links: HashMap<EdgeKey, Vec<EdgeKey>> = HashMap::new();
+        for row in -1..self.rows {
+            for col in -1..self.cols {
+                let top = (col, row, true);
+                let bottom = (col, row + 1, true);
+                let left = (col, row, false);
+                let right = (col + 1, row, false);
+
+                let a = self.inside(col, row, threshold);
+                let b = self.inside(col + 1, row, threshold);
+                let c = self.inside(col + 1, row + 1, threshold);
+                let d = self.inside(col, row + 1, threshold);
+                let case = (a as u8) << 3 | (b as u8) << 2 | (c as u8) << 1 | d as u8;
+
+                // Saddles are resolved by the average of the four corners
+                let center = (self.value(col, row)
+                    + self.value(col + 1, row)
+                    + self.value(col + 1, row + 1)
+                    + self.value(col, row + 1))
+                    / 4.0
+                    >= threshold;
+
+                let segments: &[(EdgeKey, EdgeKey)] = match case {
+                    1 | 14 => &[(left, bottom)],
+                    2 | 13 => &[(bottom, right)],
+                    3 | 12 => &[(left, right)],
+                    4 | 11 => &[(top, right)],
+                    6 | 9 => &[(top, bottom)],
+                    7 | 8 => &[(left, top)],
+                    5 if center => &[(left, top), (bottom, right)],
+                    5 => &[(top, right), (left, bottom)],
+                    10 if center => &[(top, right), (left, bottom)],
+                    10 => &[(left, top), (bottom, right)],
+                    _ => &[],
+                };
+                for &(from, to) in segments {
+                    links.entry(from).or_default().push(to);
+                    links.entry(to).or_default().push(from);
+                }
+            }
+        }
+
+        // Walk the links into closed rings
+        let mut keys: Vec<EdgeKey> = links.keys().copied().collect();
+        keys.sort_unstable();
+        let mut visited: HashMap<EdgeKey, bool> = HashMap::new();
+        let mut rings = Vec::new();
+        for start in keys {
+            if visited.contains_key(&start) {
+                continue;
+            }
+            let mut ring = Vec::new();
+            let (mut previous, mut current) = (None, start);
+            loop {
+                visited.insert(current, true);
+                ring.push(self.crossing(current, threshold));
+                let next = links[&current]
+                    .iter()
+                    .copied()
+                    .find(|&n| Some(n) != previous && !visited.contains_key(&n));
+                match next {
+                    Some(n) => {
+                        previous = Some(current);
+                        current = n;
+                    }
+                    None => break,
+                }
+            }
+            if ring.len() >= 3 {
+                rings.push(ring);
+            }
+        }
+        rings
+    }
+}
\ No newline at end of file
@@ -0,0 +1,166 @@
+use crate::config::{DitherMode, HalftoneCurve};
+use crate::processor::PixelData;
+use std::collections::HashMap;
+
+// 16x16 blue-noise rank matrix (void-and-cluster), tiled over the dot lattice
+const BLUE_NOISE_SIZE: usize = 16;
+const BLUE_NOISE: [[u8; BLUE_NOISE_SIZE]; BLUE_NOISE_SIZE] = [
+    [234,  50, 188,  19,  58, 171, 121,  47, 163,   3, 247, 104,  22, 132,  14,  65],
+    [209,   8, 118,  97, 240, 205,  23, 228, 138,  64, 123, 170,  72, 224,  99, 149],
+    [ 85, 139, 229, 165,  78, 146, 111,  84, 176, 216,  30, 231, 153, 201,  42, 180],
+    [ 25,  62, 195,  29,  43, 185,   7, 249,  41, 100, 191,  48,  87,   5, 128, 243],
+    [221, 152, 101, 253, 130, 220,  59, 200, 156,  12, 136, 112, 254, 174,  69, 109],
+    [ 46, 189,   2,  73, 172,  90, 142, 116,  80, 237, 210,  61, 147,  33, 206, 160],
+    [ 81, 124, 217, 113, 208,  15, 241,  27, 168,  45, 178,  20, 193,  96, 225,  18],
+    [242, 164,  60,  35, 157,  53, 181,  68, 223, 105, 125,  83, 236, 131,  55, 141],
+    [197,  10, 227, 134, 246,  95, 126, 198, 148,   1, 244, 161,  71,   9, 182, 106],
+    [ 40,  93, 179,  75, 192,   6, 218,  36,  91,  57, 202,  34, 215, 155, 233,  74],
+    [252, 120, 150,  24, 110,  63, 166, 119, 232, 183, 133, 103,  49, 117,  31, 167],
+    [ 16, 212,  51, 238, 207, 137, 255,  21,  76, 151,  13, 250, 190,  88, 203, 135],
+    [102, 184,  82, 169,  38,  89, 187,  52, 204,  98, 173,  67, 129,   4, 222,  56],
+    [230, 144,   0, 127, 226,  11, 154, 114, 239,  39, 219,  28, 235, 145, 175,  77],
+    [196,  37, 248,  70, 107, 199,  66, 177,  17, 143, 115, 159,  86,  44, 108,  26],
+    [122,  92, 158, 214, 140,  32, 245,  94, 213,  79, 194,  54, 211, 186, 251, 162],
+];
+
+/// Returns the blue-noise threshold (0.0 to 1.0) for a lattice position
+pub fn blue_noise_threshold(col: usize, row: usize) -> f32 {
+    let rank = BLUE_NOISE[row % BLUE_NOISE_SIZE][col % BLUE_NOISE_SIZE];
+    (rank as f32 + 0.5) / 256.0
+}
+
+/// Thresholds each pixel's brightness to 0.0 or 1.0 using the given dither mode
+/// 
+/// Returns one dithered tone per pixel, in order. Pixels without a lattice
+/// position are thresholded at the midpoint.
+pub fn dither_brightness(pixels: &[PixelData], mode: DitherMode) -> Vec<f32> {
+    let threshold = |tone: f32, limit: f32| if tone > limit { 1.0 } else { 0.0 };
+    
+    match mode {
+        DitherMode::None => pixels.iter().map(|p| p.brightness).collect(),
+        DitherMode::BlueNoise => pixels
+            .iter()
+            .map(|p| {
+                let limit = p.grid_position.map_or(0.5, |(col, row)| blue_noise_threshold(col, row));
+                threshold(p.brightness, limit)
+            })
+            .collect(),
+        DitherMode::FloydSteinberg => {
+            // Visit dots in lattice scan order and push the quantization error
+            // onto unvisited neighbors
+            let index: HashMap<(usize, usize), usize> = pixels
+                .iter()
+                .enumerate()
+                .filter_map(|(i, p)| p.grid_position.map(|pos| (pos, i)))
+                .collect();
+            let mut order: Vec<usize> = (0..pixels.len()).collect();
+            order.sort_by_key(|&i| pixels[i].grid_position.map(|(col, row)| (row, col)));
+            
+            let mut tones: Vec<f32> = pixels.iter().map(|p| p.brightness).collect();
+            let mut output = vec![0.0; pixels.len()];
+            
+            for i in order {
+                let value = threshold(tones[i], 0.5);
+                output[i] = value;
+                
+                let Some((col, row)) = pixels[i].grid_position else {
+                    continue;
+                };
+                let error = tones[i] - value;
+                let neighbors = [
+                    (col + 1, row, 7.0 / 16.0),
+                    (col.wrapping_sub(1), row + 1, 3.0 / 16.0),
+                    (col, row + 1, 5.0 / 16.0),
+                    (col + 1, row + 1, 1.0 / 16.0),
+                ];
+                for (ncol, nrow, weight) in neighbors {
+                    if let Some(&n) = index.get(&(ncol, nrow)) {
+                        tones[n] += error * weight;
+                    }
+                }
+            }
+            
+            output
+        }
+    }
+}
+
+/// Frequency-modulated (stochastic) screening of the sampled cells
+/// 
+/// Each cell of side `cell_size` around a pixel is divided into slots of
+/// `dot_size` on a global lattice; a slot receives a fixed-size dot when its
+/// blue-noise threshold is below the cell's darkness. Darker cells therefore
+/// get proportionally more dots, scattered without visible patterns.
+/// Returns the centers of the emitted dots.
+pub fn fm_screen(pixels: &[PixelData], cell_size: f32, dot_size: f32, curve: &HalftoneCurve) -> Vec<(f32, f32)> {
+    if dot_size <= 0.0 || cell_size <= 0.0 {
+        return Vec::new();
+    }
+    
+    let mut dots = Vec::new();
+    for pixel in pixels {
+        let darkness = curve.apply(1.0 - pixel.brightness);
+        let half = cell_size / 2.0;
+        let first_col = ((pixel.x - half) / dot_size).floor() as i64;
+        let first_row = ((pixel.y - half) / dot_size).floor() as i64;
+        let last_col = ((pixel.x + half) / dot_size).ceil() as i64;
+        let last_row = ((pixel.y + half) / dot_size).ceil() as i64;
+        
+        for row in first_row..last_row {
+            let cy = (row as f32 + 0.5) * dot_size;
+            if cy < pixel.y - half || cy >= pixel.y + half {
+                continue;
+            }
+            for col in first_col..last_col {
+                let cx = (col as f32 + 0.5) * dot_size;
+                if cx < pixel.x - half || cx >= pixel.x + half {
+                    continue;
+                }
+                let threshold = blue_noise_threshold(
+                    col.rem_euclid(BLUE_NOISE_SIZE as i64) as usize,
+                    row.rem_euclid(BLUE_NOISE_SIZE as i64) as usize,
+                );
+                if threshold < darkness {
+                    dots.push((cx, cy));
+                }
+            }
+        }
+    }
+    dots
+}
+
+/// Stochastic stippling of the sampled cells
+/// 
+/// Each cell of side `cell_size` around a pixel offers one candidate dot per
+/// `dot_size` square of its area. Every candidate is kept with probability
+/// equal to the cell's darkness and placed at a random point in the cell.
+/// Returns the centers of the emitted dots.
+pub fn stipple<R: rand::Rng>(
+    pixels: &[PixelData],
+    cell_size: f32,
+    dot_size: f32,
+    curve: &HalftoneCurve,
+    rng: &mut R,
+) -> Vec<(f32, f32)> {
+    if dot_size <= 0.0 || cell_size <= 0.0 {
+        return Vec::new();
+    }
+    
+    let candidates = ((cell_size / dot_size).powi(2).round() as usize).max(1);
+    let half = cell_size / 2.0;
+    let mut dots = Vec::new();
+    for pixel in pixels {
+        let darkness = curve.apply(1.0 - pixel.brightness);
+        for _ in 0..candidates {
+            // Draw the position even for rejected dots so one cell's tone
+            // does not shift the random sequence of the cells after it
+            let keep = rng.gen::<f32>() < darkness;
+            let x = pixel.x + rng.gen_range(-half..half);
+            let y = pixel.y + rng.gen_range(-half..half);
+            if keep {
+                dots.push((x, y));
+            }
+        }
+    }
+    dots
+}
\ No newline at end of file
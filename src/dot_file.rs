@@ -0,0 +1,110 @@
+use crate::config::{PixelatorConfig, SampleMode};
+use crate::error::Result;
+use crate::processor::PixelData;
+use image::Rgba;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Sampled dots saved together with the configuration and source size
+/// needed to render them again without re-sampling the image
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DotFile {
+    pub config: PixelatorConfig,
+    pub width: u32,
+    pub height: u32,
+    pub dots: Vec<PixelData>,
+}
+
+impl DotFile {
+    /// Serializes the dot file to pretty-printed JSON
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+    
+    /// Parses a dot file from JSON
+    pub fn from_json(json: &str) -> Result<Self> {
+        Ok(serde_json::from_str(json)?)
+    }
+    
+    /// Counts the dots of each distinct RGB color, most frequent first
+    /// Colors with equal counts keep the order they first appear in
+    pub fn palette_histogram(&self) -> Vec<(Rgba<u8>, usize)> {
+        let mut counts: Vec<(Rgba<u8>, usize)> = Vec::new();
+        let mut index: HashMap<[u8; 3], usize> = HashMap::new();
+        
+        for dot in &self.dots {
+            let rgb = [dot.color[0], dot.color[1], dot.color[2]];
+            let slot = *index.entry(rgb).or_insert_with(|| {
+                counts.push((Rgba([rgb[0], rgb[1], rgb[2], 255]), 0));
+                counts.len() - 1
+            });
+            counts[slot].1 += 1;
+        }
+        
+        counts.sort_by_key(|entry| std::cmp::Reverse(entry.1));
+        counts
+    }
+    
+    /// Fraction of the output page covered by dots, for estimating ink use
+    /// 
+    /// Sums the area of every dot and divides it by the page area, both in
+    /// millimeters: the output size plus margins, or the fitted page. Without
+    /// output dimensions one pixel counts as one millimeter, as in the SVG.
+    /// Overlapping dots are counted twice, so the result can exceed 1.0 when
+    /// dots are larger than their cells.
+    pub fn coverage_fraction(&self) -> f32 {
+        let (width_mm, height_mm) = match (self.config.output_width_mm, self.config.output_height_mm) {
+            (Some(w), Some(h)) => (w, h),
+            _ => (self.width as f32, self.height as f32),
+        };
+        let (page_width, page_height) = match self.config.page_mm {
+            Some(page) => page,
+            None => (width_mm + 2.0 * self.config.margin_mm, height_mm + 2.0 * self.config.margin_mm),
+        };
+        let page_area = page_width * page_height;
+        if page_area <= 0.0 || self.width == 0 || self.height == 0 {
+            return 0.0;
+        }
+        
+        let mm2_per_pixel = (width_mm / self.width as f32) * (height_mm / self.height as f32);
+        let dot_area: f32 = self
+            .dots
+            .iter()
+            .map(|dot| std::f32::consts::FRAC_PI_4 * dot.dot_size * dot.dot_size)
+            .sum();
+        dot_area * mm2_per_pixel / page_area
+    }
+    
+    /// Lists the palette as one `#rrggbb count` line per color
+    pub fn palette_listing(&self) -> String {
+        self.palette_histogram()
+            .iter()
+            .map(|(color, count)| format!("#{:02x}{:02x}{:02x} {}\n", color[0], color[1], color[2], count))
+            .collect()
+    }
+    
+    /// Formats the dots as CSV with one `x,y,r,g,b,size` row per dot
+    /// 
+    /// In grid mode the column and row index of each dot are appended as
+    /// extra `col,row` columns.
+    pub fn to_csv(&self) -> String {
+        let with_grid = matches!(self.config.sample_mode, SampleMode::Grid);
+        let mut csv = String::from(if with_grid { "x,y,r,g,b,size,col,row\n" } else { "x,y,r,g,b,size\n" });
+        
+        for dot in &self.dots {
+            csv.push_str(&format!(
+                "{},{},{},{},{},{}",
+                dot.x, dot.y, dot.color[0], dot.color[1], dot.color[2], dot.dot_size
+            ));
+            if with_grid {
+                match dot.grid_position {
+                    Some((col, row)) => csv.push_str(&format!(",{},{}", col, row)),
+                    None => csv.push_str(",,"),
+                }
+            }
+            csv.push('\n');
+        }
+        
+        csv
+    }
+}
@@ -0,0 +1,50 @@
+use image::ImageFormat;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+// Inches per meter, for the PNG pHYs chunk which counts pixels per meter
+const INCHES_PER_METER: f32 = 39.370_08;
+const CM_PER_INCH: f32 = 2.54;
+
+// Start of a JPEG file followed by a JFIF APP0 segment marker
+const JPEG_SOI_APP0: [u8; 4] = [0xFF, 0xD8, 0xFF, 0xE0];
+
+/// Reads the horizontal resolution in dots per inch stored in a PNG or JPEG
+///
+/// Uses the PNG `pHYs` chunk or the JFIF density of a JPEG. Returns `None`
+/// for other formats, or when the file carries no physical resolution (an
+/// aspect ratio alone doesn't count).
+pub fn read_dpi<P: AsRef<Path>>(path: P) -> Option<f32> {
+    let format = ImageFormat::from_path(&path).ok()?;
+    let reader = BufReader::new(File::open(&path).ok()?);
+    let dpi = match format {
+        ImageFormat::Png => {
+            let decoder = png::Decoder::new(reader).read_info().ok()?;
+            let dims = decoder.info().pixel_dims?;
+            if dims.unit != png::Unit::Meter {
+                return None;
+            }
+            dims.xppu as f32 / INCHES_PER_METER
+        }
+        ImageFormat::Jpeg => jfif_dpi(reader)?,
+        _ => return None,
+    };
+    (dpi > 0.0).then_some(dpi)
+}
+
+// JFIF APP0 layout after the markers: length (2), "JFIF\0" (5), version (2),
+// density units (1), x density (2), y density (2)
+fn jfif_dpi<R: Read>(mut reader: R) -> Option<f32> {
+    let mut header = [0u8; 16];
+    reader.read_exact(&mut header).ok()?;
+    if header[0..4] != JPEG_SOI_APP0 || &header[6..11] != b"JFIF\0" {
+        return None;
+    }
+    let density = u16::from_be_bytes([header[14], header[15]]) as f32;
+    match header[13] {
+        1 => Some(density),
+        2 => Some(density * CM_PER_INCH),
+        _ => None,
+    }
+}
\ No newline at end of file
@@ -8,6 +8,9 @@ pub enum PixelatorError {
     #[error("Image processing error: {0}")]
     Image(#[from] image::ImageError),
     
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+    
     #[error("Invalid configuration: {0}")]
     InvalidConfig(String),
     
@@ -0,0 +1,16 @@
+/// Formats a number for vector output, independent of any locale
+///
+/// The value is rounded to `decimals` places, always uses `.` as the decimal
+/// separator, and has trailing zeros removed, so `10.0` becomes `10` and
+/// `2.500` becomes `2.5`. Negative zero is written as `0`.
+pub fn format_number(value: f32, decimals: usize) -> String {
+    let mut text = format!("{:.*}", decimals, value);
+    if text.contains('.') {
+        let trimmed = text.trim_end_matches('0').trim_end_matches('.').len();
+        text.truncate(trimmed);
+    }
+    if text == "-0" {
+        text = "0".to_string();
+    }
+    text
+}
\ No newline at end of file
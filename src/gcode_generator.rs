@@ -0,0 +1,113 @@
+use crate::config::{PixelatorConfig, RenderMode};
+use crate::error::Result;
+use crate::format::format_number;
+use crate::processor::PixelData;
+use crate::render::Render;
+
+// Decimal places for machine coordinates in millimeters
+const GCODE_DECIMALS: usize = 3;
+
+/// Generates G-code for laser and pen plotters from sampled pixel data
+///
+/// Each dot becomes a full circle (`G2`) traced with the tool on (`M3`),
+/// with rapid moves (`G0`) between dots. Coordinates are in millimeters.
+pub struct GcodeGenerator<'a> {
+    config: &'a PixelatorConfig,
+}
+
+impl<'a> GcodeGenerator<'a> {
+    /// Creates a new G-code generator with the given configuration
+    pub fn new(config: &'a PixelatorConfig) -> Self {
+        Self { config }
+    }
+
+    /// Generates a G-code program from pixel data
+    ///
+    /// Without output dimensions one image pixel maps to one millimeter.
+    /// With `y_up` enabled the Y axis is flipped so the image's top edge
+    /// lands at the maximum machine Y.
+    ///
+    /// Built on `render`, which writes each dot's moves to the writer as
+    /// they are produced.
+    ///
+    /// # Arguments
+    /// * `pixels` - The sampled pixel data
+    /// * `original_width` - Original image width in pixels
+    /// * `original_height` - Original image height in pixels
+    pub fn generate_gcode(
+        &self,
+        pixels: &[PixelData],
+        original_width: u32,
+        original_height: u32,
+    ) -> Result<String> {
+        let mut gcode = Vec::new();
+        self.render(&mut gcode, pixels, original_width, original_height)?;
+        Ok(String::from_utf8(gcode).expect("G-code is ASCII"))
+    }
+}
+
+impl Render for GcodeGenerator<'_> {
+    fn render<W: std::io::Write>(
+        &self,
+        writer: &mut W,
+        pixels: &[PixelData],
+        original_width: u32,
+        original_height: u32,
+    ) -> Result<()> {
+        self.config.check_aspect_ratio(original_width, original_height)?;
+        self.config.check_overlap()?;
+        let (scale_x, scale_y) = match (self.config.output_width_mm, self.config.output_height_mm) {
+            (Some(w), Some(h)) => (w / original_width as f32, h / original_height as f32),
+            _ => (1.0, 1.0),
+        };
+        let height_mm = original_height as f32 * scale_y;
+        let num = |value: f32| format_number(value, GCODE_DECIMALS);
+
+        writer.write_all(b"; Generated by pixelator\nG21 ; millimeters\nG90 ; absolute positioning\nM5\n")?;
+
+        // Collect (x, y, diameter) of every dot in image pixels
+        let dots: Vec<(f32, f32, f32)> = match &self.config.render_mode {
+            RenderMode::FmHalftone { dot_size } => {
+                let cell = self.config.get_total_spacing();
+                crate::dither::fm_screen(pixels, cell, *dot_size, &self.config.halftone_curve)
+                    .into_iter()
+                    .map(|(x, y)| (x, y, *dot_size))
+                    .collect()
+            }
+            RenderMode::Stipple { dot_size, seed } => {
+                let cell = self.config.get_total_spacing();
+                let mut rng = crate::rng::feature_rng(self.config.seed, *seed, crate::rng::STIPPLE_STREAM);
+                crate::dither::stipple(pixels, cell, *dot_size, &self.config.halftone_curve, &mut rng)
+                    .into_iter()
+                    .map(|(x, y)| (x, y, *dot_size))
+                    .collect()
+            }
+            RenderMode::Halftone(_) | RenderMode::Threshold { .. } => pixels
+                .iter()
+                // Skip very small dots, matching the SVG output
+                .filter(|p| p.dot_size >= 0.5)
+                .map(|p| (p.x, p.y, p.dot_size))
+                .collect(),
+            _ => pixels.iter().map(|p| (p.x, p.y, p.dot_size)).collect(),
+        };
+
+        for (px, py, diameter) in dots {
+            let x = self.config.snap_mm(px * scale_x);
+            let y = self.config.snap_mm(if self.config.y_up {
+                height_mm - py * scale_y
+            } else {
+                py * scale_y
+            });
+            let radius = diameter / 2.0 * scale_x;
+
+            let (start_x, y, offset) = (num(x + radius), num(y), num(-radius));
+            writeln!(writer, "G0 X{} Y{}", start_x, y)?;
+            writer.write_all(b"M3\n")?;
+            writeln!(writer, "G2 X{} Y{} I{} J0", start_x, y, offset)?;
+            writer.write_all(b"M5\n")?;
+        }
+
+        writer.write_all(b"M2\n")?;
+        Ok(())
+    }
+}
\ No newline at end of file
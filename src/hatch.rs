@@ -0,0 +1,102 @@
+use crate::config::{HalftoneCurve, HatchOrientation};
+use crate::line_screen::LineSegment;
+use crate::processor::PixelData;
+use std::collections::HashMap;
+
+// Most hatch lines drawn in a fully black cell
+const MAX_HATCH_LINES: f32 = 4.0;
+
+// Stroke width of a hatch line, relative to the cell size
+const HATCH_STROKE_CELLS: f32 = 0.08;
+
+// Angle used where the image has no structure to follow
+const FLAT_AREA_ANGLE: f32 = 45.0;
+
+// Gradient magnitude below which a cell counts as flat
+const MIN_GRADIENT: f32 = 1e-3;
+
+/// Builds hatching of parallel strokes in each dot cell
+///
+/// Darker cells get more lines, up to four in a black cell, clipped to the
+/// circle inscribed in the cell. Lines run at a fixed angle or, with
+/// `HatchOrientation::FollowStructure`, along the local edge direction:
+/// perpendicular to the brightness gradient found with a Sobel operator
+/// over neighboring grid cells.
+pub fn hatch(
+    pixels: &[PixelData],
+    cell_size: f32,
+    orientation: HatchOrientation,
+    curve: &HalftoneCurve,
+) -> Vec<LineSegment> {
+    if cell_size <= 0.0 {
+        return Vec::new();
+    }
+
+    let grid: HashMap<(usize, usize), f32> = pixels
+        .iter()
+        .filter_map(|p| p.grid_position.map(|position| (position, p.brightness)))
+        .collect();
+    let radius = cell_size / 2.0;
+    let mut segments = Vec::new();
+
+    for pixel in pixels {
+        let lines = (curve.apply(1.0 - pixel.brightness) * MAX_HATCH_LINES).round() as usize;
+        if lines == 0 {
+            continue;
+        }
+
+        let angle = match orientation {
+            HatchOrientation::Fixed(angle) => angle,
+            HatchOrientation::FollowStructure => edge_angle(&grid, pixel),
+        };
+        let (sin, cos) = angle.to_radians().sin_cos();
+        let (dir, normal) = ((cos, sin), (-sin, cos));
+
+        // Evenly spaced offsets across the cell, each clipped to the circle
+        for k in 0..lines {
+            let offset = ((k as f32 + 0.5) / lines as f32 - 0.5) * cell_size;
+            let half = (radius * radius - offset * offset).max(0.0).sqrt();
+            let (cx, cy) = (pixel.x + normal.0 * offset, pixel.y + normal.1 * offset);
+            segments.push(LineSegment {
+                x1: cx - dir.0 * half,
+                y1: cy - dir.1 * half,
+                x2: cx + dir.0 * half,
+                y2: cy + dir.1 * half,
+                width: cell_size * HATCH_STROKE_CELLS,
+            });
+        }
+    }
+
+    segments
+}
+
+// Direction of the edge through a cell in degrees, perpendicular to its
+// brightness gradient
+fn edge_angle(grid: &HashMap<(usize, usize), f32>, pixel: &PixelData) -> f32 {
+    let (gx, gy) = sobel(grid, pixel);
+    if gx.hypot(gy) < MIN_GRADIENT {
+        return FLAT_AREA_ANGLE;
+    }
+    gy.atan2(gx).to_degrees() + 90.0
+}
+
+/// Sobel brightness gradient `(gx, gy)` over a dot's 3x3 grid neighborhood
+///
+/// `grid` maps grid positions to brightness. Missing neighbors repeat the
+/// dot's own brightness, and dots without a grid position have no gradient.
+pub(crate) fn sobel(grid: &HashMap<(usize, usize), f32>, pixel: &PixelData) -> (f32, f32) {
+    let Some((col, row)) = pixel.grid_position else {
+        return (0.0, 0.0);
+    };
+    let at = |dc: i64, dr: i64| {
+        let (c, r) = (col as i64 + dc, row as i64 + dr);
+        if c < 0 || r < 0 {
+            return pixel.brightness;
+        }
+        grid.get(&(c as usize, r as usize)).copied().unwrap_or(pixel.brightness)
+    };
+
+    let gx = (at(1, -1) + 2.0 * at(1, 0) + at(1, 1)) - (at(-1, -1) + 2.0 * at(-1, 0) + at(-1, 1));
+    let gy = (at(-1, 1) + 2.0 * at(0, 1) + at(1, 1)) - (at(-1, -1) + 2.0 * at(0, -1) + at(1, -1));
+    (gx, gy)
+}
\ No newline at end of file
@@ -0,0 +1,84 @@
+// Minimal page around an inline SVG: the drawing fills the window and the
+// script pans by dragging and zooms with the mouse wheel around the cursor
+const PAGE_START: &str = r##"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>Pixelator</title>
+<style>
+html, body { margin: 0; height: 100%; overflow: hidden; background: #808080; }
+#viewer { width: 100%; height: 100%; cursor: grab; touch-action: none; }
+#viewer > svg { width: 100%; height: 100%; }
+</style>
+</head>
+<body>
+<div id="viewer">
+"##;
+
+const PAGE_END: &str = r##"
+</div>
+<script id="pixelator-viewer">
+(function () {
+  var svg = document.querySelector("#viewer > svg");
+  var box = svg.viewBox.baseVal;
+  var initial = { x: box.x, y: box.y, w: box.width, h: box.height };
+  var view = Object.assign({}, initial);
+  svg.removeAttribute("width");
+  svg.removeAttribute("height");
+  function apply() {
+    svg.setAttribute("viewBox", view.x + " " + view.y + " " + view.w + " " + view.h);
+  }
+  // viewBox units per screen pixel, as fitted by preserveAspectRatio meet
+  function metrics() {
+    var rect = svg.getBoundingClientRect();
+    return { scale: Math.max(view.w / rect.width, view.h / rect.height), rect: rect };
+  }
+  svg.addEventListener("wheel", function (event) {
+    event.preventDefault();
+    var t = metrics();
+    var factor = event.deltaY < 0 ? 0.8 : 1.25;
+    var px = view.x + (event.clientX - t.rect.left - (t.rect.width - view.w / t.scale) / 2) * t.scale;
+    var py = view.y + (event.clientY - t.rect.top - (t.rect.height - view.h / t.scale) / 2) * t.scale;
+    view.x = px - (px - view.x) * factor;
+    view.y = py - (py - view.y) * factor;
+    view.w *= factor;
+    view.h *= factor;
+    apply();
+  }, { passive: false });
+  var drag = null;
+  svg.addEventListener("pointerdown", function (event) {
+    drag = { x: event.clientX, y: event.clientY };
+    svg.setPointerCapture(event.pointerId);
+  });
+  svg.addEventListener("pointermove", function (event) {
+    if (!drag) { return; }
+    var scale = metrics().scale;
+    view.x -= (event.clientX - drag.x) * scale;
+    view.y -= (event.clientY - drag.y) * scale;
+    drag = { x: event.clientX, y: event.clientY };
+    apply();
+  });
+  svg.addEventListener("pointerup", function () { drag = null; });
+  svg.addEventListener("dblclick", function () {
+    view = Object.assign({}, initial);
+    apply();
+  });
+})();
+</script>
+</body>
+</html>
+"##;
+
+/// Wraps an SVG document in a standalone HTML viewer page
+///
+/// The SVG is embedded inline and scaled to fill the window. Dragging pans,
+/// the mouse wheel zooms around the cursor and a double click resets the
+/// view. The page has no external dependencies.
+pub fn viewer_page(svg: &str) -> String {
+    let mut page = String::with_capacity(PAGE_START.len() + svg.len() + PAGE_END.len());
+    page.push_str(PAGE_START);
+    page.push_str(svg);
+    page.push_str(PAGE_END);
+    page
+}
\ No newline at end of file
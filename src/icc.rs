@@ -0,0 +1,142 @@
+use image::codecs::jpeg::JpegDecoder;
+use image::codecs::png::PngDecoder;
+use image::codecs::tiff::TiffDecoder;
+use image::codecs::webp::WebPDecoder;
+use image::{DynamicImage, ImageDecoder, ImageFormat};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+// ICC profiles start with a fixed 128-byte header followed by the tag table
+const ICC_HEADER_SIZE: usize = 128;
+const ICC_TAG_ENTRY_SIZE: usize = 12;
+
+// Linear RGB conversions to sRGB primaries, both relative to D65
+const DISPLAY_P3_TO_SRGB: [[f32; 3]; 3] = [
+    [1.2249, -0.2247, 0.0],
+    [-0.0420, 1.0419, 0.0],
+    [-0.0197, -0.0786, 1.0979],
+];
+const ADOBE_RGB_TO_SRGB: [[f32; 3]; 3] = [
+    [1.3982, -0.3982, 0.0],
+    [0.0, 1.0, 0.0],
+    [0.0, -0.0429, 1.0429],
+];
+
+// Adobe RGB (1998) encodes with a pure power curve of 563/256
+const ADOBE_RGB_GAMMA: f32 = 2.199_218_8;
+
+/// RGB color spaces recognized from an embedded ICC profile
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColorProfile {
+    /// Standard sRGB; needs no conversion
+    Srgb,
+    /// Display P3: sRGB transfer curve with wider primaries
+    DisplayP3,
+    /// Adobe RGB (1998)
+    AdobeRgb,
+}
+
+/// Reads the embedded ICC profile of a PNG, JPEG, TIFF or WebP file
+pub fn read_icc_profile<P: AsRef<Path>>(path: P) -> Option<Vec<u8>> {
+    let format = ImageFormat::from_path(&path).ok()?;
+    let reader = BufReader::new(File::open(&path).ok()?);
+    match format {
+        ImageFormat::Png => PngDecoder::new(reader).ok()?.icc_profile(),
+        ImageFormat::Jpeg => JpegDecoder::new(reader).ok()?.icc_profile(),
+        ImageFormat::Tiff => TiffDecoder::new(reader).ok()?.icc_profile(),
+        ImageFormat::WebP => WebPDecoder::new(reader).ok()?.icc_profile(),
+        _ => None,
+    }
+}
+
+/// Identifies the color space of an ICC profile from its description tag
+///
+/// Only the common RGB profiles are recognized; anything else returns `None`
+/// and is treated as sRGB.
+pub fn identify_profile(icc: &[u8]) -> Option<ColorProfile> {
+    let description = profile_description(icc)?.to_lowercase();
+    if description.contains("p3") {
+        Some(ColorProfile::DisplayP3)
+    } else if description.contains("adobe rgb") {
+        Some(ColorProfile::AdobeRgb)
+    } else if description.contains("srgb") {
+        Some(ColorProfile::Srgb)
+    } else {
+        None
+    }
+}
+
+/// Converts an image from the given color space to sRGB
+///
+/// Colors are linearized, mapped to sRGB primaries with a fixed matrix and
+/// re-encoded; out-of-gamut values are clipped. Alpha is left untouched.
+pub fn convert_to_srgb(image: DynamicImage, profile: ColorProfile) -> DynamicImage {
+    let (matrix, decode): (_, fn(f32) -> f32) = match profile {
+        ColorProfile::Srgb => return image,
+        ColorProfile::DisplayP3 => (DISPLAY_P3_TO_SRGB, srgb_to_linear),
+        ColorProfile::AdobeRgb => (ADOBE_RGB_TO_SRGB, |c| c.powf(ADOBE_RGB_GAMMA)),
+    };
+
+    let mut rgba = image.to_rgba8();
+    for pixel in rgba.pixels_mut() {
+        let linear = [0, 1, 2].map(|i| decode(pixel[i] as f32 / 255.0));
+        for (channel, row) in matrix.iter().enumerate() {
+            let value = row[0] * linear[0] + row[1] * linear[1] + row[2] * linear[2];
+            pixel[channel] = (linear_to_srgb(value.clamp(0.0, 1.0)) * 255.0).round() as u8;
+        }
+    }
+    DynamicImage::ImageRgba8(rgba)
+}
+
+fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.003_130_8 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+// Extracts the text of the `desc` tag, in either the v2 `desc` or v4 `mluc` form
+fn profile_description(icc: &[u8]) -> Option<String> {
+    let read_u32 = |at: usize| icc.get(at..at + 4).map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]) as usize);
+
+    // The count comes from the file; never scan past the end of the data
+    let count = read_u32(ICC_HEADER_SIZE)?.min((icc.len() - ICC_HEADER_SIZE - 4) / ICC_TAG_ENTRY_SIZE);
+    let (offset, size) = (0..count).find_map(|i| {
+        let entry = ICC_HEADER_SIZE + 4 + i * ICC_TAG_ENTRY_SIZE;
+        if icc.get(entry..entry + 4)? != b"desc" {
+            return None;
+        }
+        Some((read_u32(entry + 4)?, read_u32(entry + 8)?))
+    })?;
+    let tag = icc.get(offset..offset.checked_add(size)?)?;
+
+    match tag.get(0..4)? {
+        b"desc" => {
+            let length = read_u32(offset + 8)?;
+            let text = tag.get(12..12 + length)?;
+            Some(String::from_utf8_lossy(text).trim_end_matches('\0').to_string())
+        }
+        b"mluc" => {
+            // First localized record: length and offset of UTF-16BE text
+            let length = read_u32(offset + 20)?;
+            let start = read_u32(offset + 24)?;
+            let units: Vec<u16> = tag
+                .get(start..start + length)?
+                .chunks_exact(2)
+                .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+                .collect();
+            Some(String::from_utf16_lossy(&units))
+        }
+        _ => None,
+    }
+}
\ No newline at end of file
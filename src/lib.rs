@@ -1,17 +1,42 @@
+pub mod color;
 pub mod config;
+pub mod contour;
+pub mod dither;
+pub mod dot_file;
+pub mod dpi;
+pub mod format;
+pub mod gcode_generator;
+pub mod hatch;
+pub mod html;
+pub mod icc;
+pub mod importance;
+pub mod line_screen;
+pub mod palette;
 pub mod processor;
+pub mod render;
+mod rng;
 pub mod svg_generator;
+pub mod vector;
+pub mod voronoi;
 pub mod error;
 
 #[cfg(test)]
 mod tests;
 
 pub use config::PixelatorConfig;
+pub use dot_file::DotFile;
+pub use gcode_generator::GcodeGenerator;
 pub use processor::ImageProcessor;
+pub use render::Render;
 pub use svg_generator::SvgGenerator;
 pub use error::{PixelatorError, Result};
 
-use std::path::Path;
+use processor::PixelData;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
 
 /// Main structure for converting images to SVG circle art
 /// 
@@ -25,12 +50,25 @@ use std::path::Path;
 /// ```
 pub struct Pixelator {
     config: PixelatorConfig,
+    warnings: Mutex<Vec<String>>,
 }
 
 impl Pixelator {
     /// Creates a new Pixelator instance with the given configuration
     pub fn new(config: PixelatorConfig) -> Self {
-        Self { config }
+        Self { config, warnings: Mutex::new(Vec::new()) }
+    }
+
+    /// Returns and clears the warnings collected while processing images
+    ///
+    /// Adjustments made to fit an image, such as spacing widened to respect
+    /// the element cap, are reported here instead of printed.
+    pub fn take_warnings(&self) -> Vec<String> {
+        std::mem::take(&mut *self.warnings.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+
+    fn warn(&self, warnings: impl IntoIterator<Item = String>) {
+        self.warnings.lock().unwrap_or_else(|e| e.into_inner()).extend(warnings);
     }
 
     /// Processes an image and returns the SVG content as a string
@@ -41,16 +79,288 @@ impl Pixelator {
     /// # Returns
     /// * `Result<String>` - The SVG content or an error
     pub fn process_image<P: AsRef<Path>>(&self, input_path: P) -> Result<String> {
-        let image = image::open(input_path)?;
+        let dots = self.sample(&input_path)?;
         
-        let processor = ImageProcessor::new(&self.config);
-        let sampled_pixels = processor.sample_image(&image)?;
+        let svg_gen = Self::svg_generator(&dots, &input_path)?;
+        let svg_content = svg_gen.generate_svg(&dots.dots, dots.width, dots.height)?;
+        self.warn(svg_gen.take_warnings());
         
-        let svg_gen = SvgGenerator::new(&self.config);
-        let svg_content = svg_gen.generate_svg(&sampled_pixels, image.width(), image.height())?;
+        Ok(svg_content)
+    }
+    
+    /// Processes an image like `process_image`, aborting once `cancel` is set
+    /// 
+    /// The flag is checked before decoding, before each row of dots while
+    /// sampling in parallel, and around SVG building. A cancelled run returns
+    /// `PixelatorError::Processing("cancelled")`.
+    pub fn process_image_cancellable<P: AsRef<Path>>(&self, input_path: P, cancel: &AtomicBool) -> Result<String> {
+        let check = || {
+            if cancel.load(Ordering::Relaxed) {
+                return Err(PixelatorError::Processing("cancelled".to_string()));
+            }
+            Ok(())
+        };
+        
+        check()?;
+        let dots = self.sample_with_cancel(&input_path, Some(cancel))?;
+        check()?;
+        let svg_gen = Self::svg_generator(&dots, &input_path)?;
+        let svg_content = svg_gen.generate_svg(&dots.dots, dots.width, dots.height)?;
+        self.warn(svg_gen.take_warnings());
+        check()?;
         
         Ok(svg_content)
     }
+    
+    /// Processes an image and returns the SVG content as UTF-8 bytes
+    pub fn process_image_bytes<P: AsRef<Path>>(&self, input_path: P) -> Result<Vec<u8>> {
+        let dots = self.sample(&input_path)?;
+        let svg_gen = Self::svg_generator(&dots, &input_path)?;
+        let bytes = svg_gen.generate_svg_bytes(&dots.dots, dots.width, dots.height)?;
+        self.warn(svg_gen.take_warnings());
+        Ok(bytes)
+    }
+    
+    /// Processes an image and writes a standalone HTML page viewing the SVG
+    /// 
+    /// The SVG is embedded inline with a small script for panning and
+    /// zooming, so the file can be opened and shared directly.
+    pub fn process_image_to_html<P: AsRef<Path>, Q: AsRef<Path>>(
+        &self,
+        input_path: P,
+        output_path: Q,
+    ) -> Result<()> {
+        let page = html::viewer_page(&self.process_image(input_path)?);
+        write_atomically(output_path, |writer| Ok(writer.write_all(page.as_bytes())?))
+    }
+    
+    /// Processes an image and returns the hex SHA-256 digest of the SVG bytes
+    /// 
+    /// Useful for regression checks: the same image and configuration give
+    /// the same digest without storing the whole file. Randomized features
+    /// (stipple, jitter) need a seed for the output to be reproducible.
+    pub fn output_digest<P: AsRef<Path>>(&self, input_path: P) -> Result<String> {
+        use sha2::{Digest, Sha256};
+        
+        let digest = Sha256::digest(self.process_image_bytes(input_path)?);
+        Ok(digest.iter().map(|byte| format!("{:02x}", byte)).collect())
+    }
+    
+    /// Processes an image into a coarse preview SVG
+    /// 
+    /// The dot pitch, dot sizes and every other length in pixels are
+    /// multiplied by `downsample`, leaving about 1/downsample² as many dots
+    /// while keeping the same document size and viewBox as the full render,
+    /// so a preview can be swapped for the final output in place.
+    pub fn process_image_preview<P: AsRef<Path>>(&self, input_path: P, downsample: u32) -> Result<String> {
+        if downsample == 0 {
+            return Err(PixelatorError::InvalidConfig(
+                "Preview downsample factor must be at least 1".to_string(),
+            ));
+        }
+        
+        let factor = downsample as f32;
+        let mut config = self.config.scale_pixel_lengths(factor);
+        // Sizes resolved at sample time coarsen by the same factor
+        config.physical_dot_mm = config.physical_dot_mm.map(|(diameter, spacing)| (diameter * factor, spacing * factor));
+        config.lpi = config.lpi.map(|lpi| lpi / factor);
+        
+        let preview = Pixelator::new(config);
+        let svg = preview.process_image(input_path)?;
+        self.warn(preview.take_warnings());
+        Ok(svg)
+    }
+    
+    /// Processes an image and streams the output of `renderer` to `writer`
+    /// 
+    /// Works with any output format implementing `Render`. The renderer is
+    /// built by the caller and renders with its own configuration, which
+    /// should normally be the one this `Pixelator` was created with.
+    pub fn process_image_to_writer<R: Render, P: AsRef<Path>, W: std::io::Write>(
+        &self,
+        renderer: &R,
+        input_path: P,
+        writer: &mut W,
+    ) -> Result<()> {
+        let dots = self.sample(input_path)?;
+        renderer.render(writer, &dots.dots, dots.width, dots.height)
+    }
+    
+    /// Samples several images and composites their dots into one SVG
+    /// 
+    /// Each input is placed at its `(x, y)` offset in image pixels, and the
+    /// document is sized to the bounding box of all placed images. Later
+    /// images draw on top of earlier ones. Grid positions are dropped since
+    /// they are only meaningful within a single image.
+    /// 
+    /// The composite is rendered with the configuration the images were
+    /// sampled with. Settings resolved per image, such as the element cap,
+    /// must come out the same for every input; otherwise an `InvalidConfig`
+    /// error is returned.
+    pub fn process_images_to_svg(&self, inputs: &[(PathBuf, (f32, f32))]) -> Result<String> {
+        if inputs.is_empty() {
+            return Err(PixelatorError::InvalidConfig(
+                "At least one input image is required".to_string(),
+            ));
+        }
+        
+        let mut placed = Vec::with_capacity(inputs.len());
+        let (mut min_x, mut min_y) = (f32::MAX, f32::MAX);
+        let (mut max_x, mut max_y) = (f32::MIN, f32::MIN);
+        for (path, (dx, dy)) in inputs {
+            let dots = self.sample(path)?;
+            if let Some((first, _)) = placed.first() {
+                Self::check_shared_config(first, &dots, path)?;
+            }
+            min_x = min_x.min(*dx);
+            min_y = min_y.min(*dy);
+            max_x = max_x.max(dx + dots.width as f32);
+            max_y = max_y.max(dy + dots.height as f32);
+            placed.push((dots, (*dx, *dy)));
+        }
+        
+        // Shift everything so the bounding box starts at the origin
+        let pixels: Vec<PixelData> = placed
+            .iter()
+            .flat_map(|(dots, (dx, dy))| {
+                dots.dots.iter().map(move |p| PixelData {
+                    x: p.x + dx - min_x,
+                    y: p.y + dy - min_y,
+                    grid_position: None,
+                    ..p.clone()
+                })
+            })
+            .collect();
+        
+        let width = (max_x - min_x).ceil() as u32;
+        let height = (max_y - min_y).ceil() as u32;
+        let svg_gen = SvgGenerator::new(&placed[0].0.config);
+        let svg = svg_gen.generate_svg(&pixels, width, height)?;
+        self.warn(svg_gen.take_warnings());
+        Ok(svg)
+    }
+    
+    // Composited images are rendered together, so they must have been
+    // sampled with the same resolved configuration
+    fn check_shared_config(first: &DotFile, dots: &DotFile, path: &Path) -> Result<()> {
+        if serde_json::to_value(&first.config)? == serde_json::to_value(&dots.config)? {
+            return Ok(());
+        }
+        Err(PixelatorError::InvalidConfig(format!(
+            "{} resolves to a different configuration than the first image; composited images must share dot sizes and output dimensions",
+            path.display()
+        )))
+    }
+    
+    /// Samples an image and returns the dots and configuration as JSON
+    /// 
+    /// The result can be rendered later with `SvgGenerator::from_dot_json`
+    /// without decoding or sampling the image again.
+    pub fn process_image_json<P: AsRef<Path>>(&self, input_path: P) -> Result<String> {
+        self.sample(input_path)?.to_json()
+    }
+    
+    /// Samples an image and writes the dots and configuration to a JSON file
+    pub fn process_image_to_json<P: AsRef<Path>, Q: AsRef<Path>>(
+        &self,
+        input_path: P,
+        output_path: Q,
+    ) -> Result<()> {
+        let json = self.process_image_json(input_path)?;
+        write_atomically(output_path, |writer| Ok(writer.write_all(json.as_bytes())?))
+    }
+    
+    /// Samples an image and writes the dots as CSV rows of `x,y,r,g,b,size`
+    /// In grid mode each row also carries the dot's column and row index
+    pub fn process_image_to_csv<P: AsRef<Path>, Q: AsRef<Path>>(
+        &self,
+        input_path: P,
+        output_path: Q,
+    ) -> Result<()> {
+        let csv = self.sample(input_path)?.to_csv();
+        write_atomically(output_path, |writer| Ok(writer.write_all(csv.as_bytes())?))
+    }
+    
+    /// Samples an image and writes a G-code program tracing every dot
+    pub fn process_image_to_gcode<P: AsRef<Path>, Q: AsRef<Path>>(
+        &self,
+        input_path: P,
+        output_path: Q,
+    ) -> Result<()> {
+        let dots = self.sample(input_path)?;
+        let gcode = GcodeGenerator::new(&dots.config).generate_gcode(&dots.dots, dots.width, dots.height)?;
+        write_atomically(output_path, |writer| Ok(writer.write_all(gcode.as_bytes())?))
+    }
+    
+    /// Decodes and samples an image into a dot file
+    /// 
+    /// The stored configuration is the one resolved for the image size, so the
+    /// dot file can be rendered to any supported format afterwards.
+    pub fn sample<P: AsRef<Path>>(&self, input_path: P) -> Result<DotFile> {
+        self.sample_with_cancel(input_path, None)
+    }
+    
+    fn sample_with_cancel<P: AsRef<Path>>(&self, input_path: P, cancel: Option<&AtomicBool>) -> Result<DotFile> {
+        self.config.check_physical_dot_size()?;
+        let mut image = vector::open_image(&input_path, self.config.svg_input_dpi)?;
+        let sized = self.source_dpi_config(&input_path);
+        let (config, warnings) = sized.as_ref().unwrap_or(&self.config).resolve_with_warnings(image.width(), image.height());
+        self.warn(warnings);
+        
+        if !config.assume_srgb {
+            // Wide-gamut sources would otherwise be read as oversaturated sRGB
+            if let Some(profile) = icc::read_icc_profile(&input_path).and_then(|icc| icc::identify_profile(&icc)) {
+                image = icc::convert_to_srgb(image, profile);
+            }
+        }
+        
+        let mut processor = ImageProcessor::new(&config);
+        if config.palette_size.is_some() {
+            // Indexed PNGs already carry a palette; reuse it instead of re-clustering
+            if let Some(source) = palette::read_png_palette(&input_path) {
+                processor = processor.with_source_palette(source);
+            }
+        }
+        if let Some(cancel) = cancel {
+            processor = processor.with_cancel_flag(cancel);
+        }
+        let dots = processor.sample_image(&image)?;
+        
+        Ok(DotFile {
+            config,
+            width: image.width(),
+            height: image.height(),
+            dots,
+        })
+    }
+
+    // SVG generator for a sampled image, given the source again when the
+    // configuration asks for a reference layer
+    fn svg_generator<'a, P: AsRef<Path>>(dots: &'a DotFile, input_path: P) -> Result<SvgGenerator<'a>> {
+        let generator = SvgGenerator::new(&dots.config);
+        if dots.config.reference_layer.is_none() {
+            return Ok(generator);
+        }
+        generator.with_reference_image(&vector::open_image(input_path, dots.config.svg_input_dpi)?)
+    }
+
+    // Configuration with the physical dot size converted at the image's DPI,
+    // when enabled
+    fn source_dpi_config<P: AsRef<Path>>(&self, input_path: P) -> Option<PixelatorConfig> {
+        if !self.config.respect_source_dpi || self.config.physical_dot_mm.is_none() {
+            return None;
+        }
+        match dpi::read_dpi(&input_path) {
+            Some(dpi) => Some(self.config.at_source_dpi(dpi)),
+            None => {
+                self.warn([format!(
+                    "{} has no DPI metadata; using the configured pixel dot size",
+                    input_path.as_ref().display()
+                )]);
+                None
+            }
+        }
+    }
 
     /// Processes an image and writes the SVG to a file
     /// 
@@ -60,13 +370,49 @@ impl Pixelator {
     /// 
     /// # Returns
     /// * `Result<()>` - Success or an error
+    /// 
+    /// The SVG is written to a temporary file next to `output_path` and
+    /// renamed into place once complete, so a failed write never leaves a
+    /// truncated file at the destination.
     pub fn process_image_to_file<P: AsRef<Path>, Q: AsRef<Path>>(
         &self,
         input_path: P,
         output_path: Q,
     ) -> Result<()> {
         let svg_content = self.process_image(input_path)?;
-        std::fs::write(output_path, svg_content)?;
+        write_atomically(output_path, |writer| Ok(writer.write_all(svg_content.as_bytes())?))
+    }
+}
+
+/// Writes a file through a temporary sibling that is renamed into place on success
+/// 
+/// If `write` fails, or the data cannot be flushed, the temporary file is
+/// removed and any existing file at `path` is left untouched.
+pub fn write_atomically<P, F>(path: P, write: F) -> Result<()>
+where
+    P: AsRef<Path>,
+    F: FnOnce(&mut dyn Write) -> Result<()>,
+{
+    let path = path.as_ref();
+    let name = path.file_name().ok_or_else(|| {
+        PixelatorError::InvalidConfig(format!("Output path has no file name: {}", path.display()))
+    })?;
+    let mut temp_name = std::ffi::OsString::from(".");
+    temp_name.push(name);
+    temp_name.push(format!(".tmp-{}", std::process::id()));
+    let temp_path = path.with_file_name(temp_name);
+    
+    let result = File::create(&temp_path).map_err(PixelatorError::from).and_then(|file| {
+        let mut writer = BufWriter::new(file);
+        write(&mut writer)?;
+        writer.into_inner().map_err(|e| e.into_error())?.sync_all()?;
         Ok(())
+    });
+    match result.and_then(|()| Ok(std::fs::rename(&temp_path, path)?)) {
+        Ok(()) => Ok(()),
+        Err(e) => {
+            let _ = std::fs::remove_file(&temp_path);
+            Err(e)
+        }
     }
 }
\ No newline at end of file
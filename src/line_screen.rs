@@ -0,0 +1,112 @@
+use crate::config::HalftoneCurve;
+use crate::processor::PixelData;
+use crate::voronoi::Bounds;
+use std::collections::HashMap;
+
+/// A straight stroke of a line screen, drawn with butt caps
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LineSegment {
+    pub x1: f32,
+    pub y1: f32,
+    pub x2: f32,
+    pub y2: f32,
+    pub width: f32,
+}
+
+/// Builds a line-screen halftone covering `bounds`
+///
+/// Parallel scan lines run at `angle` degrees, `frequency` lines per dot
+/// cell of side `cell_size`. Each line is split into segments one line pitch
+/// long whose stroke width follows the darkness of the nearest sampled dot,
+/// from zero for white up to the full pitch for black.
+pub fn line_screen(
+    pixels: &[PixelData],
+    bounds: Bounds,
+    cell_size: f32,
+    angle: f32,
+    frequency: f32,
+    curve: &HalftoneCurve,
+) -> Vec<LineSegment> {
+    if pixels.is_empty() || cell_size <= 0.0 || frequency <= 0.0 {
+        return Vec::new();
+    }
+
+    let pitch = cell_size / frequency;
+    let (sin, cos) = angle.to_radians().sin_cos();
+    let (dir, normal) = ((cos, sin), (-sin, cos));
+
+    // Extent of the bounds along the line direction and across the lines
+    let (min_x, min_y, max_x, max_y) = bounds;
+    let corners = [(min_x, min_y), (max_x, min_y), (min_x, max_y), (max_x, max_y)];
+    let project = |axis: (f32, f32)| {
+        corners.iter().fold((f32::MAX, f32::MIN), |(min, max), &(x, y)| {
+            let value = x * axis.0 + y * axis.1;
+            (min.min(value), max.max(value))
+        })
+    };
+    let (along_min, along_max) = project(dir);
+    let (across_min, across_max) = project(normal);
+
+    let bins = bin_pixels(pixels, cell_size);
+    let mut segments = Vec::new();
+
+    let mut offset = (across_min / pitch).floor() * pitch + pitch / 2.0;
+    while offset < across_max {
+        let mut along = along_min;
+        while along < along_max {
+            let mid = along + pitch / 2.0;
+            let (mx, my) = (offset * normal.0 + mid * dir.0, offset * normal.1 + mid * dir.1);
+
+            if (min_x..=max_x).contains(&mx) && (min_y..=max_y).contains(&my) {
+                if let Some(pixel) = nearest_pixel(pixels, &bins, cell_size, mx, my) {
+                    let stroke = curve.apply(1.0 - pixel.brightness) * pitch;
+                    if stroke > 0.0 {
+                        let half = pitch / 2.0;
+                        segments.push(LineSegment {
+                            x1: mx - dir.0 * half,
+                            y1: my - dir.1 * half,
+                            x2: mx + dir.0 * half,
+                            y2: my + dir.1 * half,
+                            width: stroke,
+                        });
+                    }
+                }
+            }
+            along += pitch;
+        }
+        offset += pitch;
+    }
+
+    segments
+}
+
+// Buckets pixel indices by the dot cell they fall in
+fn bin_pixels(pixels: &[PixelData], cell_size: f32) -> HashMap<(i64, i64), Vec<usize>> {
+    let mut bins: HashMap<(i64, i64), Vec<usize>> = HashMap::new();
+    for (i, pixel) in pixels.iter().enumerate() {
+        let key = ((pixel.x / cell_size).floor() as i64, (pixel.y / cell_size).floor() as i64);
+        bins.entry(key).or_default().push(i);
+    }
+    bins
+}
+
+// Finds the closest pixel among the 3x3 cells around a point
+fn nearest_pixel<'p>(
+    pixels: &'p [PixelData],
+    bins: &HashMap<(i64, i64), Vec<usize>>,
+    cell_size: f32,
+    x: f32,
+    y: f32,
+) -> Option<&'p PixelData> {
+    let (col, row) = ((x / cell_size).floor() as i64, (y / cell_size).floor() as i64);
+    (row - 1..=row + 1)
+        .flat_map(|r| (col - 1..=col + 1).map(move |c| (c, r)))
+        .filter_map(|key| bins.get(&key))
+        .flatten()
+        .map(|&i| &pixels[i])
+        .min_by(|a, b| {
+            let da = (a.x - x).powi(2) + (a.y - y).powi(2);
+            let db = (b.x - x).powi(2) + (b.y - y).powi(2);
+            da.total_cmp(&db)
+        })
+}
\ No newline at end of file
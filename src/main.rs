@@ -1,7 +1,21 @@
 use anyhow::Result;
-use clap::{Parser, ValueEnum};
-use pixelator::{Pixelator, PixelatorConfig, config::{SampleMode, RenderMode, HalftoneStyle}};
-use std::path::PathBuf;
+use clap::parser::ValueSource;
+use clap::{CommandFactory, FromArgMatches, Parser, ValueEnum};
+use pixelator::{GcodeGenerator, Pixelator, PixelatorConfig, SvgGenerator, config::{SampleMode, RenderMode, HalftoneStyle, HalftoneCurve, Origin, QuantizeSpace, CbType, BrightnessMetric, GridFit, DitherMode, PatternKind, PaintOrder, HatchOrientation, EdgeMode, HexOrientation, DistortionPolicy, OverlapPolicy, SizeSource}};
+use std::path::{Path, PathBuf};
+
+// Default fixed dot size for the FM halftone render mode
+const DEFAULT_FM_DOT_SIZE: f32 = 1.0;
+
+// Default angle (degrees) and lines per dot cell for the line screen render mode
+const DEFAULT_SCREEN_ANGLE: f32 = 45.0;
+const DEFAULT_SCREEN_FREQUENCY: f32 = 1.0;
+
+// Default number of darkness bands for the contours render mode
+const DEFAULT_CONTOUR_LEVELS: u8 = 4;
+
+// Default brightness below which the threshold render mode places a dot
+const DEFAULT_THRESHOLD_LEVEL: f32 = 0.5;
 
 #[derive(Debug, Clone, Copy, ValueEnum)]
 enum SampleModeArg {
@@ -24,6 +38,18 @@ enum RenderModeArg {
     Color,
     HalftoneBlack,
     HalftoneWhite,
+    SaturationMap,
+    Voronoi,
+    FmHalftone,
+    LineScreen,
+    Stipple,
+    Hatch,
+    HatchFollow,
+    PointCloud,
+    ColorByNumber,
+    Contours,
+    Threshold,
+    MorphShape,
 }
 
 impl From<RenderModeArg> for RenderMode {
@@ -32,81 +58,838 @@ impl From<RenderModeArg> for RenderMode {
             RenderModeArg::Color => RenderMode::Color,
             RenderModeArg::HalftoneBlack => RenderMode::Halftone(HalftoneStyle::BlackOnWhite),
             RenderModeArg::HalftoneWhite => RenderMode::Halftone(HalftoneStyle::WhiteOnBlack),
+            RenderModeArg::SaturationMap => RenderMode::SaturationMap,
+            RenderModeArg::Voronoi => RenderMode::Voronoi,
+            RenderModeArg::FmHalftone => RenderMode::FmHalftone { dot_size: DEFAULT_FM_DOT_SIZE },
+            RenderModeArg::LineScreen => RenderMode::LineScreen {
+                angle: DEFAULT_SCREEN_ANGLE,
+                frequency: DEFAULT_SCREEN_FREQUENCY,
+            },
+            RenderModeArg::Stipple => RenderMode::Stipple { dot_size: DEFAULT_FM_DOT_SIZE, seed: None },
+            RenderModeArg::Hatch => RenderMode::Hatch(HatchOrientation::Fixed(DEFAULT_SCREEN_ANGLE)),
+            RenderModeArg::HatchFollow => RenderMode::Hatch(HatchOrientation::FollowStructure),
+            RenderModeArg::PointCloud => RenderMode::PointCloud,
+            RenderModeArg::ColorByNumber => RenderMode::ColorByNumber,
+            RenderModeArg::Contours => RenderMode::Contours { levels: DEFAULT_CONTOUR_LEVELS },
+            RenderModeArg::Threshold => RenderMode::Threshold { level: DEFAULT_THRESHOLD_LEVEL },
+            RenderModeArg::MorphShape => RenderMode::MorphShape,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum QuantizeSpaceArg {
+    Rgb,
+    Lab,
+}
+
+impl From<QuantizeSpaceArg> for QuantizeSpace {
+    fn from(space: QuantizeSpaceArg) -> Self {
+        match space {
+            QuantizeSpaceArg::Rgb => QuantizeSpace::Rgb,
+            QuantizeSpaceArg::Lab => QuantizeSpace::Lab,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum HalftoneCurveArg {
+    Linear,
+    Gamma,
+    Sine,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum OriginArg {
+    TopLeft,
+    Center,
+    BottomLeft,
+}
+
+impl From<OriginArg> for Origin {
+    fn from(origin: OriginArg) -> Self {
+        match origin {
+            OriginArg::TopLeft => Origin::TopLeft,
+            OriginArg::Center => Origin::Center,
+            OriginArg::BottomLeft => Origin::BottomLeft,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum CbTypeArg {
+    Deuteranopia,
+    Protanopia,
+    Tritanopia,
+}
+
+impl From<CbTypeArg> for CbType {
+    fn from(cb_type: CbTypeArg) -> Self {
+        match cb_type {
+            CbTypeArg::Deuteranopia => CbType::Deuteranopia,
+            CbTypeArg::Protanopia => CbType::Protanopia,
+            CbTypeArg::Tritanopia => CbType::Tritanopia,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum BrightnessMetricArg {
+    Luminance,
+    Lightness,
+}
+
+impl From<BrightnessMetricArg> for BrightnessMetric {
+    fn from(metric: BrightnessMetricArg) -> Self {
+        match metric {
+            BrightnessMetricArg::Luminance => BrightnessMetric::Luminance,
+            BrightnessMetricArg::Lightness => BrightnessMetric::CieLStar,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum SizeSourceArg {
+    Brightness,
+    Alpha,
+    Saturation,
+}
+
+impl From<SizeSourceArg> for SizeSource {
+    fn from(source: SizeSourceArg) -> Self {
+        match source {
+            SizeSourceArg::Brightness => SizeSource::Brightness,
+            SizeSourceArg::Alpha => SizeSource::Alpha,
+            SizeSourceArg::Saturation => SizeSource::Saturation,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum GridFitArg {
+    Floor,
+    Round,
+    Ceil,
+    Stretch,
+}
+
+impl From<GridFitArg> for GridFit {
+    fn from(fit: GridFitArg) -> Self {
+        match fit {
+            GridFitArg::Floor => GridFit::Floor,
+            GridFitArg::Round => GridFit::Round,
+            GridFitArg::Ceil => GridFit::Ceil,
+            GridFitArg::Stretch => GridFit::Stretch,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum DitherModeArg {
+    None,
+    FloydSteinberg,
+    BlueNoise,
+}
+
+impl From<DitherModeArg> for DitherMode {
+    fn from(mode: DitherModeArg) -> Self {
+        match mode {
+            DitherModeArg::None => DitherMode::None,
+            DitherModeArg::FloydSteinberg => DitherMode::FloydSteinberg,
+            DitherModeArg::BlueNoise => DitherMode::BlueNoise,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum PatternKindArg {
+    Dots,
+    Grid,
+    Diagonal,
+}
+
+impl From<PatternKindArg> for PatternKind {
+    fn from(kind: PatternKindArg) -> Self {
+        match kind {
+            PatternKindArg::Dots => PatternKind::Dots,
+            PatternKindArg::Grid => PatternKind::Grid,
+            PatternKindArg::Diagonal => PatternKind::Diagonal,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum OverlapArg {
+    Allow,
+    Warn,
+    Reject,
+}
+
+impl From<OverlapArg> for OverlapPolicy {
+    fn from(policy: OverlapArg) -> Self {
+        match policy {
+            OverlapArg::Allow => OverlapPolicy::Allow,
+            OverlapArg::Warn => OverlapPolicy::Warn,
+            OverlapArg::Reject => OverlapPolicy::Reject,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum PaintOrderArg {
+    AsSampled,
+    DarkestLast,
+    LightestLast,
+}
+
+impl From<PaintOrderArg> for PaintOrder {
+    fn from(order: PaintOrderArg) -> Self {
+        match order {
+            PaintOrderArg::AsSampled => PaintOrder::AsSampled,
+            PaintOrderArg::DarkestLast => PaintOrder::DarkestLast,
+            PaintOrderArg::LightestLast => PaintOrder::LightestLast,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum DistortionArg {
+    Allow,
+    Warn,
+    Reject,
+}
+
+impl From<DistortionArg> for DistortionPolicy {
+    fn from(policy: DistortionArg) -> Self {
+        match policy {
+            DistortionArg::Allow => DistortionPolicy::Allow,
+            DistortionArg::Warn => DistortionPolicy::Warn,
+            DistortionArg::Reject => DistortionPolicy::Reject,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum EdgeModeArg {
+    Clamp,
+    Mirror,
+    Skip,
+}
+
+impl From<EdgeModeArg> for EdgeMode {
+    fn from(mode: EdgeModeArg) -> Self {
+        match mode {
+            EdgeModeArg::Clamp => EdgeMode::Clamp,
+            EdgeModeArg::Mirror => EdgeMode::Mirror,
+            EdgeModeArg::Skip => EdgeMode::Skip,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum HexOrientationArg {
+    Pointy,
+    Flat,
+}
+
+impl From<HexOrientationArg> for HexOrientation {
+    fn from(orientation: HexOrientationArg) -> Self {
+        match orientation {
+            HexOrientationArg::Pointy => HexOrientation::PointyTop,
+            HexOrientationArg::Flat => HexOrientation::FlatTop,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum PresetArg {
+    Newspaper,
+    PenPlotter,
+    Poster,
+}
+
+impl From<PresetArg> for PixelatorConfig {
+    fn from(preset: PresetArg) -> Self {
+        match preset {
+            PresetArg::Newspaper => PixelatorConfig::preset_newspaper(),
+            PresetArg::PenPlotter => PixelatorConfig::preset_pen_plotter(),
+            PresetArg::Poster => PixelatorConfig::preset_poster(),
         }
     }
 }
 
 #[derive(Parser, Debug)]
-#[command(author, version, about, long_about = None)]
+#[command(author, version, about, long_about = None, disable_help_flag = true)]
 struct Args {
-    #[arg(help = "Input image file path")]
-    input: PathBuf,
+    // `-h` is taken by --height-mm, so help is only available as --help
+    #[arg(long, action = clap::ArgAction::Help, help = "Print help")]
+    help: Option<bool>,
+
+    #[arg(
+        required = true,
+        num_args = 1..=2,
+        value_names = ["INPUT", "OUTPUT"],
+        help = "Input image and output SVG file paths; with --from-json only the output path"
+    )]
+    paths: Vec<PathBuf>,
 
-    #[arg(help = "Output SVG file path")]
+    // Split out of `paths` once parsed
+    #[arg(skip)]
+    input: PathBuf,
+    #[arg(skip)]
     output: PathBuf,
 
+    #[arg(long, value_enum, help = "Start from a preset look; options given explicitly override it")]
+    preset: Option<PresetArg>,
+
     #[arg(short = 'd', long, default_value = "10.0", help = "Circle diameter in pixels")]
     circle_diameter: f32,
 
     #[arg(short = 's', long, default_value = "2.0", help = "Spacing between circles in pixels")]
     circle_spacing: f32,
 
+    #[arg(long, requires = "dot_mm", help = "Derive the circle diameter and spacing in pixels from the image's DPI metadata and --dot-mm/--spacing-mm")]
+    source_dpi: bool,
+
+    #[arg(long, help = "Circle diameter in millimeters of the output (with -w/-h or a page), or of the source image with --source-dpi")]
+    dot_mm: Option<f32>,
+
+    #[arg(long, default_value = "0.0", help = "Circle spacing in millimeters, alongside --dot-mm")]
+    spacing_mm: f32,
+
     #[arg(short = 'w', long, help = "Output width in millimeters")]
     width_mm: Option<f32>,
 
     #[arg(short = 'h', long, help = "Output height in millimeters")]
     height_mm: Option<f32>,
 
+    #[arg(long, default_value = "warn", value_enum, help = "When --width-mm/--height-mm distort the image aspect ratio: allow, warn or reject")]
+    distortion: DistortionArg,
+
+    #[arg(long, default_value = "warn", value_enum, help = "When the largest dots overlap their neighbors: allow, warn or reject")]
+    overlap: OverlapArg,
+
+    #[arg(long, requires = "page_height", help = "Page width in millimeters; the output is fitted and centered on the page inside --margin")]
+    page_width: Option<f32>,
+
+    #[arg(long, requires = "page_width", help = "Page height in millimeters")]
+    page_height: Option<f32>,
+
+    #[arg(long, help = "Screen frequency in lines per inch; sets the spacing from the output size (needs -w and -h)")]
+    lpi: Option<f32>,
+
+    #[arg(long, default_value = "2", help = "Decimal places for the output width and height in millimeters")]
+    mm_decimals: usize,
+
     #[arg(short = 'b', long, help = "Background color (e.g., #FFFFFF or white)")]
     background: Option<String>,
 
+    #[arg(long, help = "Draw the background as a full-size <rect> instead of a CSS style")]
+    background_rect: bool,
+
+    #[arg(long, value_enum, help = "Fill the background with a repeating pattern")]
+    background_pattern: Option<PatternKindArg>,
+
+    #[arg(long, help = "Linear background gradient as offset:color stops, e.g. \"0:skyblue,1:saddlebrown\"")]
+    background_gradient: Option<String>,
+
+    #[arg(long, default_value = "90.0", help = "Background gradient direction in degrees clockwise from +x (90 = top to bottom)")]
+    gradient_angle: f32,
+
+    #[arg(long, default_value = "0.0", help = "Blank margin in millimeters around the dot field")]
+    margin: f32,
+
+    #[arg(long, help = "Draw a labeled scale bar of this length in millimeters (needs -w/-h and --margin)")]
+    scale_bar: Option<f32>,
+
+    #[arg(long, help = "Draw guide lines along the cell borders every N cells, like a bead pegboard (grid mode)")]
+    guide_grid: Option<usize>,
+
+    #[arg(long, default_value = "#cccccc", help = "Color of the --guide-grid lines")]
+    guide_color: String,
+
+    #[arg(long, help = "Draw crosshair registration marks at the four corners, in the margin when it fits")]
+    registration_marks: bool,
+
     #[arg(short = 'm', long, default_value = "grid", value_enum, help = "Sampling mode")]
     mode: SampleModeArg,
     
-    #[arg(short = 'r', long, default_value = "color", value_enum, help = "Render mode: color, halftone-black, halftone-white")]
+    #[arg(long, default_value = "floor", value_enum, help = "How grid mode fits columns/rows to the image size")]
+    fit: GridFitArg,
+    
+    #[arg(short = 'r', long, default_value = "color", value_enum, help = "Render mode: color, halftone-black, halftone-white, saturation-map, voronoi, fm-halftone, line-screen")]
     render: RenderModeArg,
     
+    #[arg(long, default_value_t = DEFAULT_FM_DOT_SIZE, help = "Fixed dot size for the fm-halftone and stipple render modes")]
+    fm_dot: f32,
+    
+    #[arg(long, default_value_t = DEFAULT_SCREEN_ANGLE, help = "Line angle in degrees for the line-screen and hatch render modes")]
+    screen_angle: f32,
+    
+    #[arg(long, default_value_t = DEFAULT_SCREEN_FREQUENCY, help = "Lines per dot cell for the line-screen render mode")]
+    screen_frequency: f32,
+    
+    #[arg(long, default_value_t = DEFAULT_CONTOUR_LEVELS, value_parser = clap::value_parser!(u8).range(2..), help = "Number of darkness bands for the contours render mode")]
+    contour_levels: u8,
+    
+    #[arg(long, default_value_t = DEFAULT_THRESHOLD_LEVEL, help = "Brightness (0.0 to 1.0) below which the threshold render mode places a dot")]
+    threshold: f32,
+    
+    #[arg(long, help = "Grayscale mask image scaling dot sizes (white = full size, black = none)")]
+    size_mask: Option<PathBuf>,
+    
+    #[arg(long, help = "Sprite image drawn tinted in place of each color dot, for a photo-mosaic look")]
+    tile_image: Option<PathBuf>,
+    
+    #[arg(long, help = "Black/white mask image; dots are kept only where the mask is white")]
+    clip_mask: Option<PathBuf>,
+    
     #[arg(long, help = "Minimum dot size for halftone mode")]
     min_dot: Option<f32>,
     
     #[arg(long, help = "Maximum dot size for halftone mode")]
     max_dot: Option<f32>,
+    
+    #[arg(long, default_value = "luminance", value_enum, help = "Brightness metric: luminance or CIE L* lightness")]
+    brightness: BrightnessMetricArg,
+    
+    #[arg(long, default_value = "brightness", value_enum, help = "Channel halftone dot sizes follow: brightness, alpha or saturation")]
+    size_source: SizeSourceArg,
+    
+    #[arg(long, help = "Snap halftone dot sizes to this many discrete levels")]
+    dot_levels: Option<u8>,
+    
+    #[arg(long, value_delimiter = ',', help = "Snap halftone dot sizes to the nearest of these comma-separated diameters in pixels, e.g. bead sizes")]
+    bead_sizes: Option<Vec<f32>>,
+    
+    #[arg(long, help = "Keep only N dots, placed where the image has the most detail (follows --seed)")]
+    importance_dots: Option<usize>,
+    
+    #[arg(long, help = "Treat transparent areas as background tone when computing brightness")]
+    alpha_brightness: bool,
+    
+    #[arg(long, default_value = "linear", value_enum, help = "Halftone dot size response curve")]
+    curve: HalftoneCurveArg,
+    
+    #[arg(long, default_value = "2.0", help = "Exponent used by the gamma halftone curve")]
+    gamma: f32,
+    
+    #[arg(long, default_value = "none", value_enum, help = "Dithering applied before halftone dot sizing")]
+    dither: DitherModeArg,
+    
+    #[arg(long, help = "Keep exact source pixel colors (point sampling for sprites and pixel art)")]
+    pixel_art: bool,
+    
+    #[arg(long, default_value = "0.0", help = "Unsharp mask amount applied to the image before sampling")]
+    sharpen: f32,
+    
+    #[arg(long, default_value = "1", help = "Supersampling factor per axis for anti-aliased colors")]
+    supersample: u8,
+    
+    #[arg(long, default_value = "clamp", value_enum, help = "Sampling at image borders: clamp the window, mirror it, or skip edge dots")]
+    edge: EdgeModeArg,
+    
+    #[arg(long, default_value = "pointy", value_enum, help = "Hexagon orientation in hexagonal mode: pointy (staggered rows) or flat (staggered columns)")]
+    hex_orientation: HexOrientationArg,
+    
+    #[arg(long, help = "Weight source pixels by the fraction of their area inside each dot")]
+    coverage: bool,
+    
+    #[arg(long, help = "Treat the image as sRGB even if it embeds a different ICC profile")]
+    assume_srgb: bool,
+    
+    #[arg(long, help = "Seed for all randomized features, making the output reproducible")]
+    seed: Option<u64>,
+    
+    #[arg(long, default_value = "0.0", help = "Maximum random offset of each dot center in pixels")]
+    jitter: f32,
+    
+    #[arg(long, default_value = "1.0", help = "Multiplier for the saturation of sampled colors (0 = grayscale, >1 = more vivid)")]
+    saturation: f32,
+    
+    #[arg(long, help = "Compensate halftone dots for this print dot gain in percent at 50% coverage (e.g. 15)")]
+    dot_gain: Option<f32>,
+    
+    #[arg(long, help = "Minimum gap in pixels between dot edges; spacing is widened to keep it")]
+    min_gap: Option<f32>,
+    
+    #[arg(long, help = "Smallest radius in pixels drawn for color dots, so tiny dots stay visible")]
+    min_radius: Option<f32>,
+    
+    #[arg(long, value_delimiter = ',', help = "Spot ink colors, darkest tone first: brightness is split into one band per ink, each drawn in its own layer")]
+    spot_inks: Option<Vec<String>>,
+    
+    #[arg(long, help = "Fill the gaps between every four neighboring dots with a smaller dot (grid mode)")]
+    fill_interstices: bool,
+    
+    #[arg(long, help = "Average sampled colors with straight alpha, letting transparent pixel colors bleed into edge dots")]
+    straight_alpha: bool,
+    
+    #[arg(long, help = "Resolution in DPI to rasterize SVG input at (needs the svg-input feature; default: 96)")]
+    svg_dpi: Option<f32>,
+    
+    #[arg(long, help = "Animate dots growing in one after another over this many seconds")]
+    reveal: Option<f32>,
+    
+    #[arg(long, help = "Round dot centers to multiples of this pitch in output millimeters (pegboard or bead board pitch)")]
+    snap_mm: Option<f32>,
+    
+    #[arg(long, help = "Embed the source image behind the dots at this opacity (0 to 1) as a reference layer")]
+    reference_layer: Option<f32>,
+    
+    #[arg(long, help = "Fade dots out toward the image border over this many pixels")]
+    edge_fade: Option<f32>,
+    
+    #[arg(long, help = "Draw dots as unfilled outlines with this stroke width in pixels, e.g. for cutting stencils")]
+    outline: Option<f32>,
+    
+    #[arg(long, help = "Blur radius in pixels of a neon glow around the dots (color and halftone-white modes)")]
+    glow: Option<f32>,
+    
+    #[arg(long, help = "Maximum number of dots; spacing is widened automatically to fit")]
+    max_elements: Option<usize>,
+    
+    #[arg(long, help = "Average colors along a directional streak of this length in pixels")]
+    streak: Option<f32>,
+    
+    #[arg(long, default_value = "0.0", help = "Streak direction in degrees (0 is horizontal)")]
+    streak_angle: f32,
+    
+    #[arg(long, help = "Fill each dot with a radial gradient toward its neighbors' colors")]
+    gradient_dots: bool,
+    
+    #[arg(long, default_value = "as-sampled", value_enum, help = "Order dots are painted in where they overlap")]
+    paint_order: PaintOrderArg,
+    
+    #[arg(long, help = "Stretch halftone dot sizes to use the full min/max size range")]
+    normalize_dots: bool,
+    
+    #[arg(long, help = "Group dots per row in alternating direction for raster plotters")]
+    scanlines: bool,
+    
+    #[arg(long, help = "Merge same-colored dots into a single <path> per color")]
+    merge_paths: bool,
+    
+    #[arg(long, default_value = "0", help = "Emit colors within this RGB distance of each other as a single fill (0 disables)")]
+    color_merge: u8,
+    
+    #[arg(long, help = "Quantize dot colors to a palette of N colors")]
+    palette: Option<usize>,
+    
+    #[arg(long, help = "Dither dot colors onto a fixed palette of comma-separated hex colors, e.g. \"#000000,#ff0000,#ffffff\"")]
+    palette_dither: Option<String>,
+    
+    #[arg(long, help = "Print each output color's hex value and dot count to stderr")]
+    print_palette: bool,
+    
+    #[arg(long, help = "Print the fraction of the page covered by dots to stderr, for ink estimates")]
+    print_coverage: bool,
+    
+    #[arg(long, default_value = "rgb", value_enum, help = "Color space used for palette quantization")]
+    quantize_space: QuantizeSpaceArg,
+    
+    #[arg(long, value_enum, help = "Remap the palette to colors distinguishable under a color vision deficiency")]
+    colorblind_safe: Option<CbTypeArg>,
+    
+    #[arg(long, help = "Emit SVG coordinates normalized to 0..1 for responsive embedding")]
+    normalized: bool,
+    
+    #[arg(long, default_value = "top-left", value_enum, help = "Anchor used as the (0,0) origin of the SVG coordinates")]
+    origin: OriginArg,
+    
+    #[arg(long, default_value = "0", help = "Number of worker threads (0 uses all cores)")]
+    threads: usize,
+    
+    #[arg(long, help = "Grid rows sampled per parallel task (default: let rayon split the work)")]
+    chunk_rows: Option<usize>,
+    
+    #[arg(long, help = "Also save the sampled dots and configuration to a JSON dot file")]
+    json: Option<PathBuf>,
+    
+    #[arg(long, help = "Also save the dots as CSV rows of x,y,r,g,b,size (plus col,row in grid mode)")]
+    csv: Option<PathBuf>,
+    
+    #[arg(long, help = "Also save a G-code program tracing each dot (laser/pen plotters)")]
+    gcode: Option<PathBuf>,
+    
+    #[arg(long, help = "Also save an HTML page showing the SVG with drag-to-pan and wheel zoom")]
+    html: Option<PathBuf>,
+    
+    #[arg(long, help = "Flip the Y axis of G-code output so +Y points up from a bottom-left origin")]
+    y_up: bool,
+    
+    #[arg(long, help = "Write SVG content in +Y-up coordinates inside a flipping wrapper group")]
+    svg_y_up: bool,
+    
+    #[arg(long, value_name = "FILE", help = "Render a saved JSON dot file instead of sampling an input image")]
+    from_json: Option<PathBuf>,
 }
 
 fn main() -> Result<()> {
-    let args = Args::parse();
+    let matches = Args::command().get_matches();
+    let mut args = Args::from_arg_matches(&matches)?;
+    split_paths(&mut args)?;
+    
+    // Without a preset every option applies; with one, only those given on the command line
+    let applies = |id: &str| {
+        args.preset.is_none() || matches.value_source(id) == Some(ValueSource::CommandLine)
+    };
+
+    if let Some(dot_file) = &args.from_json {
+        // The dot file carries its own configuration
+        println!("Rendering dot file: {:?}", dot_file);
+        let json = std::fs::read_to_string(dot_file)?;
+        let dots = pixelator::DotFile::from_json(&json)?;
+        let generator = SvgGenerator::new(&dots.config);
+        write_file(&args.output, &generator.generate_svg(&dots.dots, dots.width, dots.height)?)?;
+        print_warnings(generator.take_warnings());
+        println!("Successfully generated SVG: {:?}", args.output);
+        return Ok(());
+    }
 
     if !args.input.exists() {
         anyhow::bail!("Input file does not exist: {:?}", args.input);
     }
 
-    let mut config = PixelatorConfig::new(args.circle_diameter, args.circle_spacing)?;
+    let mut config = match args.preset {
+        Some(preset) => PixelatorConfig::from(preset),
+        None => PixelatorConfig::new(args.circle_diameter, args.circle_spacing)?,
+    };
+    // Halftone presets bring their own dot range
+    let preset_halftone = matches!(config.render_mode, RenderMode::Halftone(_));
+    
+    if args.preset.is_some() && (applies("circle_diameter") || applies("circle_spacing")) {
+        let diameter = if applies("circle_diameter") { args.circle_diameter } else { config.circle_diameter };
+        let spacing = if applies("circle_spacing") { args.circle_spacing } else { config.circle_spacing };
+        config = config.with_circle_size(diameter, spacing)?;
+    }
+
+    if let Some(diameter) = args.dot_mm {
+        config = config.with_physical_dot_size(diameter, args.spacing_mm)?;
+    }
+    if args.source_dpi {
+        config = config.with_respect_source_dpi(true);
+    }
 
     if let (Some(w), Some(h)) = (args.width_mm, args.height_mm) {
         config = config.with_output_dimensions(w, h)?;
     }
+    if let (Some(w), Some(h)) = (args.page_width, args.page_height) {
+        config = config.with_fit_to_page(w, h, args.margin)?;
+    }
+    if let Some(lpi) = args.lpi {
+        config = config.with_lpi(lpi)?;
+    }
+    config = config.with_mm_decimals(args.mm_decimals);
 
     if let Some(bg) = args.background {
         config = config.with_background_color(bg);
     }
 
-    config = config.with_sample_mode(args.mode.into());
-    config = config.with_render_mode(args.render.into());
+    if let Some(spec) = &args.background_gradient {
+        let stops = spec
+            .split(',')
+            .map(|stop| {
+                let (offset, color) = stop
+                    .split_once(':')
+                    .ok_or_else(|| anyhow::anyhow!("Gradient stop must be offset:color, got {:?}", stop))?;
+                Ok((offset.trim().parse::<f32>()?, color.trim().to_string()))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        config = config.with_background_gradient(stops, args.gradient_angle)?;
+    }
+
+    if let Some(kind) = args.background_pattern {
+        config = config.with_background_pattern(kind.into());
+    }
+    if args.background_rect {
+        config = config.with_background_rect(true);
+    }
+    config = config.with_margin(args.margin)?;
+    if applies("mode") {
+        config = config.with_sample_mode(args.mode.into());
+    }
+    config = config.with_grid_fit(args.fit.into());
+    if applies("render") {
+        config = config.with_render_mode(match args.render {
+            RenderModeArg::FmHalftone => RenderMode::FmHalftone { dot_size: args.fm_dot },
+            RenderModeArg::Stipple => RenderMode::Stipple { dot_size: args.fm_dot, seed: None },
+            RenderModeArg::Hatch => RenderMode::Hatch(HatchOrientation::Fixed(args.screen_angle)),
+            RenderModeArg::Contours => RenderMode::Contours { levels: args.contour_levels },
+            RenderModeArg::Threshold => RenderMode::Threshold { level: args.threshold },
+            RenderModeArg::LineScreen => RenderMode::LineScreen {
+                angle: args.screen_angle,
+                frequency: args.screen_frequency,
+            },
+            render => render.into(),
+        });
+    }
+    if applies("supersample") {
+        config = config.with_supersample(args.supersample)?;
+    }
+    config = config.with_edge_mode(args.edge.into());
+    config = config.with_distortion_policy(args.distortion.into());
+    config = config.with_overlap_policy(args.overlap.into());
+    config = config.with_hex_orientation(args.hex_orientation.into());
+    if args.coverage {
+        config = config.with_coverage_sampling(true);
+    }
+    config = config.with_sharpen(args.sharpen)?;
+    if args.merge_paths {
+        config = config.with_path_merge_by_color(true);
+    }
+    config = config.with_gradient_dots(args.gradient_dots);
+    config = config.with_paint_order(args.paint_order.into());
+    if args.normalize_dots {
+        config = config.with_dot_size_normalization(true);
+    }
+    if args.scanlines {
+        config = config.with_scanline_grouping(true);
+    }
+    if applies("quantize_space") {
+        config = config.with_quantize_space(args.quantize_space.into());
+    }
+    config = config.with_origin(args.origin.into());
+    config = config.with_normalized_coordinates(args.normalized);
+    config = config.with_threads(args.threads);
+    if let Some(rows) = args.chunk_rows {
+        config = config.with_parallel_chunk_rows(rows)?;
+    }
+    config = config.with_y_up(args.y_up);
+    if args.svg_y_up {
+        config = config.with_svg_y_up(true);
+    }
+    
+    if let Some(mask) = &args.size_mask {
+        config = config.with_size_mask(mask);
+    }
+    if let Some(tile) = &args.tile_image {
+        config = config.with_tile_image(tile);
+    }
+    
+    if let Some(mask) = &args.clip_mask {
+        config = config.with_clip_mask(mask);
+    }
+    
+    if let Some(length) = args.scale_bar {
+        config = config.with_scale_bar(length)?;
+    }
+    if let Some(every) = args.guide_grid {
+        config = config.with_guide_grid(every, args.guide_color.clone())?;
+    }
+    if args.registration_marks {
+        config = config.with_registration_marks(true);
+    }
+    
+    config = config.with_color_merge_tolerance(args.color_merge);
+    if let Some(size) = args.palette {
+        config = config.with_palette_size(size)?;
+    }
+    if let Some(spec) = &args.palette_dither {
+        let colors = spec.split(',').map(|color| parse_hex_color(color.trim())).collect::<Result<Vec<_>>>()?;
+        config = config.with_palette_dither(colors)?;
+    }
+    
+    if let Some(length) = args.streak {
+        config = config.with_directional_sample(args.streak_angle, length)?;
+    }
+    
+    config = config.with_pixel_art_mode(args.pixel_art);
+    
+    config = config.with_assume_srgb(args.assume_srgb);
+    
+    if let Some(seed) = args.seed {
+        config = config.with_seed(seed);
+    }
+    config = config.with_jitter(args.jitter)?;
+    config = config.with_saturation_scale(args.saturation)?;
+    if let Some(percent) = args.dot_gain {
+        config = config.with_dot_gain(percent)?;
+    }
+    
+    if let Some(gap) = args.min_gap {
+        config = config.with_min_gap(gap)?;
+    }
+    if let Some(radius) = args.min_radius {
+        config = config.with_min_visible_radius(radius)?;
+    }
+    if let Some(inks) = &args.spot_inks {
+        config = config.with_spot_color_bands(inks.clone())?;
+    }
+    if args.fill_interstices {
+        config = config.with_fill_interstices(true);
+    }
+    if args.straight_alpha {
+        config = config.with_premultiplied_alpha(false);
+    }
+    if let Some(dpi) = args.svg_dpi {
+        config = config.with_svg_input_dpi(dpi)?;
+    }
+    if let Some(seconds) = args.reveal {
+        config = config.with_reveal_animation(seconds)?;
+    }
+    if let Some(pitch) = args.snap_mm {
+        config = config.with_snap_to_grid(pitch)?;
+    }
+    if let Some(opacity) = args.reference_layer {
+        config = config.with_reference_layer(opacity)?;
+    }
+    if let Some(margin) = args.edge_fade {
+        config = config.with_edge_fade(margin)?;
+    }
+    if let Some(width) = args.outline {
+        config = config.with_outline_only(width)?;
+    }
+    if let Some(radius) = args.glow {
+        config = config.with_glow(radius)?;
+    }
+    
+    if let Some(max) = args.max_elements {
+        config = config.with_max_elements(max)?;
+    }
+    
+    if let Some(cb_type) = args.colorblind_safe {
+        config = config.with_colorblind_safe(cb_type.into());
+    }
     
     // Set halftone range if specified
     if let (Some(min), Some(max)) = (args.min_dot, args.max_dot) {
         config = config.with_halftone_range(min, max)?;
-    } else if matches!(args.render, RenderModeArg::HalftoneBlack | RenderModeArg::HalftoneWhite) {
+    } else if applies("render") && !preset_halftone && matches!(args.render, RenderModeArg::HalftoneBlack | RenderModeArg::HalftoneWhite) {
         // Default halftone range if not specified but halftone mode is selected
-        config = config.with_halftone_range(0.5, args.circle_diameter)?;
+        let max = config.circle_diameter;
+        config = config.with_halftone_range(0.5, max)?;
     }
 
+    if let Some(levels) = args.dot_levels {
+        config = config.with_quantized_dot_sizes(levels)?;
+    }
+    if let Some(sizes) = &args.bead_sizes {
+        config = config.with_bead_sizes(sizes.clone())?;
+    }
+    if let Some(count) = args.importance_dots {
+        config = config.with_importance_dots(count)?;
+    }
+    
+    config = config.with_brightness_metric(args.brightness.into());
+    config = config.with_size_source(args.size_source.into());
+    config = config.with_alpha_in_brightness(args.alpha_brightness);
+    config = config.with_dither(args.dither.into());
+    config = config.with_halftone_curve(match args.curve {
+        HalftoneCurveArg::Linear => HalftoneCurve::Linear,
+        HalftoneCurveArg::Gamma => HalftoneCurve::Gamma(args.gamma),
+        HalftoneCurveArg::Sine => HalftoneCurve::Sine,
+    })?;
+
     println!("Processing image: {:?}", args.input);
     println!("Configuration:");
-    println!("  Circle diameter: {} pixels", args.circle_diameter);
-    println!("  Circle spacing: {} pixels", args.circle_spacing);
-    println!("  Sample mode: {:?}", args.mode);
-    println!("  Render mode: {:?}", args.render);
+    if let Some(preset) = args.preset {
+        println!("  Preset: {:?}", preset);
+    }
+    println!("  Circle diameter: {} pixels", config.circle_diameter);
+    println!("  Circle spacing: {} pixels", config.circle_spacing);
+    println!("  Sample mode: {:?}", config.sample_mode);
+    println!("  Render mode: {:?}", config.render_mode);
     
     if let (Some(w), Some(h)) = (args.width_mm, args.height_mm) {
         println!("  Output dimensions: {}mm x {}mm", w, h);
@@ -114,10 +897,85 @@ fn main() -> Result<()> {
 
     let pixelator = Pixelator::new(config);
     
-    pixelator.process_image_to_file(&args.input, &args.output)?;
+    if args.json.is_some() || args.csv.is_some() || args.gcode.is_some() || args.html.is_some() || args.print_palette || args.print_coverage {
+        // Sample once and render every requested output from the same dots
+        let dots = pixelator.sample(&args.input)?;
+        let mut generator = SvgGenerator::new(&dots.config);
+        if dots.config.reference_layer.is_some() {
+            generator = generator.with_reference_image(&pixelator::vector::open_image(&args.input, dots.config.svg_input_dpi)?)?;
+        }
+        let svg = generator.generate_svg(&dots.dots, dots.width, dots.height)?;
+        print_warnings(generator.take_warnings());
+        write_file(&args.output, &svg)?;
+        
+        if let Some(json_path) = &args.json {
+            write_file(json_path, &dots.to_json()?)?;
+            println!("Saved dot file: {:?}", json_path);
+        }
+        if let Some(csv_path) = &args.csv {
+            write_file(csv_path, &dots.to_csv())?;
+            println!("Saved CSV: {:?}", csv_path);
+        }
+        if let Some(gcode_path) = &args.gcode {
+            let gcode = GcodeGenerator::new(&dots.config).generate_gcode(&dots.dots, dots.width, dots.height)?;
+            write_file(gcode_path, &gcode)?;
+            println!("Saved G-code: {:?}", gcode_path);
+        }
+        if let Some(html_path) = &args.html {
+            write_file(html_path, &pixelator::html::viewer_page(&svg))?;
+            println!("Saved HTML viewer: {:?}", html_path);
+        }
+        if args.print_palette {
+            eprint!("{}", dots.palette_listing());
+        }
+        if args.print_coverage {
+            eprintln!("Coverage: {:.1}%", dots.coverage_fraction() * 100.0);
+        }
+    } else {
+        pixelator.process_image_to_file(&args.input, &args.output)?;
+    }
     
+    print_warnings(pixelator.take_warnings());
     println!("Successfully generated SVG: {:?}", args.output);
     println!("Ready for printing!");
 
     Ok(())
 }
+
+/// Writes text output through a temporary file, so a failed write leaves no truncated file
+fn write_file(path: &Path, contents: &str) -> Result<()> {
+    pixelator::write_atomically(path, |writer| Ok(writer.write_all(contents.as_bytes())?))?;
+    Ok(())
+}
+
+/// Prints warnings reported by the library to stderr
+fn print_warnings(warnings: Vec<String>) {
+    for warning in warnings {
+        eprintln!("Warning: {}", warning);
+    }
+}
+
+/// Splits the positional paths into the input image and output SVG
+///
+/// With `--from-json` the dot file stands in for the input image, so only
+/// the output path is expected.
+fn split_paths(args: &mut Args) -> Result<()> {
+    match (&args.from_json, args.paths.len()) {
+        (Some(_), 1) | (None, 2) => {}
+        (Some(_), _) => anyhow::bail!("With --from-json, give only the output SVG path"),
+        (None, _) => anyhow::bail!("Expected an input image path and an output SVG path"),
+    }
+    args.output = args.paths.pop().unwrap_or_default();
+    args.input = args.paths.pop().unwrap_or_default();
+    Ok(())
+}
+
+/// Parses a `#rrggbb` color (the `#` is optional) into an opaque RGBA value
+fn parse_hex_color(text: &str) -> Result<image::Rgba<u8>> {
+    let hex = text.strip_prefix('#').unwrap_or(text);
+    if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        anyhow::bail!("Expected a #rrggbb color, got {:?}", text);
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16);
+    Ok(image::Rgba([channel(0)?, channel(2)?, channel(4)?, 255]))
+}
\ No newline at end of file
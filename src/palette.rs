@@ -0,0 +1,296 @@
+use crate::color;
+use crate::config::{CbType, QuantizeSpace};
+use crate::processor::PixelData;
+use image::Rgba;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+// Number of k-means refinement passes after the median-cut seed
+const KMEANS_ITERATIONS: usize = 8;
+
+// Okabe-Ito palette, distinguishable under common color vision deficiencies
+pub const COLORBLIND_SAFE_PALETTE: [Rgba<u8>; 8] = [
+    Rgba([0, 0, 0, 255]),
+    Rgba([230, 159, 0, 255]),
+    Rgba([86, 180, 233, 255]),
+    Rgba([0, 158, 115, 255]),
+    Rgba([240, 228, 66, 255]),
+    Rgba([0, 114, 178, 255]),
+    Rgba([213, 94, 0, 255]),
+    Rgba([204, 121, 167, 255]),
+];
+
+// Minimum simulated delta E for two safe colors to count as distinguishable
+const MIN_SIMULATED_DELTA_E: f32 = 20.0;
+
+/// Builds a palette of at most `size` colors from the given samples
+///
+/// The palette is seeded with median-cut in the requested color space and
+/// refined with a few k-means passes. Clustering in `QuantizeSpace::Lab`
+/// gives perceptually more even palettes for photographs.
+pub fn build_palette(colors: &[Rgba<u8>], size: usize, space: QuantizeSpace) -> Vec<Rgba<u8>> {
+    if colors.is_empty() || size == 0 {
+        return Vec::new();
+    }
+
+    let points: Vec<[f32; 3]> = colors.iter().map(|c| to_space(c, space)).collect();
+    let mut centroids = median_cut(&points, size);
+
+    for _ in 0..KMEANS_ITERATIONS {
+        let mut sums = vec![[0.0f32; 3]; centroids.len()];
+        let mut counts = vec![0usize; centroids.len()];
+
+        for point in &points {
+            let index = nearest_point(&centroids, point);
+            for axis in 0..3 {
+                sums[index][axis] += point[axis];
+            }
+            counts[index] += 1;
+        }
+
+        for (centroid, (sum, count)) in centroids.iter_mut().zip(sums.iter().zip(&counts)) {
+            if *count > 0 {
+                *centroid = sum.map(|v| v / *count as f32);
+            }
+        }
+    }
+
+    centroids.into_iter().map(|c| from_space(c, space)).collect()
+}
+
+/// Returns the index of the palette entry closest to `color` in the given space
+pub fn nearest_color(palette: &[Rgba<u8>], color: &Rgba<u8>, space: QuantizeSpace) -> usize {
+    let points: Vec<[f32; 3]> = palette.iter().map(|c| to_space(c, space)).collect();
+    nearest_point(&points, &to_space(color, space))
+}
+
+/// Quantizes the colors of the sampled pixels in place to a palette of `size` colors
+/// Alpha is preserved per pixel; brightness and dot sizes keep their sampled values
+pub fn quantize_pixels(pixels: &mut [PixelData], size: usize, space: QuantizeSpace) -> Vec<Rgba<u8>> {
+    let colors: Vec<Rgba<u8>> = pixels.iter().map(|p| p.color).collect();
+    let palette = build_palette(&colors, size, space);
+    remap_pixels(pixels, &palette, space);
+    palette
+}
+
+/// Snaps every pixel's color to its nearest entry in `palette`
+pub fn remap_pixels(pixels: &mut [PixelData], palette: &[Rgba<u8>], space: QuantizeSpace) {
+    if palette.is_empty() {
+        return;
+    }
+
+    let points: Vec<[f32; 3]> = palette.iter().map(|c| to_space(c, space)).collect();
+    for pixel in pixels.iter_mut() {
+        let entry = palette[nearest_point(&points, &to_space(&pixel.color, space))];
+        pixel.color = Rgba([entry[0], entry[1], entry[2], pixel.color[3]]);
+    }
+}
+
+/// Snaps every pixel's color to `palette` with Floyd–Steinberg error diffusion
+/// 
+/// Pixels are visited in lattice scan order; the RGB difference between a
+/// pixel's color and its chosen entry is spread onto unvisited neighbors.
+/// Pixels without a lattice position are snapped without diffusion.
+pub fn dither_pixels(pixels: &mut [PixelData], palette: &[Rgba<u8>], space: QuantizeSpace) {
+    if palette.is_empty() {
+        return;
+    }
+
+    let points: Vec<[f32; 3]> = palette.iter().map(|c| to_space(c, space)).collect();
+    let index: HashMap<(usize, usize), usize> = pixels
+        .iter()
+        .enumerate()
+        .filter_map(|(i, p)| p.grid_position.map(|pos| (pos, i)))
+        .collect();
+    let mut order: Vec<usize> = (0..pixels.len()).collect();
+    order.sort_by_key(|&i| pixels[i].grid_position.map(|(col, row)| (row, col)));
+
+    let mut colors: Vec<[f32; 3]> = pixels
+        .iter()
+        .map(|p| [p.color[0] as f32, p.color[1] as f32, p.color[2] as f32])
+        .collect();
+
+    for i in order {
+        let [r, g, b] = colors[i].map(|v| v.round().clamp(0.0, 255.0) as u8);
+        let wanted = Rgba([r, g, b, 255]);
+        let entry = palette[nearest_point(&points, &to_space(&wanted, space))];
+        pixels[i].color = Rgba([entry[0], entry[1], entry[2], pixels[i].color[3]]);
+
+        let Some((col, row)) = pixels[i].grid_position else {
+            continue;
+        };
+        let error = [0, 1, 2].map(|c| colors[i][c] - entry[c] as f32);
+        let neighbors = [
+            (col + 1, row, 7.0 / 16.0),
+            (col.wrapping_sub(1), row + 1, 3.0 / 16.0),
+            (col, row + 1, 5.0 / 16.0),
+            (col + 1, row + 1, 1.0 / 16.0),
+        ];
+        for (ncol, nrow, weight) in neighbors {
+            if let Some(&n) = index.get(&(ncol, nrow)) {
+                for c in 0..3 {
+                    colors[n][c] += error[c] * weight;
+                }
+            }
+        }
+    }
+}
+
+/// Maps each palette entry onto a distinct colorblind-safe color
+/// 
+/// Entries are assigned in order to the nearest unused safe color (by
+/// perceptual distance), skipping safe colors that would be confusable with
+/// an already assigned one under the given deficiency. The returned palette
+/// has the same length and order as the input.
+pub fn colorblind_safe_palette(palette: &[Rgba<u8>], cb_type: CbType) -> Vec<Rgba<u8>> {
+    let simulated: Vec<Rgba<u8>> = COLORBLIND_SAFE_PALETTE
+        .iter()
+        .map(|c| color::simulate_color_blindness(c, cb_type))
+        .collect();
+    let mut used: Vec<usize> = Vec::new();
+    
+    palette
+        .iter()
+        .map(|entry| {
+            let distinguishable = |candidate: usize| {
+                used.iter().all(|&u| color::delta_e(&simulated[u], &simulated[candidate]) >= MIN_SIMULATED_DELTA_E)
+            };
+            let by_distance = |a: &usize, b: &usize| {
+                color::delta_e(entry, &COLORBLIND_SAFE_PALETTE[*a])
+                    .total_cmp(&color::delta_e(entry, &COLORBLIND_SAFE_PALETTE[*b]))
+            };
+            
+            // Fall back to reusing the nearest safe color once all distinct ones are taken
+            let choice = (0..COLORBLIND_SAFE_PALETTE.len())
+                .filter(|i| !used.contains(i) && distinguishable(*i))
+                .min_by(by_distance)
+                .or_else(|| (0..COLORBLIND_SAFE_PALETTE.len()).min_by(by_distance))
+                .unwrap_or(0);
+            
+            if !used.contains(&choice) {
+                used.push(choice);
+            }
+            COLORBLIND_SAFE_PALETTE[choice]
+        })
+        .collect()
+}
+
+/// Reads the palette of an indexed-color PNG without decoding its pixels
+/// 
+/// Returns `None` for files that are not PNGs or not palette-based. Alpha
+/// comes from the PNG's `tRNS` chunk when present.
+pub fn read_png_palette<P: AsRef<Path>>(path: P) -> Option<Vec<Rgba<u8>>> {
+    let file = File::open(path).ok()?;
+    let reader = png::Decoder::new(BufReader::new(file)).read_info().ok()?;
+    let info = reader.info();
+    if info.color_type != png::ColorType::Indexed {
+        return None;
+    }
+    
+    let alpha = info.trns.as_deref().unwrap_or(&[]);
+    let palette = info
+        .palette
+        .as_deref()?
+        .chunks_exact(3)
+        .enumerate()
+        .map(|(i, rgb)| Rgba([rgb[0], rgb[1], rgb[2], alpha.get(i).copied().unwrap_or(255)]))
+        .collect();
+    Some(palette)
+}
+
+/// Replaces each pixel color found in `from` with the entry at the same index in `to`
+pub fn substitute_colors(pixels: &mut [PixelData], from: &[Rgba<u8>], to: &[Rgba<u8>]) {
+    for pixel in pixels.iter_mut() {
+        let rgb = [pixel.color[0], pixel.color[1], pixel.color[2]];
+        if let Some(index) = from.iter().position(|c| [c[0], c[1], c[2]] == rgb) {
+            let target = to[index];
+            pixel.color = Rgba([target[0], target[1], target[2], pixel.color[3]]);
+        }
+    }
+}
+
+fn to_space(color: &Rgba<u8>, space: QuantizeSpace) -> [f32; 3] {
+    match space {
+        QuantizeSpace::Rgb => [color[0] as f32, color[1] as f32, color[2] as f32],
+        QuantizeSpace::Lab => color::rgb_to_lab(color),
+    }
+}
+
+fn from_space(point: [f32; 3], space: QuantizeSpace) -> Rgba<u8> {
+    match space {
+        QuantizeSpace::Rgb => {
+            let [r, g, b] = point.map(|v| v.round().clamp(0.0, 255.0) as u8);
+            Rgba([r, g, b, 255])
+        }
+        QuantizeSpace::Lab => color::lab_to_rgb(point),
+    }
+}
+
+fn nearest_point(points: &[[f32; 3]], target: &[f32; 3]) -> usize {
+    points
+        .iter()
+        .enumerate()
+        .map(|(i, p)| {
+            let d = (p[0] - target[0]).powi(2) + (p[1] - target[1]).powi(2) + (p[2] - target[2]).powi(2);
+            (i, d)
+        })
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(i, _)| i)
+        .unwrap_or(0)
+}
+
+/// Splits the point set into up to `size` boxes along their widest axis
+/// and returns the mean of each box
+fn median_cut(points: &[[f32; 3]], size: usize) -> Vec<[f32; 3]> {
+    let mut boxes: Vec<Vec<[f32; 3]>> = vec![points.to_vec()];
+
+    while boxes.len() < size {
+        // Pick the box with the largest extent along any axis
+        let candidate = boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, b)| b.len() > 1)
+            .map(|(i, b)| (i, widest_axis(b)))
+            .max_by(|a, b| a.1 .1.total_cmp(&b.1 .1));
+
+        let Some((index, (axis, extent))) = candidate else {
+            break;
+        };
+        if extent <= 0.0 {
+            break;
+        }
+
+        let mut current = boxes.swap_remove(index);
+        current.sort_by(|a, b| a[axis].total_cmp(&b[axis]));
+        let upper = current.split_off(current.len() / 2);
+        boxes.push(current);
+        boxes.push(upper);
+    }
+
+    boxes
+        .iter()
+        .map(|b| {
+            let mut mean = [0.0f32; 3];
+            for point in b {
+                for axis in 0..3 {
+                    mean[axis] += point[axis];
+                }
+            }
+            mean.map(|v| v / b.len() as f32)
+        })
+        .collect()
+}
+
+fn widest_axis(points: &[[f32; 3]]) -> (usize, f32) {
+    (0..3)
+        .map(|axis| {
+            let (min, max) = points.iter().fold((f32::MAX, f32::MIN), |(min, max), p| {
+                (min.min(p[axis]), max.max(p[axis]))
+            });
+            (axis, max - min)
+        })
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .unwrap_or((0, 0.0))
+}
@@ -1,102 +1,179 @@
-use crate::config::{PixelatorConfig, SampleMode};
-use crate::error::Result;
-use image::{DynamicImage, Rgba};
+use crate::config::{DirectionalSample, DitherMode, EdgeMode, HexOrientation, PixelatorConfig, SampleMode};
+use crate::error::{PixelatorError, Result};
+use image::{DynamicImage, Rgba, RgbaImage};
 use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
 
 // Hexagonal grid constant: sqrt(3)/2 for row height calculation
 pub const HEXAGONAL_ROW_HEIGHT_FACTOR: f32 = 0.866;
 
+// Sub-samples per axis used to estimate the coverage of a circle's edge pixels
+const COVERAGE_SUBSAMPLES: u32 = 8;
+
+// Gaussian blur radius used to build the unsharp mask
+const SHARPEN_SIGMA: f32 = 1.0;
+
 /// Data for a single sampled pixel/circle
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PixelData {
     pub x: f32,
     pub y: f32,
+    #[serde(with = "rgba_channels")]
     pub color: Rgba<u8>,
     pub brightness: f32,  // Brightness value for halftone mode (0.0 to 1.0)
     pub dot_size: f32,     // Variable dot size for halftone mode
+    #[serde(default)]
+    pub grid_position: Option<(usize, usize)>,  // (column, row) in the sampling lattice
+}
+
+// Serializes colors as a plain `[r, g, b, a]` array
+mod rgba_channels {
+    use image::Rgba;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    
+    pub fn serialize<S: Serializer>(color: &Rgba<u8>, serializer: S) -> Result<S::Ok, S::Error> {
+        color.0.serialize(serializer)
+    }
+    
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Rgba<u8>, D::Error> {
+        <[u8; 4]>::deserialize(deserializer).map(Rgba)
+    }
 }
 
 /// Processes images by sampling pixels at regular intervals
 pub struct ImageProcessor<'a> {
     config: &'a PixelatorConfig,
+    source_palette: Option<Vec<Rgba<u8>>>,
+    cancel: Option<&'a AtomicBool>,
 }
 
 impl<'a> ImageProcessor<'a> {
     /// Creates a new image processor with the given configuration
     pub fn new(config: &'a PixelatorConfig) -> Self {
-        Self { config }
+        Self { config, source_palette: None, cancel: None }
+    }
+    
+    /// Supplies the palette the source image is stored with (e.g. an indexed PNG)
+    /// 
+    /// When palette quantization is requested and the source palette fits the
+    /// requested size, dots are snapped to it directly instead of clustering.
+    pub fn with_source_palette(mut self, palette: Vec<Rgba<u8>>) -> Self {
+        self.source_palette = Some(palette);
+        self
+    }
+    
+    /// Aborts sampling once `cancel` is set, checked before each row of dots
+    /// 
+    /// A cancelled run returns `PixelatorError::Processing("cancelled")`.
+    pub fn with_cancel_flag(mut self, cancel: &'a AtomicBool) -> Self {
+        self.cancel = Some(cancel);
+        self
+    }
+    
+    /// Returns the cancellation error if the cancel flag has been set
+    pub fn check_cancelled(&self) -> Result<()> {
+        if self.is_cancelled() {
+            return Err(PixelatorError::Processing("cancelled".to_string()));
+        }
+        Ok(())
+    }
+    
+    fn is_cancelled(&self) -> bool {
+        self.cancel.is_some_and(|flag| flag.load(Ordering::Relaxed))
     }
     
     /// Samples the image according to the configured pattern and returns pixel data
     /// Uses parallel processing for improved performance on multi-core systems
+    /// 
+    /// When a thread count is configured, sampling runs on a dedicated thread
+    /// pool of that size instead of the global rayon pool.
     pub fn sample_image(&self, image: &DynamicImage) -> Result<Vec<PixelData>> {
-        let rgba_image = std::sync::Arc::new(image.to_rgba8());
-        let (img_width, img_height) = (rgba_image.width(), rgba_image.height());
+        if self.config.threads == 0 {
+            return self.sample_pixels(image);
+        }
         
-        let total_spacing = self.config.get_total_spacing();
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(self.config.threads)
+            .build()
+            .map_err(|e| PixelatorError::Processing(format!("Failed to build thread pool: {}", e)))?;
+        pool.install(|| self.sample_pixels(image))
+    }
+    
+    /// Applies an unsharp mask: each channel is pushed away from its blurred
+    /// value by `amount`, alpha is left untouched
+    fn sharpen(image: RgbaImage, amount: f32) -> RgbaImage {
+        if amount <= 0.0 {
+            return image;
+        }
         
-        let cols = ((img_width as f32) / total_spacing).floor() as usize;
-        let rows = ((img_height as f32) / total_spacing).floor() as usize;
+        let blurred = image::imageops::blur(&image, SHARPEN_SIGMA);
+        let mut sharpened = image;
+        for (pixel, soft) in sharpened.pixels_mut().zip(blurred.pixels()) {
+            for channel in 0..3 {
+                let value = pixel[channel] as f32;
+                let detail = value - soft[channel] as f32;
+                pixel[channel] = (value + amount * detail).round().clamp(0.0, 255.0) as u8;
+            }
+        }
+        sharpened
+    }
+    
+    fn sample_pixels(&self, image: &DynamicImage) -> Result<Vec<PixelData>> {
+        let rgba_image = std::sync::Arc::new(Self::sharpen(image.to_rgba8(), self.config.sharpen));
+        let (img_width, img_height) = (rgba_image.width(), rgba_image.height());
         
-        let pixels = match self.config.sample_mode {
-            SampleMode::Grid => {
+        let (cols, rows) = self.config.grid_dimensions(img_width, img_height);
+        if cols == 0 || rows == 0 {
+            return Err(self.too_small_error(img_width, img_height));
+        }
+        
+        let mut pixels = match (self.config.parallel_chunk_rows, &self.config.sample_mode) {
+            (Some(chunk_rows), _) => {
+                // Each task samples a run of whole rows (or hexagonal lines)
+                let steps = self.config.grid_steps(img_width, img_height);
+                let lines: Vec<usize> = match self.config.sample_mode {
+                    SampleMode::Grid => (0..rows).collect(),
+                    SampleMode::Hexagonal => (0..self.config.hex_line_count(img_width, img_height)).collect(),
+                };
+                lines
+                    .par_chunks(chunk_rows)
+                    .flat_map_iter(|chunk| {
+                        chunk
+                            .iter()
+                            .take_while(|_| !self.is_cancelled())
+                            .flat_map(|&line| self.line_dots(&rgba_image, line, cols, steps))
+                            .collect::<Vec<_>>()
+                    })
+                    .collect()
+            }
+            (None, SampleMode::Grid) => {
+                let (step_x, step_y) = self.config.grid_steps(img_width, img_height);
+                
                 // Use parallel iterator for grid sampling
                 let pixel_data: Vec<PixelData> = (0..rows)
                     .into_par_iter()
                     .flat_map(|row| {
                         let rgba_image = rgba_image.clone();
-                        let total_spacing = total_spacing;
-                        let circle_diameter = self.config.circle_diameter;
+                        // Rows started after cancellation produce no dots
+                        let row_cols = if self.is_cancelled() { 0 } else { cols };
                         
-                        (0..cols).into_par_iter().map(move |col| {
-                            let x = col as f32 * total_spacing + circle_diameter / 2.0;
-                            let y = row as f32 * total_spacing + circle_diameter / 2.0;
-                            
-                            let sample_x = (x as u32).min(img_width - 1);
-                            let sample_y = (y as u32).min(img_height - 1);
-                            
-                            let color = Self::sample_area_static(&rgba_image, sample_x, sample_y, circle_diameter);
-                            let brightness = Self::calculate_brightness(&color);
-                            let dot_size = self.calculate_dot_size(brightness);
-                            
-                            PixelData { x, y, color, brightness, dot_size }
-                        })
+                        (0..row_cols).into_par_iter().map(move |col| self.grid_dot(&rgba_image, col, row, (step_x, step_y)))
                     })
                     .collect();
                 
                 pixel_data
             }
-            SampleMode::Hexagonal => {
-                let row_height = total_spacing * HEXAGONAL_ROW_HEIGHT_FACTOR;
-                let hex_rows = ((img_height as f32) / row_height).floor() as usize;
-                
+            (None, SampleMode::Hexagonal) => {
                 // Use parallel iterator for hexagonal sampling
-                let pixel_data: Vec<Vec<PixelData>> = (0..hex_rows)
+                let lines = self.config.hex_line_count(img_width, img_height);
+                let pixel_data: Vec<Vec<PixelData>> = (0..lines)
                     .into_par_iter()
-                    .map(|row| {
+                    .map(|line| {
                         let rgba_image = rgba_image.clone();
-                        let offset = if row % 2 == 0 { 0.0 } else { total_spacing / 2.0 };
-                        let y = row as f32 * row_height + self.config.circle_diameter / 2.0;
+                        let dots = if self.is_cancelled() { 0 } else { self.config.hex_line_dots(img_width, img_height, line) };
                         
-                        let mut row_pixels = Vec::new();
-                        let mut col = 0;
-                        loop {
-                            let x = col as f32 * total_spacing + offset + self.config.circle_diameter / 2.0;
-                            if x >= img_width as f32 {
-                                break;
-                            }
-                            
-                            let sample_x = (x as u32).min(img_width - 1);
-                            let sample_y = (y as u32).min(img_height - 1);
-                            
-                            let color = Self::sample_area_static(&rgba_image, sample_x, sample_y, self.config.circle_diameter);
-                            let brightness = Self::calculate_brightness(&color);
-                            let dot_size = self.calculate_dot_size(brightness);
-                            
-                            row_pixels.push(PixelData { x, y, color, brightness, dot_size });
-                            col += 1;
-                        }
-                        row_pixels
+                        (0..dots).map(|index| self.hex_dot(&rgba_image, line, index)).collect()
                     })
                     .collect();
                 
@@ -105,53 +182,560 @@ impl<'a> ImageProcessor<'a> {
             }
         };
         
+        self.check_cancelled()?;
+        
+        if self.config.edge_mode == EdgeMode::Skip {
+            pixels.retain(|p| self.within_border(p, img_width, img_height));
+        }
+        
+        if self.config.pixel_art {
+            for pixel in pixels.iter_mut() {
+                Self::snap_to_source_pixel(pixel, img_width, img_height);
+            }
+        }
+        
+        self.apply_dither(&mut pixels);
+        self.normalize_dot_sizes(&mut pixels);
+        self.add_interstices(&mut pixels, &rgba_image, cols, rows)?;
+        let masks = self.load_masks(img_width, img_height)?;
+        let mut pixels: Vec<PixelData> = pixels
+            .into_iter()
+            .filter_map(|pixel| self.finish_dot(pixel, &masks, img_width, img_height))
+            .collect();
+        self.apply_palette(&mut pixels);
+        let mut pixels = self.apply_importance(pixels);
+        self.apply_jitter(&mut pixels);
+        
         Ok(pixels)
     }
     
-    fn sample_area_static(image: &image::RgbaImage, center_x: u32, center_y: u32, circle_diameter: f32) -> Rgba<u8> {
+    /// Samples the image lazily, yielding dots row by row in the same order
+    /// and with the same values as `sample_image`
+    /// 
+    /// Only one row of dots is held in memory at a time. Steps that need all
+    /// dots at once — dithering, dot size normalization, palette
+    /// quantization and importance sampling — are not available and return
+    /// an `InvalidConfig` error.
+    /// Sampling runs on the calling thread; a raised cancellation flag ends
+    /// the iteration early.
+    pub fn sample_iter(&self, image: &DynamicImage) -> Result<impl Iterator<Item = PixelData> + '_> {
+        if self.config.dither != DitherMode::None
+            || self.config.normalize_dot_sizes
+            || self.config.palette_size.is_some()
+            || self.config.palette_dither.is_some()
+            || self.config.importance_dots.is_some()
+            || self.config.colorblind_safe.is_some()
+            || self.config.fill_interstices
+        {
+            return Err(PixelatorError::InvalidConfig(
+                "Dithering, dot size normalization, palettes, importance sampling and interstice filling need all dots at once and can't be streamed".to_string(),
+            ));
+        }
+        
+        let rgba_image = Self::sharpen(image.to_rgba8(), self.config.sharpen);
+        let (img_width, img_height) = (rgba_image.width(), rgba_image.height());
+        let (cols, rows) = self.config.grid_dimensions(img_width, img_height);
+        if cols == 0 || rows == 0 {
+            return Err(self.too_small_error(img_width, img_height));
+        }
+        let steps = self.config.grid_steps(img_width, img_height);
+        
+        let masks = self.load_masks(img_width, img_height)?;
+        let mut rng = crate::rng::feature_rng(self.config.seed, self.config.jitter_seed, crate::rng::JITTER_STREAM);
+        
+        let lines = match self.config.sample_mode {
+            SampleMode::Grid => rows,
+            SampleMode::Hexagonal => self.config.hex_line_count(img_width, img_height),
+        };
+        let dots = (0..lines)
+            .take_while(move |_| !self.is_cancelled())
+            .flat_map(move |line| {
+                self.line_dots(&rgba_image, line, cols, steps).into_iter().filter(move |p| {
+                    self.config.edge_mode != EdgeMode::Skip || self.within_border(p, img_width, img_height)
+                })
+            })
+            .filter_map(move |mut pixel| {
+                // The per-dot steps of `sample_pixels`, in the same order
+                if self.config.pixel_art {
+                    Self::snap_to_source_pixel(&mut pixel, img_width, img_height);
+                }
+                let mut pixel = self.finish_dot(pixel, &masks, img_width, img_height)?;
+                Self::jitter_dot(&mut pixel, self.config.jitter, &mut rng);
+                Some(pixel)
+            });
+        Ok(dots)
+    }
+    
+    /// Samples every dot of grid row `line`, or of hexagonal line `line`
+    fn line_dots(&self, image: &RgbaImage, line: usize, cols: usize, steps: (f32, f32)) -> Vec<PixelData> {
+        match self.config.sample_mode {
+            SampleMode::Grid => (0..cols).map(|col| self.grid_dot(image, col, line, steps)).collect(),
+            SampleMode::Hexagonal => (0..self.config.hex_line_dots(image.width(), image.height(), line))
+                .map(|index| self.hex_dot(image, line, index))
+                .collect(),
+        }
+    }
+    
+    fn too_small_error(&self, width: u32, height: u32) -> PixelatorError {
+        PixelatorError::Processing(format!(
+            "Image is too small ({}x{} pixels) to fit a single dot at a circle diameter of {} and spacing of {} pixels; \
+             use a smaller diameter or spacing",
+            width, height, self.config.circle_diameter, self.config.circle_spacing
+        ))
+    }
+    
+    /// Samples the dot at `(col, row)` of the square grid
+    fn grid_dot(&self, image: &RgbaImage, col: usize, row: usize, (step_x, step_y): (f32, f32)) -> PixelData {
+        // Overhanging dots from GridFit::Ceil are clamped to the edge
+        let radius = self.config.circle_diameter / 2.0;
+        let x = (col as f32 * step_x + radius).min(image.width() as f32);
+        let y = (row as f32 * step_y + radius).min(image.height() as f32);
+        self.dot_at(image, x, y, (col, row))
+    }
+    
+    /// Samples dot `index` of staggered line `line` in the hexagonal grid
+    /// 
+    /// Lines are rows for pointy-top hexagons and columns for flat-top ones;
+    /// every odd line is shifted by half a cell along its length.
+    fn hex_dot(&self, image: &RgbaImage, line: usize, index: usize) -> PixelData {
+        let total_spacing = self.config.get_total_spacing();
+        let offset = if line % 2 == 0 { 0.0 } else { total_spacing / 2.0 };
+        let along = index as f32 * total_spacing + offset + self.config.circle_diameter / 2.0;
+        let across = line as f32 * (total_spacing * HEXAGONAL_ROW_HEIGHT_FACTOR) + self.config.circle_diameter / 2.0;
+        match self.config.hex_orientation {
+            HexOrientation::PointyTop => self.dot_at(image, along, across, (index, line)),
+            HexOrientation::FlatTop => self.dot_at(image, across, along, (line, index)),
+        }
+    }
+    
+    fn dot_at(&self, image: &RgbaImage, x: f32, y: f32, position: (usize, usize)) -> PixelData {
+        let color = self.sample_color(image, x, y);
+        let brightness = self.brightness(&color);
+        let dot_size = self.calculate_dot_size(self.size_tone(&color, brightness));
+        let color = self.scale_saturation(color);
+        PixelData { x, y, color, brightness, dot_size, grid_position: Some(position) }
+    }
+    
+    /// Applies the configured saturation scale in HSL space
+    fn scale_saturation(&self, color: Rgba<u8>) -> Rgba<u8> {
+        let scale = self.config.saturation_scale;
+        if scale == 1.0 {
+            return color;
+        }
+        let [hue, saturation, lightness] = crate::color::rgb_to_hsl(&color);
+        crate::color::hsl_to_rgb([hue, saturation * scale, lightness], color[3])
+    }
+    
+    /// Whether a dot's sampling circle stays inside the image border
+    fn within_border(&self, pixel: &PixelData, width: u32, height: u32) -> bool {
+        let radius = self.config.circle_diameter / 2.0;
+        let (width, height) = (width as f32, height as f32);
+        pixel.x >= radius && pixel.y >= radius && pixel.x + radius <= width && pixel.y + radius <= height
+    }
+    
+    /// Centers a dot on the single source pixel it was sampled from
+    fn snap_to_source_pixel(pixel: &mut PixelData, width: u32, height: u32) {
+        pixel.x = (pixel.x as u32).min(width - 1) as f32 + 0.5;
+        pixel.y = (pixel.y as u32).min(height - 1) as f32 + 0.5;
+    }
+    
+    /// Randomly offsets dot centers by up to the configured jitter per axis
+    fn apply_jitter(&self, pixels: &mut [PixelData]) {
+        let jitter = self.config.jitter;
+        if jitter <= 0.0 {
+            return;
+        }
+        
+        let mut rng = crate::rng::feature_rng(self.config.seed, self.config.jitter_seed, crate::rng::JITTER_STREAM);
+        for pixel in pixels.iter_mut() {
+            Self::jitter_dot(pixel, jitter, &mut rng);
+        }
+    }
+    
+    /// Offsets a dot center by up to `jitter` per axis
+    fn jitter_dot(pixel: &mut PixelData, jitter: f32, rng: &mut impl rand::Rng) {
+        if jitter > 0.0 {
+            pixel.x += rng.gen_range(-jitter..=jitter);
+            pixel.y += rng.gen_range(-jitter..=jitter);
+        }
+    }
+    
+    /// Appends a smaller dot in the gap at the middle of every square of four
+    /// neighboring grid dots, if enabled
+    /// 
+    /// Each extra dot is as wide as the gap between the four, and sampled and
+    /// sized like a regular dot scaled down to that width. Extra dots have no
+    /// grid position.
+    fn add_interstices(&self, pixels: &mut Vec<PixelData>, image: &RgbaImage, cols: usize, rows: usize) -> Result<()> {
+        if !self.config.fill_interstices {
+            return Ok(());
+        }
+        if !matches!(self.config.sample_mode, SampleMode::Grid) {
+            return Err(PixelatorError::InvalidConfig(
+                "Interstice filling requires grid sampling mode".to_string(),
+            ));
+        }
+        
+        let (step_x, step_y) = self.config.grid_steps(image.width(), image.height());
+        let gap = (step_x * step_x + step_y * step_y).sqrt() - self.config.circle_diameter;
+        if gap <= 0.0 {
+            return Ok(());
+        }
+        let scale = gap / self.config.circle_diameter;
+        let radius = self.config.circle_diameter / 2.0;
+        
+        let interstices: Vec<PixelData> = (0..rows.saturating_sub(1))
+            .into_par_iter()
+            .flat_map_iter(|row| {
+                (0..cols.saturating_sub(1)).map(move |col| {
+                    let x = (col as f32 + 0.5) * step_x + radius;
+                    let y = (row as f32 + 0.5) * step_y + radius;
+                    let pixel = self.dot_at(image, x, y, (col, row));
+                    PixelData { dot_size: pixel.dot_size * scale, grid_position: None, ..pixel }
+                })
+            })
+            .collect();
+        pixels.extend(interstices);
+        Ok(())
+    }
+    
+    /// Keeps only the dots chosen by importance sampling, if enabled
+    fn apply_importance(&self, pixels: Vec<PixelData>) -> Vec<PixelData> {
+        let Some(count) = self.config.importance_dots else {
+            return pixels;
+        };
+        
+        let mut rng = crate::rng::feature_rng(self.config.seed, None, crate::rng::IMPORTANCE_STREAM);
+        let chosen = crate::importance::importance_sample(&pixels, count, &mut rng);
+        let mut keep = vec![false; pixels.len()];
+        for i in chosen {
+            keep[i] = true;
+        }
+        pixels.into_iter().zip(keep).filter_map(|(pixel, keep)| keep.then_some(pixel)).collect()
+    }
+    
+    /// Re-sizes dots from dithered brightness, if dithering is enabled
+    fn apply_dither(&self, pixels: &mut [PixelData]) {
+        if self.config.dither == DitherMode::None {
+            return;
+        }
+        
+        let tones = crate::dither::dither_brightness(pixels, self.config.dither);
+        for (pixel, tone) in pixels.iter_mut().zip(tones) {
+            pixel.dot_size = self.calculate_dot_size(tone);
+        }
+    }
+    
+    /// Linearly rescales halftone dot sizes to span the configured size range
+    fn normalize_dot_sizes(&self, pixels: &mut [PixelData]) {
+        use crate::config::RenderMode;
+        
+        if !self.config.normalize_dot_sizes || !matches!(self.config.render_mode, RenderMode::Halftone(_)) {
+            return;
+        }
+        
+        let (low, high) = pixels
+            .iter()
+            .fold((f32::MAX, f32::MIN), |(low, high), p| (low.min(p.dot_size), high.max(p.dot_size)));
+        if high - low <= f32::EPSILON {
+            return;
+        }
+        
+        let (min_size, max_size) = (self.config.min_dot_size, self.config.max_dot_size);
+        for pixel in pixels.iter_mut() {
+            pixel.dot_size = min_size + (pixel.dot_size - low) / (high - low) * (max_size - min_size);
+        }
+    }
+    
+    /// Tapers a dot within the edge fade margin of the image border
+    /// 
+    /// Color dots fade in opacity, every other mode shrinks the dot size.
+    fn fade_edge(&self, pixel: &mut PixelData, width: u32, height: u32) {
+        use crate::config::RenderMode;
+        
+        let Some(margin) = self.config.edge_fade else {
+            return;
+        };
+        let distance = pixel.x.min(pixel.y).min(width as f32 - pixel.x).min(height as f32 - pixel.y);
+        let factor = (distance / margin).clamp(0.0, 1.0);
+        if factor >= 1.0 {
+            return;
+        }
+        
+        match self.config.render_mode {
+            RenderMode::Color | RenderMode::SaturationMap => {
+                pixel.color[3] = (pixel.color[3] as f32 * factor).round() as u8;
+            }
+            _ => pixel.dot_size *= factor,
+        }
+    }
+    
+    /// Loads the configured size and clip masks, resized to the source dimensions
+    fn load_masks(&self, width: u32, height: u32) -> Result<DotMasks> {
+        let load = |mask: &Option<std::path::PathBuf>| match mask {
+            Some(path) => Self::load_mask(path, width, height).map(Some),
+            None => Ok(None),
+        };
+        Ok(DotMasks { size: load(&self.config.size_mask)?, clip: load(&self.config.clip_mask)? })
+    }
+    
+    /// Applies the per-dot steps shared by `sample_image` and `sample_iter`
+    /// 
+    /// Scales the dot by the size mask, tapers it near the image border, drops it
+    /// where the clip mask hides it and snaps its size to the configured levels.
+    fn finish_dot(&self, mut pixel: PixelData, masks: &DotMasks, width: u32, height: u32) -> Option<PixelData> {
+        if let Some(mask) = &masks.size {
+            pixel.dot_size *= Self::mask_value(mask, &pixel)[0] as f32 / 255.0;
+        }
+        self.fade_edge(&mut pixel, width, height);
+        if let Some(mask) = &masks.clip {
+            let value = Self::mask_value(mask, &pixel);
+            if value[0] < 128 || value[1] < 128 {
+                return None;
+            }
+        }
+        if self.config.quantizes_dot_sizes() {
+            pixel.dot_size = self.config.quantize_dot_size(pixel.dot_size);
+        }
+        Some(pixel)
+    }
+    
+    /// Loads a mask image as grayscale with alpha, resized to the source dimensions
+    fn load_mask(path: &std::path::Path, width: u32, height: u32) -> Result<image::GrayAlphaImage> {
+        let mask = image::open(path)?.to_luma_alpha8();
+        Ok(image::imageops::resize(&mask, width, height, image::imageops::FilterType::Triangle))
+    }
+    
+    /// Returns the mask pixel under a dot's center
+    fn mask_value(mask: &image::GrayAlphaImage, pixel: &PixelData) -> image::LumaA<u8> {
+        let x = (pixel.x.max(0.0) as u32).min(mask.width() - 1);
+        let y = (pixel.y.max(0.0) as u32).min(mask.height() - 1);
+        *mask.get_pixel(x, y)
+    }
+    
+    /// Applies palette quantization or dithering and colorblind-safe remapping, if configured
+    fn apply_palette(&self, pixels: &mut [PixelData]) {
+        use crate::palette;
+        
+        let quantized = if let Some(fixed) = &self.config.palette_dither {
+            let fixed: Vec<Rgba<u8>> = fixed.iter().map(|&channels| Rgba(channels)).collect();
+            palette::dither_pixels(pixels, &fixed, self.config.quantize_space);
+            fixed
+        } else {
+            let size = match (self.config.palette_size, self.config.colorblind_safe) {
+                (Some(size), _) => size,
+                (None, Some(_)) => palette::COLORBLIND_SAFE_PALETTE.len(),
+                (None, None) => return,
+            };
+            
+            match &self.source_palette {
+                Some(source) if self.config.palette_size.is_some() && !source.is_empty() && source.len() <= size => {
+                    palette::remap_pixels(pixels, source, self.config.quantize_space);
+                    source.clone()
+                }
+                _ => palette::quantize_pixels(pixels, size, self.config.quantize_space),
+            }
+        };
+        
+        if let Some(cb_type) = self.config.colorblind_safe {
+            let safe = palette::colorblind_safe_palette(&quantized, cb_type);
+            palette::substitute_colors(pixels, &quantized, &safe);
+        }
+    }
+    
+    /// Samples the averaged color for a dot centered at `(x, y)`
+    /// With supersampling enabled, the dot's cell is split into a
+    /// `factor x factor` grid of sub-samples which are averaged together
+    fn sample_color(&self, image: &image::RgbaImage, x: f32, y: f32) -> Rgba<u8> {
+        let (img_width, img_height) = (image.width(), image.height());
+        let factor = self.config.supersample.max(1) as u32;
+        
+        if self.config.pixel_art {
+            let sample_x = (x as u32).min(img_width - 1);
+            let sample_y = (y as u32).min(img_height - 1);
+            return *image.get_pixel(sample_x, sample_y);
+        }
+        
+        if factor == 1 {
+            return self.sample_kernel(image, x, y, self.config.circle_diameter);
+        }
+        
+        let cell = self.config.get_total_spacing();
+        let step = cell / factor as f32;
+        let half_cell = cell / 2.0;
+        
+        let mut sum = ColorSum::new(self.config.premultiplied_alpha);
+        for sy in 0..factor {
+            for sx in 0..factor {
+                let sub_x = x - half_cell + (sx as f32 + 0.5) * step;
+                let sub_y = y - half_cell + (sy as f32 + 0.5) * step;
+                
+                sum.add(&self.sample_kernel(image, sub_x, sub_y, step), 1.0);
+            }
+        }
+        
+        Rgba(sum.average().unwrap_or_default().map(|channel| channel as u8))
+    }
+    
+    /// Averages the pixels around a sample point using the configured kernel:
+    /// a directional streak if set, otherwise a circle of the given diameter
+    fn sample_kernel(&self, image: &image::RgbaImage, x: f32, y: f32, diameter: f32) -> Rgba<u8> {
+        let center_x = (x.max(0.0) as u32).min(image.width() - 1);
+        let center_y = (y.max(0.0) as u32).min(image.height() - 1);
+        let mirror = self.config.edge_mode == EdgeMode::Mirror;
+        let premultiplied = self.config.premultiplied_alpha;
+        
+        match &self.config.directional_sample {
+            Some(streak) => Self::sample_line_static(image, center_x, center_y, streak, premultiplied),
+            None if self.config.coverage_sampling => {
+                Self::sample_coverage_static(image, x, y, diameter, mirror, premultiplied)
+            }
+            None => Self::sample_area_static(image, center_x, center_y, diameter, mirror, premultiplied),
+        }
+    }
+    
+    /// Averages the pixels under a circle, each weighted by the fraction of
+    /// its area inside the circle
+    /// 
+    /// Pixel `(px, py)` covers the unit square from `(px, py)` to
+    /// `(px + 1, py + 1)`. Squares entirely inside or outside the circle get
+    /// weight 1 or 0; edge squares are estimated on a sub-sample grid.
+    fn sample_coverage_static(
+        image: &image::RgbaImage,
+        center_x: f32,
+        center_y: f32,
+        diameter: f32,
+        mirror: bool,
+        premultiplied: bool,
+    ) -> Rgba<u8> {
+        let radius = diameter / 2.0;
+        let (img_width, img_height) = (image.width(), image.height());
+        let radius_squared = radius * radius;
+        
+        // Mirrored windows reach past the edges; otherwise they stop at them
+        let mut x_start = (center_x - radius).floor() as i32;
+        let mut x_end = (center_x + radius).ceil() as i32;
+        let mut y_start = (center_y - radius).floor() as i32;
+        let mut y_end = (center_y + radius).ceil() as i32;
+        if !mirror {
+            (x_start, x_end) = (x_start.max(0), x_end.min(img_width as i32));
+            (y_start, y_end) = (y_start.max(0), y_end.min(img_height as i32));
+        }
+        
+        let mut sum = ColorSum::new(premultiplied);
+        
+        for py in y_start..y_end {
+            for px in x_start..x_end {
+                let (left, top) = (px as f32 - center_x, py as f32 - center_y);
+                let (right, bottom) = (left + 1.0, top + 1.0);
+                
+                // Distances to the nearest and farthest points of the square
+                let near_x = if left > 0.0 { left } else if right < 0.0 { right } else { 0.0 };
+                let near_y = if top > 0.0 { top } else if bottom < 0.0 { bottom } else { 0.0 };
+                let far_x = left.abs().max(right.abs());
+                let far_y = top.abs().max(bottom.abs());
+                
+                let weight = if near_x * near_x + near_y * near_y >= radius_squared {
+                    0.0
+                } else if far_x * far_x + far_y * far_y <= radius_squared {
+                    1.0
+                } else {
+                    let step = 1.0 / COVERAGE_SUBSAMPLES as f32;
+                    let mut inside = 0;
+                    for sy in 0..COVERAGE_SUBSAMPLES {
+                        for sx in 0..COVERAGE_SUBSAMPLES {
+                            let dx = left + (sx as f32 + 0.5) * step;
+                            let dy = top + (sy as f32 + 0.5) * step;
+                            if dx * dx + dy * dy <= radius_squared {
+                                inside += 1;
+                            }
+                        }
+                    }
+                    inside as f32 / (COVERAGE_SUBSAMPLES * COVERAGE_SUBSAMPLES) as f32
+                };
+                
+                if weight > 0.0 {
+                    sum.add(image.get_pixel(mirror_index(px, img_width), mirror_index(py, img_height)), weight);
+                }
+            }
+        }
+        
+        match sum.average() {
+            Some(average) => Rgba(average.map(|channel| channel.round() as u8)),
+            None => {
+                let x = (center_x.max(0.0) as u32).min(img_width - 1);
+                let y = (center_y.max(0.0) as u32).min(img_height - 1);
+                *image.get_pixel(x, y)
+            }
+        }
+    }
+    
+    /// Averages the pixels along a line segment through the center point
+    fn sample_line_static(
+        image: &image::RgbaImage,
+        center_x: u32,
+        center_y: u32,
+        streak: &DirectionalSample,
+        premultiplied: bool,
+    ) -> Rgba<u8> {
+        let (img_width, img_height) = (image.width(), image.height());
+        let (sin, cos) = streak.angle.to_radians().sin_cos();
+        let steps = streak.length.round().max(1.0) as u32;
+        
+        let mut sum = ColorSum::new(premultiplied);
+        for i in 0..=steps {
+            let t = (i as f32 / steps as f32 - 0.5) * streak.length;
+            let x = (center_x as f32 + t * cos).round().clamp(0.0, (img_width - 1) as f32) as u32;
+            let y = (center_y as f32 + t * sin).round().clamp(0.0, (img_height - 1) as f32) as u32;
+            
+            sum.add(image.get_pixel(x, y), 1.0);
+        }
+        
+        Rgba(sum.average().unwrap_or_default().map(|channel| channel as u8))
+    }
+    
+    fn sample_area_static(
+        image: &image::RgbaImage,
+        center_x: u32,
+        center_y: u32,
+        circle_diameter: f32,
+        mirror: bool,
+        premultiplied: bool,
+    ) -> Rgba<u8> {
         let radius = (circle_diameter / 2.0) as i32;
         let (img_width, img_height) = (image.width(), image.height());
         
-        let mut r_sum = 0u32;
-        let mut g_sum = 0u32;
-        let mut b_sum = 0u32;
-        let mut a_sum = 0u32;
-        let mut count = 0u32;
+        let mut sum = ColorSum::new(premultiplied);
         
-        // Use integer bounds to avoid conversions in the loop
-        let x_start = (center_x as i32).saturating_sub(radius).max(0) as u32;
-        let x_end = ((center_x as i32) + radius).min(img_width as i32 - 1) as u32;
-        let y_start = (center_y as i32).saturating_sub(radius).max(0) as u32;
-        let y_end = ((center_y as i32) + radius).min(img_height as i32 - 1) as u32;
+        // Use integer bounds to avoid conversions in the loop; mirrored
+        // windows reach past the edges, otherwise they stop at them
+        let (center_x, center_y) = (center_x as i32, center_y as i32);
+        let mut x_start = center_x - radius;
+        let mut x_end = center_x + radius;
+        let mut y_start = center_y - radius;
+        let mut y_end = center_y + radius;
+        if !mirror {
+            (x_start, x_end) = (x_start.max(0), x_end.min(img_width as i32 - 1));
+            (y_start, y_end) = (y_start.max(0), y_end.min(img_height as i32 - 1));
+        }
         
         let radius_squared = radius * radius;
         
         for y in y_start..=y_end {
             for x in x_start..=x_end {
-                let dx = x as i32 - center_x as i32;
-                let dy = y as i32 - center_y as i32;
+                let dx = x - center_x;
+                let dy = y - center_y;
                 
                 // Use integer arithmetic for circle check
                 if dx * dx + dy * dy <= radius_squared {
-                    let pixel = image.get_pixel(x, y);
-                    r_sum += pixel[0] as u32;
-                    g_sum += pixel[1] as u32;
-                    b_sum += pixel[2] as u32;
-                    a_sum += pixel[3] as u32;
-                    count += 1;
+                    sum.add(image.get_pixel(mirror_index(x, img_width), mirror_index(y, img_height)), 1.0);
                 }
             }
         }
         
-        if count > 0 {
-            Rgba([
-                (r_sum / count) as u8,
-                (g_sum / count) as u8,
-                (b_sum / count) as u8,
-                (a_sum / count) as u8,
-            ])
-        } else {
-            *image.get_pixel(center_x, center_y)
+        match sum.average() {
+            Some(average) => Rgba(average.map(|channel| channel as u8)),
+            None => *image.get_pixel(center_x as u32, center_y as u32),
         }
     }
     
@@ -165,12 +749,78 @@ impl<'a> ImageProcessor<'a> {
         0.2126 * r + 0.7152 * g + 0.0722 * b
     }
     
+    /// Calculate perceived lightness from an RGBA color as CIE L* normalized to 0.0 to 1.0
+    pub fn calculate_lightness(color: &Rgba<u8>) -> f32 {
+        crate::color::rgb_to_lab(color)[0] / 100.0
+    }
+    
+    /// Calculate brightness using the configured metric
+    fn brightness(&self, color: &Rgba<u8>) -> f32 {
+        use crate::config::BrightnessMetric;
+        
+        let brightness = match self.config.brightness_metric {
+            BrightnessMetric::Luminance => Self::calculate_brightness(color),
+            BrightnessMetric::CieLStar => Self::calculate_lightness(color),
+        };
+        
+        if self.config.alpha_in_brightness {
+            // Composite over the background tone so transparent areas read as background
+            let alpha = color[3] as f32 / 255.0;
+            alpha * brightness + (1.0 - alpha) * self.background_brightness()
+        } else {
+            brightness
+        }
+    }
+    
+    /// Tone fed to dot sizing for the configured size source
+    /// 
+    /// Alpha and saturation are expressed as the brightness that gives a dot
+    /// of that coverage, so opaque or saturated colors get the largest dots
+    /// in either halftone style.
+    fn size_tone(&self, color: &Rgba<u8>, brightness: f32) -> f32 {
+        use crate::config::{HalftoneStyle, RenderMode, SizeSource};
+        
+        let coverage = match self.config.size_source {
+            SizeSource::Brightness => return brightness,
+            SizeSource::Alpha => color[3] as f32 / 255.0,
+            SizeSource::Saturation => crate::color::rgb_to_hsl(color)[1],
+        };
+        match self.config.render_mode {
+            RenderMode::Halftone(HalftoneStyle::WhiteOnBlack) => coverage,
+            _ => 1.0 - coverage,
+        }
+    }
+    
+    /// Brightness of the surface dots are drawn on: black for white-on-black
+    /// halftones, white paper otherwise
+    fn background_brightness(&self) -> f32 {
+        use crate::config::{RenderMode, HalftoneStyle};
+        
+        match self.config.render_mode {
+            RenderMode::Halftone(HalftoneStyle::WhiteOnBlack) => 0.0,
+            _ => 1.0,
+        }
+    }
+    
     /// Calculate dot size based on brightness for halftone effect
-    fn calculate_dot_size(&self, brightness: f32) -> f32 {
+    /// 
+    /// Halftone sizes stay within the configured dot size range whatever the
+    /// brightness: out-of-range tones are clamped to 0.0 to 1.0 and NaN counts
+    /// as no ink, giving the smallest dot. Dot gain compensation shrinks dots
+    /// no further than the minimum size.
+    pub(crate) fn calculate_dot_size(&self, brightness: f32) -> f32 {
         use crate::config::{RenderMode, HalftoneStyle};
         
         match &self.config.render_mode {
-            RenderMode::Color => self.config.circle_diameter,
+            RenderMode::Color | RenderMode::SaturationMap | RenderMode::Voronoi | RenderMode::LineScreen { .. }
+            | RenderMode::Hatch(_) | RenderMode::PointCloud | RenderMode::ColorByNumber
+            | RenderMode::Contours { .. } | RenderMode::MorphShape => {
+                self.config.circle_diameter
+            }
+            RenderMode::FmHalftone { dot_size } | RenderMode::Stipple { dot_size, .. } => *dot_size,
+            RenderMode::Threshold { level } => {
+                if brightness < *level { self.config.circle_diameter } else { 0.0 }
+            }
             RenderMode::Halftone(style) => {
                 // Invert brightness for black-on-white (darker = larger dots)
                 // Keep normal for white-on-black (brighter = larger dots)
@@ -178,11 +828,98 @@ impl<'a> ImageProcessor<'a> {
                     HalftoneStyle::BlackOnWhite => 1.0 - brightness,
                     HalftoneStyle::WhiteOnBlack => brightness,
                 };
+                let unit = |value: f32| if value.is_nan() { 0.0 } else { value.clamp(0.0, 1.0) };
+                
+                let adjusted_brightness = unit(self.config.halftone_curve.apply(unit(adjusted_brightness)));
                 
                 // Map brightness to dot size range
-                self.config.min_dot_size + 
-                    (self.config.max_dot_size - self.config.min_dot_size) * adjusted_brightness
+                let (min_size, max_size) = (self.config.min_dot_size, self.config.max_dot_size);
+                let (low, high) = (min_size.min(max_size), max_size.max(min_size));
+                let size = (min_size + (max_size - min_size) * adjusted_brightness).clamp(low, high);
+                let size = match self.config.dot_gain {
+                    Some(percent) => self.compensate_dot_gain(size, percent / 100.0),
+                    None => size,
+                };
+                size.clamp(low, high)
             }
         }
     }
-}
\ No newline at end of file
+    
+    /// Shrinks a dot so that after the expected dot gain it prints at its
+    /// intended coverage of the cell
+    /// 
+    /// Printed coverage is modeled as `c + 4g·c(1 - c)`; solving for `c`
+    /// inverts it. Dots covering the whole cell are left as they are.
+    fn compensate_dot_gain(&self, size: f32, gain: f32) -> f32 {
+        let cell = self.config.get_total_spacing();
+        let cell_area = cell * cell;
+        let coverage = std::f32::consts::FRAC_PI_4 * size * size / cell_area;
+        if gain <= 0.0 || !(0.0..1.0).contains(&coverage) {
+            return size;
+        }
+        
+        let b = 1.0 + 4.0 * gain;
+        let compensated = (b - (b * b - 16.0 * gain * coverage).sqrt()) / (8.0 * gain);
+        (compensated * cell_area / std::f32::consts::FRAC_PI_4).sqrt()
+    }
+}
+
+/// Weighted running sum of RGBA samples
+/// 
+/// With premultiplied averaging, each sample's color counts in proportion to
+/// its alpha, so fully transparent pixels (often stored as black) don't
+/// darken the average. Samples that are all transparent fall back to the
+/// straight average of their colors.
+struct ColorSum {
+    premultiplied: bool,
+    // Sums in f64 stay exact over large windows, so opaque averages match
+    // integer division
+    straight: [f64; 3],
+    weighted: [f64; 3],
+    alpha: f64,
+    weight: f64,
+}
+
+impl ColorSum {
+    fn new(premultiplied: bool) -> Self {
+        Self { premultiplied, straight: [0.0; 3], weighted: [0.0; 3], alpha: 0.0, weight: 0.0 }
+    }
+    
+    fn add(&mut self, pixel: &Rgba<u8>, weight: f32) {
+        let weight = weight as f64;
+        let alpha = pixel[3] as f64 * weight;
+        for channel in 0..3 {
+            self.straight[channel] += pixel[channel] as f64 * weight;
+            self.weighted[channel] += pixel[channel] as f64 * alpha;
+        }
+        self.alpha += alpha;
+        self.weight += weight;
+    }
+    
+    /// Average channels in `0.0..=255.0`, or `None` if nothing was added
+    fn average(&self) -> Option<[f32; 4]> {
+        if self.weight <= 0.0 {
+            return None;
+        }
+        let [r, g, b] = if self.premultiplied && self.alpha > 0.0 {
+            self.weighted.map(|sum| sum / self.alpha)
+        } else {
+            self.straight.map(|sum| sum / self.weight)
+        };
+        Some([r, g, b, self.alpha / self.weight].map(|channel| channel as f32))
+    }
+}
+
+/// Maps a possibly out-of-bounds coordinate back into `0..size` by reflecting
+/// it at the image edges, so -1 maps to 0 and `size` maps to `size - 1`
+fn mirror_index(value: i32, size: u32) -> u32 {
+    let size = size as i32;
+    let folded = value.rem_euclid(2 * size);
+    (if folded < size { folded } else { 2 * size - 1 - folded }) as u32
+}
+
+/// Mask images loaded once per sampled image, for the per-dot steps
+struct DotMasks {
+    size: Option<image::GrayAlphaImage>,
+    clip: Option<image::GrayAlphaImage>,
+}
@@ -0,0 +1,25 @@
+use crate::error::Result;
+use crate::processor::PixelData;
+use std::io::Write;
+
+/// An output format that sampled pixel data can be written in
+/// 
+/// Implemented by every generator so output can be streamed to any writer,
+/// such as a file, a socket or an in-memory buffer, without building the
+/// whole document as a `String` first.
+pub trait Render {
+    /// Writes the rendered output for the given pixel data to `writer`
+    /// 
+    /// # Arguments
+    /// * `writer` - Destination for the output bytes
+    /// * `pixels` - The sampled pixel data
+    /// * `original_width` - Original image width in pixels
+    /// * `original_height` - Original image height in pixels
+    fn render<W: Write>(
+        &self,
+        writer: &mut W,
+        pixels: &[PixelData],
+        original_width: u32,
+        original_height: u32,
+    ) -> Result<()>;
+}
\ No newline at end of file
@@ -0,0 +1,19 @@
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+// Stream ids mixed into the global seed so features draw independent sequences
+pub(crate) const JITTER_STREAM: u64 = 1;
+pub(crate) const STIPPLE_STREAM: u64 = 2;
+pub(crate) const IMPORTANCE_STREAM: u64 = 3;
+
+/// Returns the random number generator for one stochastic feature
+/// 
+/// A feature's own seed takes precedence over the global seed. Without
+/// either, the generator is seeded from system entropy.
+pub(crate) fn feature_rng(global_seed: Option<u64>, feature_seed: Option<u64>, stream: u64) -> StdRng {
+    match (feature_seed, global_seed) {
+        (Some(seed), _) => StdRng::seed_from_u64(seed),
+        (None, Some(seed)) => StdRng::seed_from_u64(seed ^ stream.wrapping_mul(0x9E37_79B9_7F4A_7C15)),
+        (None, None) => StdRng::from_entropy(),
+    }
+}
\ No newline at end of file
@@ -1,19 +1,120 @@
-use crate::config::PixelatorConfig;
-use crate::error::Result;
+use crate::color;
+use crate::config::{BackgroundGradient, Origin, PaintOrder, PatternKind, PixelatorConfig};
+use crate::dot_file::DotFile;
+use crate::error::{PixelatorError, Result};
+use crate::format::format_number;
 use crate::processor::PixelData;
+use crate::render::Render;
+use crate::voronoi;
+use std::borrow::Cow;
 use std::collections::HashMap;
+use std::sync::Mutex;
 use svg::Document;
-use svg::node::element::Circle;
+use svg::node::Comment;
+use svg::node::element::{
+    Animate, Circle, Definitions, Element, Filter, Group, Image, Line, LinearGradient, Path, Pattern, Polygon, RadialGradient,
+    Rectangle, Stop, Text, Use,
+};
+
+// Id of the background pattern def and the color its texture is drawn in
+const BACKGROUND_PATTERN_ID: &str = "background-pattern";
+const BACKGROUND_PATTERN_COLOR: &str = "#cccccc";
+
+// Id of the background gradient def
+const BACKGROUND_GRADIENT_ID: &str = "background-gradient";
+
+// Legend rows and label sizes for color-by-number, relative to the dot cell
+const LEGEND_ROW_CELLS: f32 = 1.5;
+const LEGEND_ENTRY_CELLS: f32 = 4.0;
+const LABEL_FONT_CELLS: f32 = 0.4;
+const OUTLINE_WIDTH_CELLS: f32 = 0.04;
+
+// Width of guide grid lines, relative to the dot cell
+const GUIDE_LINE_WIDTH_CELLS: f32 = 0.05;
+
+// Length of each registration crosshair arm pair, relative to the dot cell
+const REGISTRATION_MARK_CELLS: f32 = 1.0;
+
+// Id of the shared blur filter used by glow output
+const GLOW_FILTER_ID: &str = "glow";
+
+// Longest side in pixels of the source image embedded as a reference layer,
+// and the namespace of the attributes marking it and spot inks as Inkscape layers
+const REFERENCE_MAX_SIDE: u32 = 1024;
+const INKSCAPE_NAMESPACE: &str = "http://www.inkscape.org/namespaces/inkscape";
+
+// Most dots a reveal animation is emitted for, and the share of its duration
+// each dot takes to grow in
+const REVEAL_MAX_DOTS: usize = 10_000;
+const REVEAL_GROW_FRACTION: f32 = 0.1;
+
+// Id of the sprite image def used by tile output
+const TILE_SPRITE_ID: &str = "tile-sprite";
+
+// Radius of point cloud markers in image pixels
+const POINT_MARKER_RADIUS: f32 = 0.1;
+
+// Placeholder comment marking where streamed dots go in the document frame
+const STREAM_MARKER: &str = "dots";
+
+// Low bits of a packed RGB color that pick the slot within a fill cache
+// page; the remaining high bits pick the page
+const COLOR_PAGE_BITS: u32 = 12;
+
+// Decimal places kept for coordinates in image pixel units
+const DEFAULT_DECIMALS: usize = 3;
 
 /// Generates SVG output from sampled pixel data
 pub struct SvgGenerator<'a> {
     config: &'a PixelatorConfig,
+    decimals: usize,
+    reference: Option<String>,  // PNG data URI of the source image for the reference layer
+    warnings: Mutex<Vec<String>>,  // Parts of the output that were left out, for `take_warnings`
 }
 
 impl<'a> SvgGenerator<'a> {
     /// Creates a new SVG generator with the given configuration
     pub fn new(config: &'a PixelatorConfig) -> Self {
-        Self { config }
+        Self { config, decimals: DEFAULT_DECIMALS, reference: None, warnings: Mutex::new(Vec::new()) }
+    }
+    
+    /// Supplies the source image drawn as the reference layer
+    /// 
+    /// Only used when the configuration enables a reference layer. The image
+    /// is downscaled to at most 1024 pixels on its longer side and embedded
+    /// as a base64 PNG.
+    pub fn with_reference_image(mut self, image: &image::DynamicImage) -> Result<Self> {
+        use base64::Engine;
+        
+        let image = if image.width().max(image.height()) > REFERENCE_MAX_SIDE {
+            image.thumbnail(REFERENCE_MAX_SIDE, REFERENCE_MAX_SIDE)
+        } else {
+            image.clone()
+        };
+        let mut png = std::io::Cursor::new(Vec::new());
+        image.to_rgba8().write_to(&mut png, image::ImageOutputFormat::Png)?;
+        let encoded = base64::engine::general_purpose::STANDARD.encode(png.into_inner());
+        self.reference = Some(format!("data:image/png;base64,{}", encoded));
+        Ok(self)
+    }
+    
+    /// Returns and clears the warnings collected while generating output,
+    /// such as a reveal animation skipped for having too many dots
+    pub fn take_warnings(&self) -> Vec<String> {
+        std::mem::take(&mut *self.warnings.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+    
+    fn warn(&self, warnings: impl IntoIterator<Item = String>) {
+        self.warnings.lock().unwrap_or_else(|e| e.into_inner()).extend(warnings);
+    }
+    
+    /// Renders an SVG directly from a saved JSON dot file
+    /// 
+    /// The configuration stored in the file is used for rendering, so colors
+    /// and render options can be tweaked in the JSON without re-sampling.
+    pub fn from_dot_json(json: &str) -> Result<String> {
+        let dots = DotFile::from_json(json)?;
+        SvgGenerator::new(&dots.config).generate_svg(&dots.dots, dots.width, dots.height)
     }
     
     /// Generates an SVG document from pixel data
@@ -30,6 +131,182 @@ impl<'a> SvgGenerator<'a> {
         original_width: u32,
         original_height: u32,
     ) -> Result<String> {
+        Ok(self.build_document(pixels, original_width, original_height)?.to_string())
+    }
+    
+    /// Generates an SVG document from pixel data as UTF-8 bytes
+    /// 
+    /// The document is written straight into the byte buffer, avoiding an
+    /// intermediate `String` when the output goes to a socket or file.
+    pub fn generate_svg_bytes(
+        &self,
+        pixels: &[PixelData],
+        original_width: u32,
+        original_height: u32,
+    ) -> Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        self.render(&mut bytes, pixels, original_width, original_height)?;
+        Ok(bytes)
+    }
+    
+    /// Writes an SVG document to `writer` one dot at a time, as the dots are
+    /// produced
+    /// 
+    /// Pairs with `ImageProcessor::sample_iter` so neither the dots nor the
+    /// document are held in memory, and writes the same document as
+    /// `generate_svg`. Only plain color rendering can be streamed; options
+    /// that work on all dots at once, such as paint order, Y-up output or
+    /// glow, return an `InvalidConfig` error.
+    pub fn write_svg_streamed<W: std::io::Write>(
+        &self,
+        writer: &mut W,
+        pixels: impl IntoIterator<Item = PixelData>,
+        original_width: u32,
+        original_height: u32,
+    ) -> Result<()> {
+        if !matches!(self.config.render_mode, crate::config::RenderMode::Color)
+            || self.config.normalized_coordinates
+            || !matches!(self.config.origin, Origin::TopLeft)
+            || !matches!(self.config.paint_order, PaintOrder::AsSampled)
+            || self.config.snap_to_grid_mm.is_some()
+            || self.config.scanline_grouping
+            || self.config.svg_y_up
+            || self.config.spot_color_bands.is_some()
+            || self.config.tile_image.is_some()
+            || self.config.path_merge_by_color
+            || self.config.gradient_dots
+            || self.config.reveal_animation.is_some()
+            || self.config.glow.is_some()
+            || self.config.registration_marks
+            || self.config.scale_bar_mm.is_some()
+        {
+            return Err(PixelatorError::InvalidConfig(
+                "Only color rendering without whole-document options can be streamed".to_string(),
+            ));
+        }
+        
+        // Everything around the dots comes from the document without them
+        let marker = Comment::new(STREAM_MARKER);
+        let frame = self
+            .render_document(&[], original_width, original_height, 1.0)?
+            .add(marker.clone())
+            .to_string();
+        let (head, tail) = frame.split_once(&format!("\n{}", marker)).expect("the frame ends with the marker");
+        writer.write_all(head.as_bytes())?;
+        
+        let mut color_cache = ColorCache::default();
+        let mut merger = ColorMerger::new(self.config.color_merge_tolerance);
+        for pixel in pixels {
+            let color_key = merger.merge((pixel.color[0], pixel.color[1], pixel.color[2]));
+            
+            // Get or create the color string
+            let color = color_cache.fill(color_key);
+            
+            let opacity = pixel.color[3] as f32 / 255.0;
+            
+            let circle = Circle::new()
+                .set("cx", self.num(pixel.x))
+                .set("cy", self.num(pixel.y))
+                .set("r", self.num(self.color_radius(&pixel)));
+            let circle = self.paint(circle, color, Some(opacity));
+            
+            write!(writer, "\n{}", circle)?;
+        }
+        writer.write_all(tail.as_bytes())?;
+        Ok(())
+    }
+    
+    /// Renders the dots into the group with id `group_id` of an existing SVG
+    /// 
+    /// The rendered content (defs, background and dots, without the root
+    /// `<svg>` element) is inserted at the end of the group and the merged
+    /// document is returned; the rest of the template is left untouched.
+    /// Coordinates are in image pixels, so the template should size or
+    /// transform the group to fit. Returns `InvalidConfig` if the template
+    /// isn't valid SVG or has no such group.
+    pub fn append_to_svg(
+        &self,
+        existing: &str,
+        group_id: &str,
+        pixels: &[PixelData],
+        original_width: u32,
+        original_height: u32,
+    ) -> Result<String> {
+        for event in svg::read(existing)? {
+            if let svg::parser::Event::Error(error) = event {
+                return Err(PixelatorError::InvalidConfig(format!("Invalid SVG template: {}", error)));
+            }
+        }
+        let (insert_at, self_closing) = find_group(existing, group_id).ok_or_else(|| {
+            PixelatorError::InvalidConfig(format!("SVG template has no group with id {:?}", group_id))
+        })?;
+        
+        let document = self.build_document(pixels, original_width, original_height)?;
+        let content: String = document.get_children().iter().map(|node| format!("\n{}", node)).collect();
+        
+        let mut merged = String::with_capacity(existing.len() + content.len() + 8);
+        if self_closing {
+            // Reopen `<g .../>` as `<g ...>` so it can hold the dots
+            merged.push_str(existing[..insert_at].trim_end());
+            merged.push('>');
+            merged.push_str(&content);
+            merged.push_str("\n</g>");
+            merged.push_str(&existing[insert_at + "/>".len()..]);
+        } else {
+            merged.push_str(&existing[..insert_at]);
+            merged.push_str(&content);
+            merged.push('\n');
+            merged.push_str(&existing[insert_at..]);
+        }
+        Ok(merged)
+    }
+    
+    /// Builds the SVG document tree for the given pixel data
+    /// 
+    /// This is the document `generate_svg` serializes. Callers can add their
+    /// own elements (watermarks, extra layers) before writing it out with
+    /// `to_string()` or `svg::write`.
+    pub fn build_document(
+        &self,
+        pixels: &[PixelData],
+        original_width: u32,
+        original_height: u32,
+    ) -> Result<Document> {
+        if self.config.normalized_coordinates {
+            // Scale so the longer image side spans one viewBox unit
+            let unit = 1.0 / original_width.max(original_height).max(1) as f32;
+            let config = Self::scale_config(self.config, unit);
+            let pixels: Vec<PixelData> = pixels
+                .iter()
+                .map(|p| PixelData { x: p.x * unit, y: p.y * unit, dot_size: p.dot_size * unit, ..p.clone() })
+                .collect();
+            // Keep the same precision relative to the image as in pixel units
+            let decimals = DEFAULT_DECIMALS + (1.0 / unit).log10().ceil() as usize;
+            let generator = SvgGenerator {
+                config: &config,
+                decimals,
+                reference: self.reference.clone(),
+                warnings: Mutex::new(Vec::new()),
+            };
+            let document = generator.render_document(&pixels, original_width, original_height, unit);
+            self.warn(generator.take_warnings());
+            return document;
+        }
+        
+        self.render_document(pixels, original_width, original_height, 1.0)
+    }
+    
+    /// Renders the document with viewBox coordinates of `unit` per image pixel
+    fn render_document(
+        &self,
+        pixels: &[PixelData],
+        original_width: u32,
+        original_height: u32,
+        unit: f32,
+    ) -> Result<Document> {
+        self.config.check_aspect_ratio(original_width, original_height)?;
+        self.config.check_overlap()?;
+        let (width, height) = (original_width as f32 * unit, original_height as f32 * unit);
         let (svg_width, svg_height) = if let (Some(w), Some(h)) = 
             (self.config.output_width_mm, self.config.output_height_mm) {
             (w, h)
@@ -37,17 +314,86 @@ impl<'a> SvgGenerator<'a> {
             (original_width as f32, original_height as f32)
         };
         
+        if self.config.scale_bar_mm.is_some() {
+            if self.config.output_width_mm.is_none() || self.config.output_height_mm.is_none() {
+                return Err(PixelatorError::InvalidConfig(
+                    "Scale bar requires output dimensions in millimeters".to_string(),
+                ));
+            }
+            if self.config.margin_mm <= 0.0 {
+                return Err(PixelatorError::InvalidConfig(
+                    "Scale bar requires a margin to be drawn in".to_string(),
+                ));
+            }
+        }
+        
+        let (view_x, view_y) = self.view_box_origin(width, height);
+        let pixels = Self::shift_pixels(pixels, view_x, view_y);
+        let pixels = self.snap_pixels(pixels, svg_width / width, svg_height / height);
+        let pixels = self.order_pixels(pixels);
+        let (pixels, rows) = self.scanline_order(pixels);
+        let pixels = pixels.as_ref();
+        
+        // Color-by-number extends the page downward to fit its legend
+        use crate::config::{RenderMode, HalftoneStyle};
+        let palette = match self.config.render_mode {
+            RenderMode::ColorByNumber => Self::number_palette(pixels),
+            _ => Vec::new(),
+        };
+        let legend_height = self.legend_height(palette.len(), width);
+        let svg_height = svg_height + legend_height * svg_height / height;
+        let height = height + legend_height;
+        
+        // The margin is given in mm and converted to viewBox units per axis;
+        // on a fitted page it grows to center the field on the page
+        let margin = self.config.margin_mm;
+        let (margin_mm_x, margin_mm_y) = match self.config.page_mm {
+            Some((page_width, page_height)) => (
+                ((page_width - svg_width) / 2.0).max(margin),
+                ((page_height - svg_height) / 2.0).max(margin),
+            ),
+            None => (margin, margin),
+        };
+        let (margin_x, margin_y) = (margin_mm_x * width / svg_width, margin_mm_y * height / svg_height);
+        let view_box = (
+            view_x - margin_x,
+            view_y - margin_y,
+            width + 2.0 * margin_x,
+            height + 2.0 * margin_y,
+        );
+        
         let mut document = Document::new()
-            .set("width", format!("{}mm", svg_width))
-            .set("height", format!("{}mm", svg_height))
-            .set("viewBox", (0, 0, original_width, original_height))
+            .set("width", format!("{}mm", format_number(svg_width + 2.0 * margin_mm_x, self.config.mm_decimals)))
+            .set("height", format!("{}mm", format_number(svg_height + 2.0 * margin_mm_y, self.config.mm_decimals)))
+            .set("viewBox", format!(
+                "{} {} {} {}",
+                self.num(view_box.0),
+                self.num(view_box.1),
+                self.num(view_box.2),
+                self.num(view_box.3)
+            ))
             .set("xmlns", "http://www.w3.org/2000/svg")
             .set("xmlns:xlink", "http://www.w3.org/1999/xlink");
         
+        if unit != 1.0 {
+            document = document.set("preserveAspectRatio", "xMidYMid meet");
+        }
+        
         // Set background based on render mode
-        use crate::config::{RenderMode, HalftoneStyle};
         let background = match &self.config.render_mode {
-            RenderMode::Color => self.config.background_color.clone(),
+            RenderMode::Color | RenderMode::SaturationMap | RenderMode::Voronoi | RenderMode::MorphShape => {
+                self.config.background_color.clone()
+            }
+            RenderMode::PointCloud => None,
+            RenderMode::FmHalftone { .. }
+            | RenderMode::Stipple { .. }
+            | RenderMode::LineScreen { .. }
+            | RenderMode::Hatch(_)
+            | RenderMode::ColorByNumber
+            | RenderMode::Contours { .. }
+            | RenderMode::Threshold { .. } => {
+                Some("white".to_string())
+            }
             RenderMode::Halftone(style) => Some(match style {
                 HalftoneStyle::BlackOnWhite => "white".to_string(),
                 HalftoneStyle::WhiteOnBlack => "black".to_string(),
@@ -55,46 +401,250 @@ impl<'a> SvgGenerator<'a> {
         };
         
         if let Some(ref bg_color) = background {
-            document = document.set("style", format!("background-color: {}", bg_color));
+            if self.config.background_rect {
+                // An explicit rect renders in consumers that ignore CSS backgrounds
+                let rect = Rectangle::new()
+                    .set("x", self.num(view_box.0))
+                    .set("y", self.num(view_box.1))
+                    .set("width", self.num(view_box.2))
+                    .set("height", self.num(view_box.3))
+                    .set("fill", bg_color.as_str());
+                document = document.add(rect);
+            } else {
+                document = document.set("style", format!("background-color: {}", bg_color));
+            }
+        }
+        
+        if let Some(gradient) = &self.config.background_gradient {
+            document = document.add(Definitions::new().add(self.background_gradient(gradient)));
+            
+            let rect = Rectangle::new()
+                .set("x", self.num(view_box.0))
+                .set("y", self.num(view_box.1))
+                .set("width", self.num(view_box.2))
+                .set("height", self.num(view_box.3))
+                .set("fill", format!("url(#{})", BACKGROUND_GRADIENT_ID));
+            document = document.add(rect);
+        }
+        
+        if let Some(kind) = self.config.background_pattern {
+            let tile = self.config.get_total_spacing();
+            document = document.add(Definitions::new().add(self.background_pattern(kind, tile)));
+            
+            let rect = Rectangle::new()
+                .set("x", self.num(view_box.0))
+                .set("y", self.num(view_box.1))
+                .set("width", self.num(view_box.2))
+                .set("height", self.num(view_box.3))
+                .set("fill", format!("url(#{})", BACKGROUND_PATTERN_ID));
+            document = document.add(rect);
+        }
+        
+        if let (Some(opacity), Some(href)) = (self.config.reference_layer, &self.reference) {
+            // A layer group so editors like Inkscape can hide or lock it
+            let image = Image::new()
+                .set("href", href.as_str())
+                .set("x", self.num(view_x))
+                .set("y", self.num(view_y))
+                .set("width", self.num(width))
+                .set("height", self.num(height - legend_height))
+                .set("preserveAspectRatio", "none")
+                .set("opacity", self.num(opacity));
+            document = document.set("xmlns:inkscape", INKSCAPE_NAMESPACE).add(
+                Group::new()
+                    .set("id", "reference-layer")
+                    .set("inkscape:groupmode", "layer")
+                    .set("inkscape:label", "Reference")
+                    .add(image),
+            );
         }
         
+        if let Some((every, color)) = &self.config.guide_grid {
+            document = document.add(self.guide_grid(pixels, *every, color)?);
+        }
+        
+        let pixels = match &self.config.render_mode {
+            RenderMode::SaturationMap => Cow::Owned(Self::map_saturation(pixels)),
+            _ => Cow::Borrowed(pixels),
+        };
+        
+        // Y-up content is mirrored about the image's middle row, keeping the
+        // same coordinate range, and flipped back by a wrapper group below
+        let flip = 2.0 * view_y + height;
+        let pixels = if self.config.svg_y_up {
+            if let RenderMode::ColorByNumber = self.config.render_mode {
+                return Err(PixelatorError::InvalidConfig(
+                    "Y-up SVG output is not supported for color-by-number".to_string(),
+                ));
+            }
+            // Grid rows are mirrored too, so neighbor-based modes see the same layout
+            let last_row = pixels.iter().filter_map(|p| p.grid_position).map(|(_, row)| row).max().unwrap_or(0);
+            Cow::Owned(
+                pixels
+                    .iter()
+                    .map(|p| PixelData {
+                        y: flip - p.y,
+                        grid_position: p.grid_position.map(|(col, row)| (col, last_row - row)),
+                        ..p.clone()
+                    })
+                    .collect(),
+            )
+        } else {
+            pixels
+        };
+        let pixels = pixels.as_ref();
+        let content_start = document.get_children().len();
+        
         match &self.config.render_mode {
-            RenderMode::Color => {
+            RenderMode::Color | RenderMode::SaturationMap | RenderMode::Halftone(_) | RenderMode::Threshold { .. }
+                if self.config.spot_color_bands.is_some() =>
+            {
+                document = self.add_spot_layers(document, pixels);
+            }
+            RenderMode::Color | RenderMode::SaturationMap if self.config.tile_image.is_some() => {
+                document = self.add_tiles(document, pixels);
+            }
+            RenderMode::Color | RenderMode::SaturationMap if self.config.path_merge_by_color => {
+                document = self.add_color_paths(document, pixels);
+            }
+            RenderMode::Color | RenderMode::SaturationMap => {
                 // Original color rendering
-                let radius = self.config.circle_diameter / 2.0;
                 
                 // Cache color strings to avoid repeated allocations
-                let mut color_cache: HashMap<(u8, u8, u8), String> = HashMap::new();
+                let mut color_cache = ColorCache::default();
+                let mut merger = ColorMerger::new(self.config.color_merge_tolerance);
                 
-                for pixel in pixels {
-                    let color_key = (pixel.color[0], pixel.color[1], pixel.color[2]);
+                let gradient_fills = if self.config.gradient_dots {
+                    let (defs, fills) = Self::gradient_fills(pixels);
+                    document = document.add(defs);
+                    Some(fills)
+                } else {
+                    None
+                };
+                
+                let mut circles = Vec::with_capacity(pixels.len());
+                for (i, pixel) in pixels.iter().enumerate() {
+                    let color_key = merger.merge((pixel.color[0], pixel.color[1], pixel.color[2]));
                     
                     // Get or create the color string
-                    let color = color_cache.entry(color_key)
-                        .or_insert_with(|| {
-                            format!("rgb({},{},{})", color_key.0, color_key.1, color_key.2)
-                        });
+                    let color = match &gradient_fills {
+                        Some(fills) => fills[i].as_str(),
+                        None => color_cache.fill(color_key),
+                    };
                     
                     let opacity = pixel.color[3] as f32 / 255.0;
                     
                     let circle = Circle::new()
-                        .set("cx", pixel.x)
-                        .set("cy", pixel.y)
-                        .set("r", radius)
-                        .set("fill", color.as_str())
-                        .set("fill-opacity", opacity);
+                        .set("cx", self.num(pixel.x))
+                        .set("cy", self.num(pixel.y))
+                        .set("r", self.num(self.color_radius(pixel)));
+                    let circle = self.paint(circle, color, Some(opacity));
+                    
+                    circles.push((i, circle));
+                }
+                document = self.add_dots(document, circles, &rows);
+            }
+            RenderMode::Voronoi => {
+                let bounds = (
+                    view_x,
+                    view_y,
+                    view_x + width,
+                    view_y + height,
+                );
+                document = self.add_voronoi_cells(document, pixels, bounds);
+            }
+            RenderMode::MorphShape => {
+                // Corner radius runs from zero (square) for black to half the
+                // side (circle) for white
+                let mut color_cache = ColorCache::default();
+                for pixel in pixels {
+                    let half = pixel.dot_size / 2.0;
+                    let radius = self.num(half * pixel.brightness.clamp(0.0, 1.0));
+                    let rect = Rectangle::new()
+                        .set("x", self.num(pixel.x - half))
+                        .set("y", self.num(pixel.y - half))
+                        .set("width", self.num(pixel.dot_size))
+                        .set("height", self.num(pixel.dot_size))
+                        .set("rx", radius.as_str())
+                        .set("ry", radius);
+                    let color = color_cache.fill((pixel.color[0], pixel.color[1], pixel.color[2]));
+                    document = document.add(self.paint(rect, color, Some(pixel.color[3] as f32 / 255.0)));
+                }
+            }
+            RenderMode::PointCloud => {
+                // Centers only: no fill or other styling, to keep the file minimal
+                let radius = self.num(POINT_MARKER_RADIUS * unit);
+                for pixel in pixels {
+                    let marker = Circle::new()
+                        .set("cx", self.num(pixel.x))
+                        .set("cy", self.num(pixel.y))
+                        .set("r", radius.as_str());
+                    document = document.add(marker);
+                }
+            }
+            RenderMode::ColorByNumber => {
+                let bounds = (view_x, view_y, view_x + width, view_y + height - legend_height);
+                document = self.add_numbered_cells(document, pixels, &palette, bounds);
+                document = document.add(self.legend(&palette, view_x, view_y + height - legend_height, width));
+            }
+            RenderMode::Contours { levels } => {
+                document = self.add_contours(document, pixels, *levels);
+            }
+            RenderMode::FmHalftone { dot_size } | RenderMode::Stipple { dot_size, .. } => {
+                let cell = self.config.get_total_spacing();
+                let curve = &self.config.halftone_curve;
+                let dots = match self.config.render_mode {
+                    RenderMode::Stipple { seed, .. } => {
+                        let mut rng = crate::rng::feature_rng(self.config.seed, seed, crate::rng::STIPPLE_STREAM);
+                        crate::dither::stipple(pixels, cell, *dot_size, curve, &mut rng)
+                    }
+                    _ => crate::dither::fm_screen(pixels, cell, *dot_size, curve),
+                };
+                
+                for (x, y) in dots {
+                    let circle = Circle::new()
+                        .set("cx", self.num(x))
+                        .set("cy", self.num(y))
+                        .set("r", self.num(dot_size / 2.0))
+                        .set("fill", "black");
                     
                     document = document.add(circle);
                 }
             }
-            RenderMode::Halftone(style) => {
-                // Halftone rendering with variable dot sizes
-                let dot_color = match style {
-                    HalftoneStyle::BlackOnWhite => "black",
-                    HalftoneStyle::WhiteOnBlack => "white",
+            RenderMode::LineScreen { .. } | RenderMode::Hatch(_) => {
+                let cell = self.config.get_total_spacing();
+                let curve = &self.config.halftone_curve;
+                let segments = match self.config.render_mode {
+                    RenderMode::Hatch(orientation) => crate::hatch::hatch(pixels, cell, orientation, curve),
+                    RenderMode::LineScreen { angle, frequency } => {
+                        let bounds = (view_x, view_y, view_x + width, view_y + height);
+                        crate::line_screen::line_screen(pixels, bounds, cell, angle, frequency, curve)
+                    }
+                    _ => Vec::new(),
                 };
                 
-                for pixel in pixels {
+                for segment in segments {
+                    let line = Line::new()
+                        .set("x1", self.num(segment.x1))
+                        .set("y1", self.num(segment.y1))
+                        .set("x2", self.num(segment.x2))
+                        .set("y2", self.num(segment.y2))
+                        .set("stroke", "black")
+                        .set("stroke-width", self.num(segment.width));
+                    
+                    document = document.add(line);
+                }
+            }
+            RenderMode::Halftone(_) | RenderMode::Threshold { .. } => {
+                // Halftone rendering with variable dot sizes; threshold dots
+                // are either full size or empty
+                let dot_color = match self.config.render_mode {
+                    RenderMode::Halftone(HalftoneStyle::WhiteOnBlack) => "white",
+                    _ => "black",
+                };
+                
+                let mut circles = Vec::with_capacity(pixels.len());
+                for (i, pixel) in pixels.iter().enumerate() {
                     // Skip very small dots (essentially white/transparent areas)
                     if pixel.dot_size < 0.5 {
                         continue;
@@ -103,16 +653,1093 @@ impl<'a> SvgGenerator<'a> {
                     let radius = pixel.dot_size / 2.0;
                     
                     let circle = Circle::new()
-                        .set("cx", pixel.x)
-                        .set("cy", pixel.y)
-                        .set("r", radius)
-                        .set("fill", dot_color);
+                        .set("cx", self.num(pixel.x))
+                        .set("cy", self.num(pixel.y))
+                        .set("r", self.num(radius));
                     
-                    document = document.add(circle);
+                    circles.push((i, self.paint(circle, dot_color, None)));
+                }
+                document = self.add_dots(document, circles, &rows);
+            }
+        }
+        
+        let glows = matches!(
+            self.config.render_mode,
+            RenderMode::Color | RenderMode::SaturationMap | RenderMode::Halftone(HalftoneStyle::WhiteOnBlack)
+        );
+        if let (Some(radius), true) = (self.config.glow, glows) {
+            // A single filter on the whole dot group keeps rendering cheap
+            let content: Vec<_> = document.get_children_mut().drain(content_start..).collect();
+            let group = content.into_iter().fold(
+                Group::new().set("class", "glow").set("filter", format!("url(#{})", GLOW_FILTER_ID)),
+                |group, node| group.add(node),
+            );
+            document = document.add(Definitions::new().add(self.glow_filter(radius))).add(group);
+        }
+        
+        if self.config.svg_y_up {
+            let content: Vec<_> = document.get_children_mut().drain(content_start..).collect();
+            let wrapper = content.into_iter().fold(
+                Group::new().set("class", "y-up").set("transform", format!("translate(0,{}) scale(1,-1)", self.num(flip))),
+                |group, node| group.add(node),
+            );
+            document = document.add(wrapper);
+        }
+        
+        if self.config.registration_marks {
+            for mark in self.registration_marks(view_box, margin_x, margin_y) {
+                document = document.add(mark);
+            }
+        }
+        
+        if let Some(length) = self.config.scale_bar_mm {
+            // Draw in mm units anchored at the image's top-left corner
+            let transform = format!(
+                "translate({},{}) scale({},{})",
+                self.num(view_x),
+                self.num(view_y),
+                self.num(width / svg_width),
+                self.num(height / svg_height)
+            );
+            document = document.add(self.scale_bar(length, svg_height, margin_mm_y).set("transform", transform));
+        }
+        
+        Ok(document)
+    }
+    
+    /// Places a tinted copy of the tile sprite over every dot's cell
+    /// 
+    /// The sprite is defined once and instanced with `<use>`; each distinct
+    /// color gets one `feColorMatrix` filter that maps the sprite's luminance
+    /// onto the color, keeping its alpha.
+    fn add_tiles(&self, mut document: Document, pixels: &[PixelData]) -> Document {
+        let Some(path) = &self.config.tile_image else {
+            return document;
+        };
+        let size = self.config.circle_diameter;
+        
+        let sprite = Image::new()
+            .set("id", TILE_SPRITE_ID)
+            .set("href", path.to_string_lossy().into_owned())
+            .set("width", self.num(size))
+            .set("height", self.num(size))
+            .set("preserveAspectRatio", "xMidYMid slice");
+        let mut defs = Definitions::new().add(sprite);
+        
+        // Keep filters in first-seen color order so the output is deterministic
+        let mut filters: HashMap<(u8, u8, u8), String> = HashMap::new();
+        let mut tiles = Vec::with_capacity(pixels.len());
+        for pixel in pixels {
+            let key = (pixel.color[0], pixel.color[1], pixel.color[2]);
+            if !filters.contains_key(&key) {
+                let id = format!("tint{}", filters.len());
+                defs = defs.add(self.tint_filter(&id, key));
+                filters.insert(key, id);
+            }
+            let id = &filters[&key];
+            
+            let tile = Use::new()
+                .set("href", format!("#{}", TILE_SPRITE_ID))
+                .set("x", self.num(pixel.x - size / 2.0))
+                .set("y", self.num(pixel.y - size / 2.0))
+                .set("filter", format!("url(#{})", id))
+                .set("opacity", self.num(pixel.color[3] as f32 / 255.0));
+            tiles.push(tile);
+        }
+        
+        document = document.add(defs);
+        for tile in tiles {
+            document = document.add(tile);
+        }
+        document
+    }
+    
+    /// Builds a filter that tints its input by mapping luminance onto `color`
+    fn tint_filter(&self, id: &str, color: (u8, u8, u8)) -> Filter {
+        use svg::Node;
+        
+        let rows: Vec<String> = [color.0, color.1, color.2]
+            .iter()
+            .map(|&channel| {
+                let scale = channel as f32 / 255.0;
+                format!(
+                    "{} {} {} 0 0",
+                    self.num(0.2126 * scale),
+                    self.num(0.7152 * scale),
+                    self.num(0.0722 * scale)
+                )
+            })
+            .collect();
+        let mut matrix = Element::new("feColorMatrix");
+        matrix.assign("type", "matrix");
+        matrix.assign("values", format!("{} 0 0 0 1 0", rows.join(" ")));
+        
+        Filter::new()
+            .set("id", id)
+            .set("color-interpolation-filters", "sRGB")
+            .add(matrix)
+    }
+    
+    /// Builds the background `<linearGradient>`, running through the center of
+    /// the background along the configured angle
+    fn background_gradient(&self, gradient: &BackgroundGradient) -> LinearGradient {
+        let (sin, cos) = gradient.angle.to_radians().sin_cos();
+        let mut linear = LinearGradient::new()
+            .set("id", BACKGROUND_GRADIENT_ID)
+            .set("x1", self.num(0.5 - cos / 2.0))
+            .set("y1", self.num(0.5 - sin / 2.0))
+            .set("x2", self.num(0.5 + cos / 2.0))
+            .set("y2", self.num(0.5 + sin / 2.0));
+        for (offset, color) in &gradient.stops {
+            linear = linear.add(Stop::new().set("offset", self.num(*offset)).set("stop-color", color.as_str()));
+        }
+        linear
+    }
+    
+    /// Builds the glow filter: the blurred dots merged under the sharp ones
+    fn glow_filter(&self, radius: f32) -> Filter {
+        use svg::Node;
+        
+        let mut blur = Element::new("feGaussianBlur");
+        blur.assign("in", "SourceGraphic");
+        blur.assign("stdDeviation", self.num(radius));
+        blur.assign("result", "blur");
+        let mut merge = Element::new("feMerge");
+        for input in ["blur", "SourceGraphic"] {
+            let mut node = Element::new("feMergeNode");
+            node.assign("in", input);
+            merge.append(node);
+        }
+        
+        Filter::new()
+            .set("id", GLOW_FILTER_ID)
+            .add(blur)
+            .add(merge)
+    }
+    
+    /// Adds one layer group per spot ink, holding the dots whose brightness
+    /// falls in that ink's band
+    /// 
+    /// Brightness is split into as many equal bands as there are inks, the
+    /// first ink taking the darkest band. Every ink gets its layer, even if
+    /// no dot falls in its band, so each file has the same separations.
+    fn add_spot_layers(&self, document: Document, pixels: &[PixelData]) -> Document {
+        use crate::config::RenderMode;
+        
+        let Some(inks) = &self.config.spot_color_bands else {
+            return document;
+        };
+        let halftone = matches!(self.config.render_mode, RenderMode::Halftone(_) | RenderMode::Threshold { .. });
+        let mut bands: Vec<Vec<Circle>> = vec![Vec::new(); inks.len()];
+        for pixel in pixels {
+            // Halftone dots this small are skipped, as without spot inks
+            if halftone && pixel.dot_size < 0.5 {
+                continue;
+            }
+            let radius = if halftone { pixel.dot_size / 2.0 } else { self.color_radius(pixel) };
+            let band = ((pixel.brightness.clamp(0.0, 1.0) * inks.len() as f32) as usize).min(inks.len() - 1);
+            let circle = Circle::new()
+                .set("cx", self.num(pixel.x))
+                .set("cy", self.num(pixel.y))
+                .set("r", self.num(radius));
+            bands[band].push(self.paint(circle, &inks[band], None));
+        }
+        
+        let mut document = document.set("xmlns:inkscape", INKSCAPE_NAMESPACE);
+        for (k, (ink, circles)) in inks.iter().zip(bands).enumerate() {
+            let layer = Group::new()
+                .set("id", format!("spot-{}", k + 1))
+                .set("class", "spot-layer")
+                .set("inkscape:groupmode", "layer")
+                .set("inkscape:label", format!("Ink {} ({})", k + 1, ink));
+            document = document.add(circles.into_iter().fold(layer, |layer, circle| layer.add(circle)));
+        }
+        document
+    }
+    
+    /// Adds one `<path>` per distinct color, drawing every dot of that color as
+    /// a subpath made of two half-circle arcs
+    fn add_color_paths(&self, mut document: Document, pixels: &[PixelData]) -> Document {
+        // Keep colors in first-seen order so the output is deterministic
+        let mut index: HashMap<(u8, u8, u8, u8), usize> = HashMap::new();
+        let mut paths: Vec<((u8, u8, u8, u8), String)> = Vec::new();
+        let mut merger = ColorMerger::new(self.config.color_merge_tolerance);
+        
+        for pixel in pixels {
+            let (r, g, b) = merger.merge((pixel.color[0], pixel.color[1], pixel.color[2]));
+            let key = (r, g, b, pixel.color[3]);
+            let slot = *index.entry(key).or_insert_with(|| {
+                paths.push((key, String::new()));
+                paths.len() - 1
+            });
+            
+            let radius = self.color_radius(pixel);
+            let diameter = self.num(radius * 2.0);
+            paths[slot].1.push_str(&format!(
+                "M{},{}a{r},{r} 0 1 0 {d},0a{r},{r} 0 1 0 -{d},0z",
+                self.num(pixel.x - radius),
+                self.num(pixel.y),
+                r = self.num(radius),
+                d = diameter
+            ));
+        }
+        
+        for (key, data) in paths {
+            let path = Path::new().set("d", data);
+            let color = format!("rgb({},{},{})", key.0, key.1, key.2);
+            document = document.add(self.paint(path, &color, Some(key.3 as f32 / 255.0)));
+        }
+        
+        document
+    }
+    
+    /// Builds radial gradients from each dot's color to the average color of its
+    /// grid neighbours, returning the gradient defs and each dot's fill reference
+    /// 
+    /// Gradients are shared between dots with the same color pair. Dots without
+    /// a grid position, or without neighbours, reference their own color only.
+    fn gradient_fills(pixels: &[PixelData]) -> (Definitions, Vec<String>) {
+        let positions: HashMap<(usize, usize), usize> = pixels
+            .iter()
+            .enumerate()
+            .filter_map(|(i, p)| p.grid_position.map(|pos| (pos, i)))
+            .collect();
+        
+        let mut defs = Definitions::new();
+        let mut gradient_ids: HashMap<([u8; 3], [u8; 3]), String> = HashMap::new();
+        
+        let fills = pixels
+            .iter()
+            .map(|pixel| {
+                let own = [pixel.color[0], pixel.color[1], pixel.color[2]];
+                let neighbours: Vec<&PixelData> = pixel
+                    .grid_position
+                    .map(|(col, row)| {
+                        [(col.wrapping_sub(1), row), (col + 1, row), (col, row.wrapping_sub(1)), (col, row + 1)]
+                            .iter()
+                            .filter_map(|pos| positions.get(pos).map(|&j| &pixels[j]))
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                
+                if neighbours.is_empty() {
+                    return format!("rgb({},{},{})", own[0], own[1], own[2]);
+                }
+                
+                let mut sums = [0u32; 3];
+                for neighbour in &neighbours {
+                    for (sum, channel) in sums.iter_mut().zip(neighbour.color.0.iter()) {
+                        *sum += *channel as u32;
+                    }
+                }
+                let edge = sums.map(|sum| (sum / neighbours.len() as u32) as u8);
+                
+                let next_id = gradient_ids.len();
+                let id = gradient_ids.entry((own, edge)).or_insert_with(|| {
+                    let id = format!("g{}", next_id);
+                    let gradient = RadialGradient::new()
+                        .set("id", id.as_str())
+                        .add(Stop::new()
+                            .set("offset", 0)
+                            .set("stop-color", format!("rgb({},{},{})", own[0], own[1], own[2])))
+                        .add(Stop::new()
+                            .set("offset", 1)
+                            .set("stop-color", format!("rgb({},{},{})", edge[0], edge[1], edge[2])));
+                    defs = std::mem::take(&mut defs).add(gradient);
+                    id
+                });
+                format!("url(#{})", id)
+            })
+            .collect();
+        
+        (defs, fills)
+    }
+    
+    /// Builds the `<pattern>` def for a background texture with square tiles of side `tile`
+    fn background_pattern(&self, kind: PatternKind, tile: f32) -> Pattern {
+        let stroke = tile / 16.0;
+        let pattern = Pattern::new()
+            .set("id", BACKGROUND_PATTERN_ID)
+            .set("patternUnits", "userSpaceOnUse")
+            .set("width", self.num(tile))
+            .set("height", self.num(tile));
+        
+        match kind {
+            PatternKind::Dots => pattern.add(
+                Circle::new()
+                    .set("cx", self.num(tile / 2.0))
+                    .set("cy", self.num(tile / 2.0))
+                    .set("r", self.num(tile / 8.0))
+                    .set("fill", BACKGROUND_PATTERN_COLOR),
+            ),
+            PatternKind::Grid => pattern.add(
+                Path::new()
+                    .set("d", format!("M{t},0L0,0L0,{t}", t = self.num(tile)))
+                    .set("fill", "none")
+                    .set("stroke", BACKGROUND_PATTERN_COLOR)
+                    .set("stroke-width", self.num(stroke)),
+            ),
+            PatternKind::Diagonal => pattern.add(
+                Line::new()
+                    .set("x1", 0)
+                    .set("y1", self.num(tile))
+                    .set("x2", self.num(tile))
+                    .set("y2", 0)
+                    .set("stroke", BACKGROUND_PATTERN_COLOR)
+                    .set("stroke-width", self.num(stroke)),
+            ),
+        }
+    }
+    
+    /// Adds one filled `<polygon>` per pixel covering its Voronoi cell
+    fn add_voronoi_cells(&self, mut document: Document, pixels: &[PixelData], bounds: voronoi::Bounds) -> Document {
+        let sites: Vec<(f32, f32)> = pixels.iter().map(|p| (p.x, p.y)).collect();
+        let cells = voronoi::voronoi_cells(&sites, bounds);
+        
+        for (pixel, cell) in pixels.iter().zip(cells) {
+            if cell.len() < 3 {
+                continue;
+            }
+            
+            let points = cell
+                .iter()
+                .map(|&(x, y)| format!("{},{}", self.num(x), self.num(y)))
+                .collect::<Vec<String>>()
+                .join(" ");
+            let polygon = Polygon::new()
+                .set("points", points)
+                .set("fill", format!("rgb({},{},{})", pixel.color[0], pixel.color[1], pixel.color[2]))
+                .set("fill-opacity", self.num(pixel.color[3] as f32 / 255.0));
+            document = document.add(polygon);
+        }
+        
+        document
+    }
+    
+    /// Collects the distinct RGB colors in first-seen order; a color's
+    /// worksheet number is its index plus one
+    fn number_palette(pixels: &[PixelData]) -> Vec<(u8, u8, u8)> {
+        let mut palette: Vec<(u8, u8, u8)> = Vec::new();
+        for pixel in pixels {
+            let key = (pixel.color[0], pixel.color[1], pixel.color[2]);
+            if !palette.contains(&key) {
+                palette.push(key);
+            }
+        }
+        palette
+    }
+    
+    /// Height in viewBox units of a legend with `entries` colors laid out in
+    /// rows across `width`; zero when there is nothing to list
+    fn legend_height(&self, entries: usize, width: f32) -> f32 {
+        if entries == 0 {
+            return 0.0;
+        }
+        let cell = self.config.get_total_spacing();
+        let per_row = ((width / (cell * LEGEND_ENTRY_CELLS)).floor() as usize).max(1);
+        // Half a row of padding separates the legend from the image
+        (entries.div_ceil(per_row) as f32 + 0.5) * cell * LEGEND_ROW_CELLS
+    }
+    
+    /// Adds one outlined, unfilled `<polygon>` per pixel covering its Voronoi
+    /// cell, labeled at the sample point with its color's palette number
+    fn add_numbered_cells(
+        &self,
+        document: Document,
+        pixels: &[PixelData],
+        palette: &[(u8, u8, u8)],
+        bounds: voronoi::Bounds,
+    ) -> Document {
+        let cell_size = self.config.get_total_spacing();
+        let sites: Vec<(f32, f32)> = pixels.iter().map(|p| (p.x, p.y)).collect();
+        let cells = voronoi::voronoi_cells(&sites, bounds);
+        
+        let mut shapes = Group::new()
+            .set("class", "cells")
+            .set("fill", "none")
+            .set("stroke", "black")
+            .set("stroke-width", self.num(cell_size * OUTLINE_WIDTH_CELLS));
+        let mut labels = Group::new()
+            .set("class", "labels")
+            .set("font-size", self.num(cell_size * LABEL_FONT_CELLS))
+            .set("font-family", "sans-serif")
+            .set("text-anchor", "middle")
+            .set("dominant-baseline", "central");
+        
+        for (pixel, cell) in pixels.iter().zip(cells) {
+            if cell.len() < 3 {
+                continue;
+            }
+            
+            let points = cell
+                .iter()
+                .map(|&(x, y)| format!("{},{}", self.num(x), self.num(y)))
+                .collect::<Vec<String>>()
+                .join(" ");
+            shapes = shapes.add(Polygon::new().set("points", points));
+            
+            let key = (pixel.color[0], pixel.color[1], pixel.color[2]);
+            let number = palette.iter().position(|&c| c == key).map_or(0, |i| i + 1);
+            labels = labels.add(
+                Text::new()
+                    .add(svg::node::Text::new(number.to_string()))
+                    .set("x", self.num(pixel.x))
+                    .set("y", self.num(pixel.y)),
+            );
+        }
+        
+        document.add(shapes).add(labels)
+    }
+    
+    /// Adds one group per contour level, lightest first so darker layers sit
+    /// on top, each holding a single even-odd filled path in the level's gray
+    fn add_contours(&self, mut document: Document, pixels: &[PixelData], levels: u8) -> Document {
+        let cell_size = self.config.get_total_spacing();
+        let rings = crate::contour::contour_rings(pixels, levels, cell_size);
+        
+        for (level, rings) in rings.iter().enumerate() {
+            if rings.is_empty() {
+                continue;
+            }
+            
+            let data = rings
+                .iter()
+                .map(|ring| {
+                    let points = ring
+                        .iter()
+                        .map(|&(x, y)| format!("{},{}", self.num(x), self.num(y)))
+                        .collect::<Vec<String>>()
+                        .join(" L");
+                    format!("M{} Z", points)
+                })
+                .collect::<Vec<String>>()
+                .join(" ");
+            
+            // Each layer is shaded with the middle darkness of its band
+            let gray = ((1.0 - (level as f32 + 0.5) / levels as f32) * 255.0).round() as u8;
+            let path = Path::new()
+                .set("d", data)
+                .set("fill", format!("#{:02x}{:02x}{:02x}", gray, gray, gray))
+                .set("fill-rule", "evenodd")
+                .set("stroke", "black")
+                .set("stroke-width", self.num(cell_size * OUTLINE_WIDTH_CELLS));
+            document = document.add(
+                Group::new()
+                    .set("class", "contour-level")
+                    .set("data-level", level)
+                    .add(path),
+            );
+        }
+        
+        document
+    }
+    
+    /// Builds the worksheet legend: a color swatch and its number for each
+    /// palette entry, in rows starting at `top`
+    fn legend(&self, palette: &[(u8, u8, u8)], left: f32, top: f32, width: f32) -> Group {
+        let cell = self.config.get_total_spacing();
+        let row = cell * LEGEND_ROW_CELLS;
+        let entry = cell * LEGEND_ENTRY_CELLS;
+        let per_row = ((width / entry).floor() as usize).max(1);
+        let swatch = cell;
+        
+        let mut legend = Group::new()
+            .set("class", "legend")
+            .set("font-size", self.num(cell * 0.8))
+            .set("font-family", "sans-serif");
+        
+        for (i, &(r, g, b)) in palette.iter().enumerate() {
+            let x = left + (i % per_row) as f32 * entry + cell * 0.25;
+            let y = top + row * (0.5 + (i / per_row) as f32) + (row - swatch) / 2.0;
+            
+            let rect = Rectangle::new()
+                .set("x", self.num(x))
+                .set("y", self.num(y))
+                .set("width", self.num(swatch))
+                .set("height", self.num(swatch))
+                .set("fill", format!("rgb({},{},{})", r, g, b))
+                .set("stroke", "black")
+                .set("stroke-width", self.num(cell * OUTLINE_WIDTH_CELLS));
+            let label = Text::new()
+                .add(svg::node::Text::new((i + 1).to_string()))
+                .set("x", self.num(x + swatch * 1.25))
+                .set("y", self.num(y + swatch / 2.0))
+                .set("dominant-baseline", "central");
+            legend = legend.add(rect).add(label);
+        }
+        
+        legend
+    }
+    
+    /// Formats a coordinate or length at this generator's precision
+    fn num(&self, value: f32) -> String {
+        format_number(value, self.decimals)
+    }
+    
+    /// Returns the top-left corner of the viewBox for the configured origin
+    fn view_box_origin(&self, width: f32, height: f32) -> (f32, f32) {
+        match self.config.origin {
+            Origin::TopLeft => (0.0, 0.0),
+            Origin::Center => (width / -2.0, height / -2.0),
+            Origin::BottomLeft => (0.0, -height),
+        }
+    }
+    
+    /// Returns a copy of the configuration with all pixel lengths multiplied by `unit`
+    fn scale_config(config: &PixelatorConfig, unit: f32) -> PixelatorConfig {
+        let mut scaled = config.scale_pixel_lengths(unit);
+        scaled.normalized_coordinates = false;
+        scaled
+    }
+    
+    /// Fills a dot shape with `color`, or strokes its outline in outline-only mode
+    fn paint<T: svg::Node>(&self, mut shape: T, color: &str, opacity: Option<f32>) -> T {
+        let (paint, opacity_attr) = match self.config.outline_only {
+            Some(width) => {
+                shape.assign("fill", "none");
+                shape.assign("stroke-width", self.num(width));
+                ("stroke", "stroke-opacity")
+            }
+            None => ("fill", "fill-opacity"),
+        };
+        shape.assign(paint, color);
+        if let Some(opacity) = opacity {
+            shape.assign(opacity_attr, self.num(opacity));
+        }
+        shape
+    }
+    
+    /// Radius drawn for a color dot, raised to the configured visible minimum
+    fn color_radius(&self, pixel: &PixelData) -> f32 {
+        (pixel.dot_size / 2.0).max(self.config.min_visible_radius.unwrap_or(0.0))
+    }
+    
+    /// Rounds dot centers to the configured snap grid, given the output
+    /// millimeters per viewBox unit on each axis
+    fn snap_pixels<'p>(&self, pixels: Cow<'p, [PixelData]>, mm_x: f32, mm_y: f32) -> Cow<'p, [PixelData]> {
+        if self.config.snap_to_grid_mm.is_none() {
+            return pixels;
+        }
+        Cow::Owned(
+            pixels
+                .iter()
+                .map(|p| PixelData {
+                    x: self.config.snap_mm(p.x * mm_x) / mm_x,
+                    y: self.config.snap_mm(p.y * mm_y) / mm_y,
+                    ..p.clone()
+                })
+                .collect(),
+        )
+    }
+    
+    /// Translates pixel positions by the given offset
+    fn shift_pixels(pixels: &[PixelData], dx: f32, dy: f32) -> Cow<'_, [PixelData]> {
+        if dx == 0.0 && dy == 0.0 {
+            return Cow::Borrowed(pixels);
+        }
+        
+        Cow::Owned(
+            pixels
+                .iter()
+                .map(|p| PixelData { x: p.x + dx, y: p.y + dy, ..p.clone() })
+                .collect(),
+        )
+    }
+    
+    /// Sorts pixels by brightness for the configured paint order
+    /// The sort is stable, so equally bright dots keep their sampling order
+    fn order_pixels<'p>(&self, pixels: Cow<'p, [PixelData]>) -> Cow<'p, [PixelData]> {
+        let descending = match self.config.paint_order {
+            PaintOrder::AsSampled => return pixels,
+            PaintOrder::DarkestLast => true,
+            PaintOrder::LightestLast => false,
+        };
+        
+        let mut sorted = pixels.into_owned();
+        sorted.sort_by(|a, b| {
+            let order = a.brightness.total_cmp(&b.brightness);
+            if descending { order.reverse() } else { order }
+        });
+        Cow::Owned(sorted)
+    }
+    
+    /// Reorders pixels into boustrophedon scanlines when scanline grouping is on
+    /// 
+    /// Returns the reordered pixels and the scanline index of each, top row
+    /// first; the index list is empty when grouping is disabled. Rows are
+    /// taken from grid positions when known, otherwise from equal `y` values.
+    fn scanline_order<'p>(&self, pixels: Cow<'p, [PixelData]>) -> (Cow<'p, [PixelData]>, Vec<usize>) {
+        if !self.config.scanline_grouping {
+            return (pixels, Vec::new());
+        }
+        
+        let mut rows: Vec<(f32, Vec<PixelData>)> = Vec::new();
+        let mut index: HashMap<u32, usize> = HashMap::new();
+        for pixel in pixels.iter() {
+            let key = pixel.grid_position.map_or(pixel.y, |(_, row)| row as f32);
+            let slot = *index.entry(key.to_bits()).or_insert_with(|| {
+                rows.push((key, Vec::new()));
+                rows.len() - 1
+            });
+            rows[slot].1.push(pixel.clone());
+        }
+        rows.sort_by(|a, b| a.0.total_cmp(&b.0));
+        
+        let mut ordered = Vec::with_capacity(pixels.len());
+        let mut row_ids = Vec::with_capacity(pixels.len());
+        for (row, (_, mut dots)) in rows.into_iter().enumerate() {
+            dots.sort_by(|a, b| a.x.total_cmp(&b.x));
+            if row % 2 == 1 {
+                dots.reverse();
+            }
+            row_ids.extend(std::iter::repeat(row).take(dots.len()));
+            ordered.extend(dots);
+        }
+        (Cow::Owned(ordered), row_ids)
+    }
+    
+    /// Adds dot circles to the document, wrapped in one `<g class="scanline">`
+    /// per row when `rows` holds the scanline index of each pixel
+    fn add_dots(&self, mut document: Document, circles: Vec<(usize, Circle)>, rows: &[usize]) -> Document {
+        let circles = match self.config.reveal_animation {
+            Some(seconds) => self.reveal(circles, seconds),
+            None => circles,
+        };
+        if rows.is_empty() {
+            for (_, circle) in circles {
+                document = document.add(circle);
+            }
+            return document;
+        }
+        
+        let mut current: Option<(usize, Group)> = None;
+        for (i, circle) in circles {
+            current = match current {
+                Some((row, group)) if row == rows[i] => Some((row, group.add(circle))),
+                previous => {
+                    if let Some((_, group)) = previous {
+                        document = document.add(group);
+                    }
+                    Some((rows[i], Group::new().set("class", "scanline").add(circle)))
+                }
+            };
+        }
+        if let Some((_, group)) = current {
+            document = document.add(group);
+        }
+        document
+    }
+    
+    /// Grows dots in one after another, in emission order, over `seconds`
+    /// 
+    /// Each dot keeps its radius at zero until its turn, then animates up to
+    /// its full radius. The animations leave the static radius in place, so
+    /// viewers without SMIL support show the finished image. Above
+    /// `REVEAL_MAX_DOTS` the dots are left static and a warning is recorded.
+    fn reveal(&self, circles: Vec<(usize, Circle)>, seconds: f32) -> Vec<(usize, Circle)> {
+        if circles.len() > REVEAL_MAX_DOTS {
+            self.warn([format!(
+                "reveal animation skipped for {} dots (limit {}); raise the spacing to animate",
+                circles.len(),
+                REVEAL_MAX_DOTS
+            )]);
+            return circles;
+        }
+        
+        let grow = seconds * REVEAL_GROW_FRACTION;
+        let step = (seconds - grow) / circles.len().saturating_sub(1).max(1) as f32;
+        circles
+            .into_iter()
+            .enumerate()
+            .map(|(k, (i, circle))| {
+                let radius = circle.get_attributes().get("r").map(|r| r.to_string()).unwrap_or_default();
+                let begin = k as f32 * step;
+                let mut circle = circle;
+                if begin > 0.0 {
+                    // Hidden until this dot's turn
+                    circle = circle.add(
+                        Animate::new()
+                            .set("attributeName", "r")
+                            .set("values", "0;0")
+                            .set("begin", "0s")
+                            .set("dur", format!("{}s", format_number(begin, DEFAULT_DECIMALS))),
+                    );
                 }
+                let circle = circle.add(
+                    Animate::new()
+                        .set("attributeName", "r")
+                        .set("values", format!("0;{}", radius))
+                        .set("begin", format!("{}s", format_number(begin, DEFAULT_DECIMALS)))
+                        .set("dur", format!("{}s", format_number(grow, DEFAULT_DECIMALS))),
+                );
+                (i, circle)
+            })
+            .collect()
+    }
+    
+    /// Keeps each pixel's hue and lightness but sets its saturation from brightness,
+    /// so dark areas become muted and bright areas vivid
+    fn map_saturation(pixels: &[PixelData]) -> Vec<PixelData> {
+        pixels
+            .iter()
+            .map(|p| {
+                let [hue, _, lightness] = color::rgb_to_hsl(&p.color);
+                let color = color::hsl_to_rgb([hue, p.brightness, lightness], p.color[3]);
+                PixelData { color, ..p.clone() }
+            })
+            .collect()
+    }
+    
+    /// Builds guide lines along the cell borders every `every` columns and rows
+    /// 
+    /// Cell borders lie halfway between neighboring dot centers and half a
+    /// step outside the first and last dots.
+    fn guide_grid(&self, pixels: &[PixelData], every: usize, color: &str) -> Result<Group> {
+        use crate::config::SampleMode;
+        
+        if !matches!(self.config.sample_mode, SampleMode::Grid) {
+            return Err(PixelatorError::InvalidConfig(
+                "Guide grid requires grid sampling mode".to_string(),
+            ));
+        }
+        
+        // First and last dot center and the count along each axis
+        let mut x_range = (f32::MAX, f32::MIN, 0);
+        let mut y_range = (f32::MAX, f32::MIN, 0);
+        for pixel in pixels {
+            let Some((col, row)) = pixel.grid_position else {
+                continue;
+            };
+            x_range = (x_range.0.min(pixel.x), x_range.1.max(pixel.x), x_range.2.max(col + 1));
+            y_range = (y_range.0.min(pixel.y), y_range.1.max(pixel.y), y_range.2.max(row + 1));
+        }
+        
+        let pitch = self.config.get_total_spacing();
+        let borders = |(first, last, count): (f32, f32, usize)| -> Vec<f32> {
+            if count == 0 {
+                return Vec::new();
+            }
+            let step = if count > 1 { (last - first) / (count - 1) as f32 } else { pitch };
+            (0..=count).step_by(every).map(|k| first - step / 2.0 + k as f32 * step).collect()
+        };
+        let (xs, ys) = (borders(x_range), borders(y_range));
+        
+        let mut group = Group::new()
+            .set("class", "guide-grid")
+            .set("stroke", color)
+            .set("stroke-width", self.num(pitch * GUIDE_LINE_WIDTH_CELLS));
+        if let (Some(&left), Some(&top)) = (xs.first(), ys.first()) {
+            let right = x_range.1 + (x_range.0 - left);
+            let bottom = y_range.1 + (y_range.0 - top);
+            for &x in &xs {
+                group = group.add(
+                    Line::new()
+                        .set("x1", self.num(x))
+                        .set("y1", self.num(top))
+                        .set("x2", self.num(x))
+                        .set("y2", self.num(bottom)),
+                );
+            }
+            for &y in &ys {
+                group = group.add(
+                    Line::new()
+                        .set("x1", self.num(left))
+                        .set("y1", self.num(y))
+                        .set("x2", self.num(right))
+                        .set("y2", self.num(y)),
+                );
             }
         }
+        Ok(group)
+    }
+    
+    /// Builds `+` crosshairs at the four corners of the viewBox
+    /// 
+    /// Each mark is centered in the corner of the margin when the margin is
+    /// wide enough to hold it, and otherwise tucked into the corner of the
+    /// viewBox over the dot field.
+    fn registration_marks(&self, view_box: (f32, f32, f32, f32), margin_x: f32, margin_y: f32) -> Vec<Group> {
+        let size = self.config.get_total_spacing() * REGISTRATION_MARK_CELLS;
+        let inset = |margin: f32| if margin >= size { margin / 2.0 } else { size / 2.0 };
+        let (inset_x, inset_y) = (inset(margin_x), inset(margin_y));
+        let (left, top) = (view_box.0 + inset_x, view_box.1 + inset_y);
+        let (right, bottom) = (view_box.0 + view_box.2 - inset_x, view_box.1 + view_box.3 - inset_y);
+        
+        let arm = size / 2.0;
+        [(left, top), (right, top), (left, bottom), (right, bottom)]
+            .into_iter()
+            .map(|(x, y)| {
+                Group::new()
+                    .set("class", "registration-mark")
+                    .set("stroke", "black")
+                    .set("stroke-width", self.num(size * GUIDE_LINE_WIDTH_CELLS))
+                    .add(
+                        Line::new()
+                            .set("x1", self.num(x - arm))
+                            .set("y1", self.num(y))
+                            .set("x2", self.num(x + arm))
+                            .set("y2", self.num(y)),
+                    )
+                    .add(
+                        Line::new()
+                            .set("x1", self.num(x))
+                            .set("y1", self.num(y - arm))
+                            .set("x2", self.num(x))
+                            .set("y2", self.num(y + arm)),
+                    )
+            })
+            .collect()
+    }
+    
+    /// Builds a labeled scale bar of the given physical length, in mm units,
+    /// placed in the bottom margin below the left edge of the dot field
+    fn scale_bar(&self, length_mm: f32, field_height_mm: f32, margin_mm: f32) -> Group {
+        let bar_y = field_height_mm + margin_mm * 0.35;
         
-        Ok(document.to_string())
+        let line = Line::new()
+            .set("x1", 0)
+            .set("y1", self.num(bar_y))
+            .set("x2", self.num(length_mm))
+            .set("y2", self.num(bar_y))
+            .set("stroke", "black")
+            .set("stroke-width", self.num(margin_mm * 0.08));
+        
+        let label = Text::new()
+            .add(svg::node::Text::new(format!("{} mm", self.num(length_mm))))
+            .set("x", self.num(length_mm / 2.0))
+            .set("y", self.num(field_height_mm + margin_mm * 0.8))
+            .set("font-size", self.num(margin_mm * 0.3))
+            .set("font-family", "sans-serif")
+            .set("text-anchor", "middle");
+        
+        Group::new()
+            .set("class", "scale-bar")
+            .add(line)
+            .add(label)
+    }
+}
+
+impl Render for SvgGenerator<'_> {
+    fn render<W: std::io::Write>(
+        &self,
+        writer: &mut W,
+        pixels: &[PixelData],
+        original_width: u32,
+        original_height: u32,
+    ) -> Result<()> {
+        let document = self.build_document(pixels, original_width, original_height)?;
+        svg::write(writer, &document)?;
+        Ok(())
+    }
+}
+
+/// Fill strings for RGB colors, indexed directly by the color packed into
+/// 24 bits
+/// 
+/// A two-level table keeps hashing out of the per-dot loop of large color
+/// images: the high bits of the color pick a page, allocated on first use,
+/// and the low bits a slot within it. Images with few colors only touch a
+/// few pages. Grays (equal channels, as every dot of a grayscale input has)
+/// skip the table and are looked up directly by level.
+#[derive(Default)]
+pub(crate) struct ColorCache {
+    pages: Vec<Option<Box<[Option<u32>]>>>,
+    grays: Vec<Option<u32>>,
+    fills: Vec<String>,
+}
+
+impl ColorCache {
+    /// Returns the `rgb(r,g,b)` fill for a color, formatting it on first use
+    pub(crate) fn fill(&mut self, (r, g, b): (u8, u8, u8)) -> &str {
+        let fills = &mut self.fills;
+        let format = || {
+            fills.push(format!("rgb({},{},{})", r, g, b));
+            (fills.len() - 1) as u32
+        };
+        let slot = if r == g && g == b {
+            if self.grays.is_empty() {
+                self.grays = vec![None; 256];
+            }
+            *self.grays[r as usize].get_or_insert_with(format)
+        } else {
+            *Self::slot(&mut self.pages, u32::from_be_bytes([0, r, g, b])).get_or_insert_with(format)
+        };
+        &self.fills[slot as usize]
+    }
+    
+    /// Number of distinct colors formatted so far
+    #[cfg(test)]
+    pub(crate) fn colors(&self) -> usize {
+        self.fills.len()
+    }
+    
+    /// Number of non-gray colors held in the paged table
+    #[cfg(test)]
+    pub(crate) fn paged_colors(&self) -> usize {
+        self.pages.iter().flatten().map(|page| page.iter().flatten().count()).sum()
+    }
+    
+    // The table slot of a packed color, allocating its page if needed
+    fn slot(pages: &mut Vec<Option<Box<[Option<u32>]>>>, key: u32) -> &mut Option<u32> {
+        if pages.is_empty() {
+            pages.resize(1 << (24 - COLOR_PAGE_BITS), None);
+        }
+        let page = pages[(key >> COLOR_PAGE_BITS) as usize]
+            .get_or_insert_with(|| vec![None; 1 << COLOR_PAGE_BITS].into_boxed_slice());
+        &mut page[(key & ((1 << COLOR_PAGE_BITS) - 1)) as usize]
+    }
+}
+
+/// Snaps near-identical colors onto a shared representative
+/// 
+/// The first color seen starts a group; later colors within `tolerance`
+/// (Euclidean RGB distance) of an existing representative reuse it. A
+/// tolerance of zero keeps every color as-is.
+struct ColorMerger {
+    tolerance: u8,
+    representatives: Vec<(u8, u8, u8)>,
+    merged: HashMap<(u8, u8, u8), (u8, u8, u8)>,
+}
+
+impl ColorMerger {
+    fn new(tolerance: u8) -> Self {
+        Self { tolerance, representatives: Vec::new(), merged: HashMap::new() }
+    }
+    
+    fn merge(&mut self, color: (u8, u8, u8)) -> (u8, u8, u8) {
+        if self.tolerance == 0 {
+            return color;
+        }
+        if let Some(&representative) = self.merged.get(&color) {
+            return representative;
+        }
+        
+        let limit = self.tolerance as i32 * self.tolerance as i32;
+        let distance = |a: (u8, u8, u8), b: (u8, u8, u8)| {
+            let (dr, dg, db) = (a.0 as i32 - b.0 as i32, a.1 as i32 - b.1 as i32, a.2 as i32 - b.2 as i32);
+            dr * dr + dg * dg + db * db
+        };
+        let nearest = self
+            .representatives
+            .iter()
+            .copied()
+            .filter(|&r| distance(r, color) <= limit)
+            .min_by_key(|&r| distance(r, color));
+        let representative = match nearest {
+            Some(representative) => representative,
+            None => {
+                self.representatives.push(color);
+                color
+            }
+        };
+        self.merged.insert(color, representative);
+        representative
+    }
+}
+
+/// Locates the `<g>` element with the given id in SVG source
+/// 
+/// Returns the byte offset of its closing `</g>` tag, or of the `/>` that
+/// ends it when the group is self-closing, and whether it is self-closing.
+/// Tags inside comments and CDATA sections are ignored.
+fn find_group(source: &str, id: &str) -> Option<(usize, bool)> {
+    let mut tags = source_tags(source);
+    let open = tags
+        .by_ref()
+        .find(|tag| tag.name == "g" && tag.kind != TagKind::End && attribute(tag.attributes, "id") == Some(id))?;
+    if open.kind == TagKind::Empty {
+        return Some((open.end - "/>".len(), true));
+    }
+    
+    // Match the closing tag, counting nested groups
+    let mut depth = 1;
+    for tag in tags.filter(|tag| tag.name == "g") {
+        match tag.kind {
+            TagKind::Start => depth += 1,
+            TagKind::End => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some((tag.start, false));
+                }
+            }
+            TagKind::Empty => {}
+        }
+    }
+    None
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum TagKind {
+    Start,
+    End,
+    Empty,
+}
+
+/// A tag in SVG source and the byte range it spans
+struct SourceTag<'s> {
+    name: &'s str,
+    attributes: &'s str,
+    kind: TagKind,
+    start: usize,
+    end: usize,
+}
+
+/// Iterates over the element tags of SVG source in document order
+/// 
+/// Comments, CDATA sections, processing instructions and declarations are
+/// skipped whole, and a `>` inside a quoted attribute value doesn't end its
+/// tag.
+fn source_tags(source: &str) -> impl Iterator<Item = SourceTag<'_>> {
+    const SKIPPED: [(&str, &str); 4] = [("<!--", "-->"), ("<![CDATA[", "]]>"), ("<?", "?>"), ("<!", ">")];
+    
+    let mut position = 0;
+    std::iter::from_fn(move || loop {
+        let start = position + source[position..].find('<')?;
+        let rest = &source[start..];
+        if let Some((open, close)) = SKIPPED.iter().find(|(open, _)| rest.starts_with(open)) {
+            position = start + open.len() + rest[open.len()..].find(close)? + close.len();
+            continue;
+        }
+        
+        let mut quote = None;
+        let length = rest.char_indices().find_map(|(i, c)| {
+            match (quote, c) {
+                (None, '>') => return Some(i + 1),
+                (None, '"' | '\'') => quote = Some(c),
+                (Some(open), _) if c == open => quote = None,
+                _ => {}
+            }
+            None
+        })?;
+        position = start + length;
+        
+        let inner = &rest[1..length - 1];
+        let (kind, inner) = match (inner.strip_prefix('/'), inner.strip_suffix('/')) {
+            (Some(inner), _) => (TagKind::End, inner),
+            (None, Some(inner)) => (TagKind::Empty, inner),
+            (None, None) => (TagKind::Start, inner),
+        };
+        let name_end = inner.find(char::is_whitespace).unwrap_or(inner.len());
+        return Some(SourceTag {
+            name: &inner[..name_end],
+            attributes: &inner[name_end..],
+            kind,
+            start,
+            end: position,
+        });
+    })
+}
+
+/// Returns the value of attribute `name` in the attribute source of a tag
+fn attribute<'s>(mut attributes: &'s str, name: &str) -> Option<&'s str> {
+    loop {
+        let (key, rest) = attributes.split_once('=')?;
+        let rest = rest.trim_start();
+        let quote = rest.chars().next().filter(|c| matches!(c, '"' | '\''))?;
+        let (value, after) = rest[1..].split_once(quote)?;
+        if key.trim() == name {
+            return Some(value);
+        }
+        attributes = after;
     }
 }
\ No newline at end of file
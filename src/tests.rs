@@ -1,9 +1,41 @@
 #[cfg(test)]
+#[allow(clippy::module_inception)]
 mod tests {
     use crate::{PixelatorConfig, Pixelator, config::SampleMode};
     use crate::processor::{ImageProcessor, PixelData};
     use image::{DynamicImage, RgbaImage, Rgba};
 
+    // Scratch directory that is removed when dropped, even if the test fails
+    struct TempDir(std::path::PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("pixelator_{}_{}", name, std::process::id()));
+            std::fs::create_dir_all(&dir).unwrap();
+            TempDir(dir)
+        }
+    }
+
+    impl std::ops::Deref for TempDir {
+        type Target = std::path::Path;
+
+        fn deref(&self) -> &std::path::Path {
+            &self.0
+        }
+    }
+
+    impl AsRef<std::path::Path> for TempDir {
+        fn as_ref(&self) -> &std::path::Path {
+            &self.0
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
     #[test]
     fn test_config_validation() {
         // Test invalid circle diameter
@@ -77,6 +109,7 @@ mod tests {
             color: Rgba([255, 128, 64, 255]),
             brightness: 0.5,
             dot_size: 5.0,
+            grid_position: None,
         };
         
         assert_eq!(pixel.x, 10.0);
@@ -152,6 +185,7 @@ mod tests {
                 color: Rgba([255, 0, 0, 255]),
                 brightness: 0.5,
                 dot_size: 5.0,
+                grid_position: None,
             },
             PixelData {
                 x: 30.0,
@@ -159,6 +193,7 @@ mod tests {
                 color: Rgba([0, 255, 0, 255]),
                 brightness: 0.5,
                 dot_size: 5.0,
+                grid_position: None,
             },
         ];
         
@@ -191,6 +226,7 @@ mod tests {
                 color: Rgba([128, 128, 128, 255]), // Same color for all
                 brightness: 0.5,
                 dot_size: 5.0,
+                grid_position: None,
             });
         }
         
@@ -263,6 +299,7 @@ mod tests {
                 color: Rgba([0, 0, 0, 255]),
                 brightness: 0.0,
                 dot_size: 10.0,  // Large dot for black
+                grid_position: None,
             },
             PixelData {
                 x: 30.0,
@@ -270,6 +307,7 @@ mod tests {
                 color: Rgba([255, 255, 255, 255]),
                 brightness: 1.0,
                 dot_size: 1.0,  // Small dot for white
+                grid_position: None,
             },
         ];
         
@@ -280,4 +318,2755 @@ mod tests {
         // Check background is white
         assert!(svg.contains("background-color: white"));
     }
+    
+    #[test]
+    fn test_supersample_reduces_aliasing() {
+        // Checkerboard with 8px tiles: every dot center lands on a white tile,
+        // but each 16px cell spans both tile colors
+        let img = RgbaImage::from_fn(64, 64, |x, y| {
+            if (x / 8 + y / 8) % 2 == 0 {
+                Rgba([255, 255, 255, 255])
+            } else {
+                Rgba([0, 0, 0, 255])
+            }
+        });
+        let dynamic_img = DynamicImage::ImageRgba8(img);
+        
+        let mean_distance_from_gray = |pixels: &[PixelData]| {
+            pixels.iter()
+                .map(|p| (p.color[0] as f32 - 127.5).abs())
+                .sum::<f32>() / pixels.len() as f32
+        };
+        
+        let config_single = PixelatorConfig::new(4.0, 12.0).unwrap();
+        let single = ImageProcessor::new(&config_single).sample_image(&dynamic_img).unwrap();
+        
+        let config_super = PixelatorConfig::new(4.0, 12.0)
+            .unwrap()
+            .with_supersample(4)
+            .unwrap();
+        let supersampled = ImageProcessor::new(&config_super).sample_image(&dynamic_img).unwrap();
+        
+        assert_eq!(single.len(), supersampled.len());
+        // Single sample stays at the extreme value
+        assert!(single.iter().all(|p| p.color[0] == 255));
+        // Supersampled colors trend toward 50% gray
+        assert!(mean_distance_from_gray(&supersampled) < 64.0);
+        assert!(mean_distance_from_gray(&supersampled) < mean_distance_from_gray(&single));
+        
+        assert!(PixelatorConfig::new(4.0, 12.0).unwrap().with_supersample(0).is_err());
+    }
+    
+    #[test]
+    fn test_path_merge_by_color() {
+        let config = PixelatorConfig::new(10.0, 2.0)
+            .unwrap()
+            .with_path_merge_by_color(true);
+        let generator = crate::svg_generator::SvgGenerator::new(&config);
+        
+        let colors = [Rgba([255, 0, 0, 255]), Rgba([0, 0, 255, 255]), Rgba([0, 255, 0, 255])];
+        let pixels: Vec<PixelData> = (0..30)
+            .map(|i| PixelData {
+                x: (i * 12) as f32 + 5.0,
+                y: 5.0,
+                color: colors[i % colors.len()],
+                brightness: 0.5,
+                dot_size: 10.0,
+                grid_position: None,
+            })
+            .collect();
+        
+        let svg = generator.generate_svg(&pixels, 360, 10).unwrap();
+        
+        assert_eq!(svg.matches("<path").count(), colors.len());
+        assert!(!svg.contains("<circle"));
+        assert!(svg.contains("rgb(255,0,0)"));
+        assert!(svg.contains("rgb(0,0,255)"));
+        assert!(svg.contains("rgb(0,255,0)"));
+    }
+    
+    #[test]
+    fn test_lab_quantization_reduces_perceptual_error() {
+        use crate::color::delta_e;
+        use crate::config::QuantizeSpace;
+        use crate::palette::{build_palette, nearest_color};
+        
+        // Skin-tone gradient from light peach to deep brown
+        let light = [255.0, 224.0, 196.0];
+        let dark = [92.0, 51.0, 23.0];
+        let gradient: Vec<Rgba<u8>> = (0..64)
+            .map(|i| {
+                let t = i as f32 / 63.0;
+                let channel = |c: usize| (light[c] + (dark[c] - light[c]) * t).round() as u8;
+                Rgba([channel(0), channel(1), channel(2), 255])
+            })
+            .collect();
+        
+        let max_error = |space: QuantizeSpace| {
+            let palette = build_palette(&gradient, 4, space);
+            assert_eq!(palette.len(), 4);
+            gradient.iter()
+                .map(|c| delta_e(c, &palette[nearest_color(&palette, c, QuantizeSpace::Lab)]))
+                .fold(0.0f32, f32::max)
+        };
+        
+        let rgb_error = max_error(QuantizeSpace::Rgb);
+        let lab_error = max_error(QuantizeSpace::Lab);
+        assert!(lab_error < rgb_error, "lab {} vs rgb {}", lab_error, rgb_error);
+    }
+    
+    #[test]
+    fn test_palette_quantization_limits_colors() {
+        let img = RgbaImage::from_fn(120, 120, |x, y| Rgba([(x * 2) as u8, (y * 2) as u8, 128, 255]));
+        let dynamic_img = DynamicImage::ImageRgba8(img);
+        
+        let config = PixelatorConfig::new(10.0, 2.0)
+            .unwrap()
+            .with_palette_size(5)
+            .unwrap();
+        let pixels = ImageProcessor::new(&config).sample_image(&dynamic_img).unwrap();
+        
+        let distinct: std::collections::HashSet<_> = pixels.iter().map(|p| p.color.0).collect();
+        assert!(distinct.len() <= 5);
+    }
+    
+    #[test]
+    fn test_dot_json_round_trip() {
+        let dir = TempDir::new("json");
+        let input = dir.join("input.png");
+        let img = RgbaImage::from_fn(60, 40, |x, y| Rgba([(x * 4) as u8, (y * 6) as u8, 90, 255]));
+        img.save(&input).unwrap();
+        
+        let config = PixelatorConfig::new(6.0, 1.5)
+            .unwrap()
+            .with_background_color("white".to_string());
+        let pixelator = Pixelator::new(config);
+        
+        let direct = pixelator.process_image(&input).unwrap();
+        let json = pixelator.process_image_json(&input).unwrap();
+        let from_json = crate::svg_generator::SvgGenerator::from_dot_json(&json).unwrap();
+        
+        assert_eq!(direct, from_json);
+    }
+    
+    #[test]
+    fn test_halftone_curve_changes_midtone_size() {
+        use crate::config::{RenderMode, HalftoneStyle, HalftoneCurve};
+        
+        let img = RgbaImage::from_pixel(40, 40, Rgba([128, 128, 128, 255]));
+        let dynamic_img = DynamicImage::ImageRgba8(img);
+        
+        let midtone_size = |curve: HalftoneCurve| {
+            let config = PixelatorConfig::new(10.0, 2.0)
+                .unwrap()
+                .with_render_mode(RenderMode::Halftone(HalftoneStyle::BlackOnWhite))
+                .with_halftone_range(1.0, 10.0)
+                .unwrap()
+                .with_halftone_curve(curve)
+                .unwrap();
+            ImageProcessor::new(&config).sample_image(&dynamic_img).unwrap()[0].dot_size
+        };
+        
+        let linear = midtone_size(HalftoneCurve::Linear);
+        let gamma = midtone_size(HalftoneCurve::Gamma(2.0));
+        assert!((linear - gamma).abs() > 0.5);
+        assert!(gamma < linear);
+        
+        assert!(PixelatorConfig::default().with_halftone_curve(HalftoneCurve::Gamma(0.0)).is_err());
+    }
+    
+    #[test]
+    fn test_grid_dimensions_match_sampling() {
+        let distinct = |values: Vec<f32>| {
+            let mut values: Vec<u32> = values.into_iter().map(f32::to_bits).collect();
+            values.sort();
+            values.dedup();
+            values.len()
+        };
+        
+        for &(width, height) in &[(100, 100), (97, 53), (250, 31), (12, 12)] {
+            let img = DynamicImage::ImageRgba8(RgbaImage::from_pixel(width, height, Rgba([90, 90, 90, 255])));
+            
+            let grid = PixelatorConfig::new(10.0, 2.0).unwrap();
+            let pixels = ImageProcessor::new(&grid).sample_image(&img).unwrap();
+            let (cols, rows) = grid.grid_dimensions(width, height);
+            assert_eq!(cols * rows, pixels.len());
+            assert_eq!(grid.dot_count(width, height), pixels.len());
+            if !pixels.is_empty() {
+                assert_eq!(distinct(pixels.iter().map(|p| p.x).collect()), cols);
+                assert_eq!(distinct(pixels.iter().map(|p| p.y).collect()), rows);
+            }
+            
+            let hex = grid.clone().with_sample_mode(SampleMode::Hexagonal);
+            let pixels = ImageProcessor::new(&hex).sample_image(&img).unwrap();
+            let (_, hex_rows) = hex.grid_dimensions(width, height);
+            assert_eq!(hex.dot_count(width, height), pixels.len());
+            assert_eq!(distinct(pixels.iter().map(|p| p.y).collect()), hex_rows);
+        }
+    }
+    
+    /// Extracts every numeric value of the given attribute from an SVG string
+    fn attr_values(svg: &str, attr: &str) -> Vec<f32> {
+        let needle = format!(" {}=\"", attr);
+        svg.match_indices(&needle)
+            .filter_map(|(i, _)| {
+                let rest = &svg[i + needle.len()..];
+                rest[..rest.find('"')?].parse().ok()
+            })
+            .collect()
+    }
+    
+    #[test]
+    fn test_center_origin() {
+        use crate::config::Origin;
+        
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_pixel(120, 80, Rgba([200, 100, 50, 255])));
+        let config = PixelatorConfig::new(10.0, 2.0)
+            .unwrap()
+            .with_origin(Origin::Center);
+        let pixels = ImageProcessor::new(&config).sample_image(&img).unwrap();
+        let svg = crate::svg_generator::SvgGenerator::new(&config)
+            .generate_svg(&pixels, 120, 80)
+            .unwrap();
+        
+        let cx = attr_values(&svg, "cx");
+        let cy = attr_values(&svg, "cy");
+        assert_eq!(cx.len(), pixels.len());
+        let mean_x = cx.iter().sum::<f32>() / cx.len() as f32;
+        let mean_y = cy.iter().sum::<f32>() / cy.len() as f32;
+        assert!(mean_x.abs() < config.get_total_spacing());
+        assert!(mean_y.abs() < config.get_total_spacing());
+        assert!(svg.contains("viewBox=\"-60 -40 120 80\""));
+    }
+    
+    #[test]
+    fn test_colorblind_safe_remapping() {
+        use crate::color::{delta_e, simulate_color_blindness};
+        use crate::config::CbType;
+        
+        // Orange-brown and olive look nearly identical under deuteranopia
+        let a = Rgba([170, 110, 40, 255]);
+        let b = Rgba([120, 125, 40, 255]);
+        let confusable = delta_e(
+            &simulate_color_blindness(&a, CbType::Deuteranopia),
+            &simulate_color_blindness(&b, CbType::Deuteranopia),
+        );
+        assert!(confusable < 10.0);
+        
+        let img = RgbaImage::from_fn(48, 24, |x, _| if x < 24 { a } else { b });
+        let config = PixelatorConfig::new(6.0, 2.0)
+            .unwrap()
+            .with_palette_size(2)
+            .unwrap()
+            .with_colorblind_safe(CbType::Deuteranopia);
+        let pixels = ImageProcessor::new(&config)
+            .sample_image(&DynamicImage::ImageRgba8(img))
+            .unwrap();
+        
+        let left = pixels.iter().find(|p| p.x < 20.0).unwrap().color;
+        let right = pixels.iter().find(|p| p.x > 28.0).unwrap().color;
+        let remapped = delta_e(
+            &simulate_color_blindness(&left, CbType::Deuteranopia),
+            &simulate_color_blindness(&right, CbType::Deuteranopia),
+        );
+        assert!(remapped > 20.0, "remapped delta E {}", remapped);
+    }
+    
+    #[test]
+    fn test_cie_lightness_brightness_metric() {
+        use crate::config::BrightnessMetric;
+        
+        let gray = Rgba([128, 128, 128, 255]);
+        let lightness = ImageProcessor::calculate_lightness(&gray);
+        assert!((lightness - 0.54).abs() < 0.01);
+        assert!((lightness - ImageProcessor::calculate_brightness(&gray)).abs() > 0.02);
+        
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_pixel(30, 30, gray));
+        let config = PixelatorConfig::new(10.0, 2.0)
+            .unwrap()
+            .with_brightness_metric(BrightnessMetric::CieLStar);
+        let pixels = ImageProcessor::new(&config).sample_image(&img).unwrap();
+        assert!(pixels.iter().all(|p| (p.brightness - lightness).abs() < 1e-6));
+    }
+    
+    #[test]
+    fn test_max_elements_sparsifies_output() {
+        let dir = TempDir::new("cap");
+        let input = dir.join("input.png");
+        RgbaImage::from_pixel(200, 150, Rgba([40, 80, 120, 255])).save(&input).unwrap();
+        
+        let config = PixelatorConfig::new(2.0, 0.0).unwrap();
+        assert!(config.dot_count(200, 150) > 500);
+        let capped = config.with_max_elements(500).unwrap();
+        
+        let pixelator = Pixelator::new(capped);
+        let svg = pixelator.process_image(&input).unwrap();
+        // The adjusted spacing is reported instead of printed
+        let warnings = pixelator.take_warnings();
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("circle spacing increased"));
+        assert!(pixelator.take_warnings().is_empty());
+        
+        let circles = svg.matches("<circle").count();
+        assert!(circles <= 500);
+        assert!(circles > 250);
+    }
+    
+    #[test]
+    fn test_directional_sampling_blends_across_edge() {
+        // Vertical edge at x = 20: black on the left, white on the right
+        let img = RgbaImage::from_fn(64, 16, |x, _| {
+            if x < 20 { Rgba([0, 0, 0, 255]) } else { Rgba([255, 255, 255, 255]) }
+        });
+        let dynamic_img = DynamicImage::ImageRgba8(img);
+        
+        // Dot centers fall at x = 2, 18, 34, ...; the dot at x = 18 sits next to the edge
+        let near_edge = |pixels: &[PixelData]| pixels.iter().find(|p| p.x == 18.0).unwrap().color[0];
+        
+        let circular = PixelatorConfig::new(4.0, 12.0).unwrap();
+        let circular_pixels = ImageProcessor::new(&circular).sample_image(&dynamic_img).unwrap();
+        
+        let streak = circular.clone().with_directional_sample(0.0, 12.0).unwrap();
+        let streak_pixels = ImageProcessor::new(&streak).sample_image(&dynamic_img).unwrap();
+        
+        assert!(near_edge(&streak_pixels) > near_edge(&circular_pixels) + 40);
+    }
+    
+    #[test]
+    fn test_grid_fit_stretch_fills_to_edge() {
+        use crate::config::GridFit;
+        
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_pixel(100, 70, Rgba([10, 20, 30, 255])));
+        let last_x = |config: &PixelatorConfig| {
+            ImageProcessor::new(config).sample_image(&img).unwrap()
+                .iter()
+                .map(|p| p.x)
+                .fold(f32::MIN, f32::max)
+        };
+        
+        let floor = PixelatorConfig::new(10.0, 2.0).unwrap();
+        let stretch = floor.clone().with_grid_fit(GridFit::Stretch);
+        
+        assert!((last_x(&stretch) - 95.0).abs() < 0.01);
+        assert!(last_x(&stretch) > last_x(&floor));
+        
+        let ceil = floor.clone().with_grid_fit(GridFit::Ceil);
+        assert_eq!(ceil.grid_dimensions(100, 70), (9, 6));
+        assert!(last_x(&ceil) <= 100.0);
+    }
+    
+    #[test]
+    fn test_background_rect() {
+        let config = PixelatorConfig::new(10.0, 2.0)
+            .unwrap()
+            .with_background_color("#112233".to_string())
+            .with_background_rect(true);
+        let generator = crate::svg_generator::SvgGenerator::new(&config);
+        let pixels = vec![PixelData {
+            x: 5.0,
+            y: 5.0,
+            color: Rgba([255, 0, 0, 255]),
+            brightness: 0.3,
+            dot_size: 10.0,
+            grid_position: None,
+        }];
+        
+        let svg = generator.generate_svg(&pixels, 120, 80).unwrap();
+        
+        // The rect is the first child of the root element
+        let first_child = svg.split('\n').nth(1).unwrap();
+        assert!(first_child.starts_with("<rect"));
+        assert!(first_child.contains("fill=\"#112233\""));
+        assert!(first_child.contains("width=\"120\""));
+        assert!(first_child.contains("height=\"80\""));
+        assert!(svg.find("<rect").unwrap() < svg.find("<circle").unwrap());
+        assert!(!svg.contains("background-color"));
+    }
+    
+    #[test]
+    fn test_saturation_map_mode() {
+        use crate::color::rgb_to_hsl;
+        use crate::config::RenderMode;
+        
+        // Same hue, one dark and one bright
+        let dark = Rgba([90, 20, 20, 255]);
+        let bright = Rgba([255, 140, 140, 255]);
+        let img = RgbaImage::from_fn(48, 24, |x, _| if x < 24 { dark } else { bright });
+        
+        let config = PixelatorConfig::new(6.0, 2.0)
+            .unwrap()
+            .with_render_mode(RenderMode::SaturationMap);
+        let pixels = ImageProcessor::new(&config)
+            .sample_image(&DynamicImage::ImageRgba8(img))
+            .unwrap();
+        let svg = crate::svg_generator::SvgGenerator::new(&config)
+            .generate_svg(&pixels, 48, 24)
+            .unwrap();
+        
+        let fills: Vec<Rgba<u8>> = svg.match_indices("fill=\"rgb(")
+            .map(|(i, _)| {
+                let rest = &svg[i + 10..];
+                let channels: Vec<u8> = rest[..rest.find(')').unwrap()]
+                    .split(',')
+                    .map(|c| c.parse().unwrap())
+                    .collect();
+                Rgba([channels[0], channels[1], channels[2], 255])
+            })
+            .collect();
+        assert_eq!(fills.len(), pixels.len());
+        
+        let dark_out = rgb_to_hsl(&fills[0]);
+        let bright_out = rgb_to_hsl(fills.last().unwrap());
+        assert!(dark_out[1] < bright_out[1]);
+        assert!(dark_out[1] < rgb_to_hsl(&dark)[1]);
+        // Hue is preserved
+        assert!(dark_out[0] < 5.0 || dark_out[0] > 355.0);
+    }
+    
+    #[test]
+    fn test_single_thread_matches_default() {
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_fn(90, 70, |x, y| {
+            Rgba([(x * 3) as u8, (y * 3) as u8, ((x + y) * 2) as u8, 255])
+        }));
+        
+        let config = PixelatorConfig::new(6.0, 1.0).unwrap();
+        let single = config.clone().with_threads(1);
+        
+        let render = |config: &PixelatorConfig| {
+            let pixels = ImageProcessor::new(config).sample_image(&img).unwrap();
+            crate::svg_generator::SvgGenerator::new(config).generate_svg(&pixels, 90, 70).unwrap()
+        };
+        
+        assert_eq!(render(&config), render(&single));
+    }
+    
+    #[test]
+    fn test_csv_export() {
+        let dir = TempDir::new("csv");
+        let input = dir.join("input.png");
+        let output = dir.join("dots.csv");
+        RgbaImage::from_pixel(50, 30, Rgba([10, 200, 30, 255])).save(&input).unwrap();
+        
+        let config = PixelatorConfig::new(8.0, 2.0).unwrap();
+        let expected_dots = config.dot_count(50, 30);
+        Pixelator::new(config).process_image_to_csv(&input, &output).unwrap();
+        let csv = std::fs::read_to_string(&output).unwrap();
+        
+        let mut lines = csv.lines();
+        assert_eq!(lines.next(), Some("x,y,r,g,b,size,col,row"));
+        let rows: Vec<&str> = lines.collect();
+        assert_eq!(rows.len(), expected_dots);
+        assert_eq!(rows[0], "4,4,10,200,30,8,0,0");
+        assert!(rows.iter().all(|r| r.split(',').count() == 8));
+    }
+    
+    #[test]
+    fn test_blue_noise_dither_differs() {
+        use crate::config::{RenderMode, HalftoneStyle, DitherMode};
+        
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_pixel(120, 120, Rgba([128, 128, 128, 255])));
+        let sizes = |mode: DitherMode| {
+            let config = PixelatorConfig::new(6.0, 0.0)
+                .unwrap()
+                .with_render_mode(RenderMode::Halftone(HalftoneStyle::BlackOnWhite))
+                .with_dither(mode);
+            ImageProcessor::new(&config).sample_image(&img).unwrap()
+                .iter()
+                .map(|p| p.dot_size)
+                .collect::<Vec<f32>>()
+        };
+        
+        let plain = sizes(DitherMode::None);
+        let floyd = sizes(DitherMode::FloydSteinberg);
+        let blue = sizes(DitherMode::BlueNoise);
+        
+        assert_ne!(blue, plain);
+        assert_ne!(blue, floyd);
+        assert_ne!(floyd, plain);
+        
+        // Both dithers binarize a flat midtone into roughly half large dots
+        let large = blue.iter().filter(|&&s| s > 3.0).count() as f32 / blue.len() as f32;
+        assert!((large - 0.5).abs() < 0.1);
+    }
+    
+    #[test]
+    fn test_scale_bar_in_margin() {
+        let config = PixelatorConfig::new(10.0, 0.0)
+            .unwrap()
+            .with_output_dimensions(100.0, 50.0)
+            .unwrap()
+            .with_margin(10.0)
+            .unwrap()
+            .with_scale_bar(25.0)
+            .unwrap();
+        let pixels = vec![PixelData {
+            x: 5.0,
+            y: 5.0,
+            color: Rgba([0, 0, 0, 255]),
+            brightness: 0.0,
+            dot_size: 10.0,
+            grid_position: None,
+        }];
+        
+        let svg = crate::svg_generator::SvgGenerator::new(&config).generate_svg(&pixels, 200, 100).unwrap();
+        assert!(svg.contains("width=\"120mm\""));
+        assert!(svg.contains("height=\"70mm\""));
+        assert!(svg.contains("viewBox=\"-20 -20 240 140\""));
+        assert!(svg.contains("<line"));
+        assert!(svg.contains("x2=\"25\""));
+        assert!(svg.contains("25 mm\n</text>"));
+        
+        // A scale bar cannot be sized without physical dimensions
+        let config = PixelatorConfig::new(10.0, 0.0)
+            .unwrap()
+            .with_margin(10.0)
+            .unwrap()
+            .with_scale_bar(25.0)
+            .unwrap();
+        assert!(crate::svg_generator::SvgGenerator::new(&config).generate_svg(&pixels, 200, 100).is_err());
+    }
+    
+    #[test]
+    fn test_sharpen_increases_edge_contrast() {
+        // A soft ramp from dark to light across a vertical edge at x = 20
+        let img = RgbaImage::from_fn(40, 8, |x, _| {
+            let t = ((x as f32 - 16.0) / 8.0).clamp(0.0, 1.0);
+            let v = (60.0 + 140.0 * t) as u8;
+            Rgba([v, v, v, 255])
+        });
+        let img = DynamicImage::ImageRgba8(img);
+        
+        let edge_contrast = |amount: f32| {
+            let config = PixelatorConfig::new(2.0, 0.0)
+                .unwrap()
+                .with_sharpen(amount)
+                .unwrap();
+            let pixels = ImageProcessor::new(&config).sample_image(&img).unwrap();
+            let row: Vec<&PixelData> = pixels.iter().filter(|p| p.grid_position.map(|g| g.1) == Some(1)).collect();
+            let left = row.iter().find(|p| p.grid_position.unwrap().0 == 8).unwrap().color[0];
+            let right = row.iter().find(|p| p.grid_position.unwrap().0 == 11).unwrap().color[0];
+            right as i32 - left as i32
+        };
+        
+        assert!(edge_contrast(1.0) > edge_contrast(0.0));
+        assert!(PixelatorConfig::new(2.0, 0.0).unwrap().with_sharpen(-1.0).is_err());
+    }
+    
+    #[test]
+    fn test_voronoi_cells_tile_canvas() {
+        use crate::config::RenderMode;
+        use crate::voronoi::{polygon_area, voronoi_cells};
+        
+        let sites = vec![(10.0, 10.0), (80.0, 15.0), (40.0, 45.0), (15.0, 70.0), (90.0, 90.0), (60.0, 75.0)];
+        let cells = voronoi_cells(&sites, (0.0, 0.0, 100.0, 100.0));
+        assert_eq!(cells.len(), sites.len());
+        
+        // Cells are disjoint, so their areas must sum to the full canvas
+        let total: f32 = cells.iter().map(|c| polygon_area(c)).sum();
+        assert!((total - 10_000.0).abs() < 0.5, "covered area {}", total);
+        assert!(cells.iter().all(|c| c.len() >= 3));
+        
+        // Every canvas corner belongs to the cell of its nearest site
+        for corner in [(0.0, 0.0), (100.0, 0.0), (0.0, 100.0), (100.0, 100.0)] {
+            assert!(cells.iter().any(|c| c.iter().any(|v| (v.0 - corner.0).abs() < 1e-3 && (v.1 - corner.1).abs() < 1e-3)));
+        }
+        
+        let config = PixelatorConfig::new(10.0, 0.0).unwrap().with_render_mode(RenderMode::Voronoi);
+        let pixels: Vec<PixelData> = sites
+            .iter()
+            .map(|&(x, y)| PixelData {
+                x,
+                y,
+                color: Rgba([200, 50, 50, 255]),
+                brightness: 0.5,
+                dot_size: 10.0,
+                grid_position: None,
+            })
+            .collect();
+        let svg = crate::svg_generator::SvgGenerator::new(&config).generate_svg(&pixels, 100, 100).unwrap();
+        assert_eq!(svg.matches("<polygon").count(), sites.len());
+        assert!(!svg.contains("<circle"));
+    }
+    
+    #[test]
+    fn test_pixel_art_keeps_exact_colors() {
+        let palette = [
+            Rgba([255, 0, 77, 255]),
+            Rgba([41, 173, 255, 255]),
+            Rgba([255, 236, 39, 255]),
+            Rgba([29, 43, 83, 255]),
+        ];
+        // Checkerboard sprite of single-pixel cells, the worst case for averaging
+        let img = RgbaImage::from_fn(16, 16, |x, y| palette[((x + 2 * y) % 4) as usize]);
+        let img = DynamicImage::ImageRgba8(img);
+        
+        let config = PixelatorConfig::new(1.0, 0.0)
+            .unwrap()
+            .with_supersample(4)
+            .unwrap()
+            .with_pixel_art_mode(true);
+        let pixels = ImageProcessor::new(&config).sample_image(&img).unwrap();
+        
+        assert_eq!(pixels.len(), 256);
+        let mut seen: Vec<Rgba<u8>> = Vec::new();
+        for pixel in &pixels {
+            assert!(palette.contains(&pixel.color), "unexpected color {:?}", pixel.color);
+            assert_eq!(pixel.x.fract(), 0.5);
+            assert_eq!(pixel.y.fract(), 0.5);
+            if !seen.contains(&pixel.color) {
+                seen.push(pixel.color);
+            }
+        }
+        assert_eq!(seen.len(), 4);
+    }
+    
+    #[test]
+    fn test_gradient_dots_reference_defs() {
+        let img = RgbaImage::from_fn(40, 40, |x, _| if x < 20 { Rgba([255, 0, 0, 255]) } else { Rgba([0, 0, 255, 255]) });
+        let config = PixelatorConfig::new(10.0, 0.0).unwrap().with_gradient_dots(true);
+        let pixels = ImageProcessor::new(&config)
+            .sample_image(&DynamicImage::ImageRgba8(img))
+            .unwrap();
+        let svg = crate::svg_generator::SvgGenerator::new(&config).generate_svg(&pixels, 40, 40).unwrap();
+        
+        assert!(svg.contains("<radialGradient"));
+        assert_eq!(svg.matches("fill=\"url(#").count(), pixels.len());
+        
+        // Dots sharing a color pair share one gradient definition
+        let gradients = svg.matches("<radialGradient").count();
+        assert!(gradients < pixels.len());
+        assert!(gradients >= 2);
+    }
+    
+    #[test]
+    fn test_gcode_y_up_flips_top_to_max_y() {
+        let config = PixelatorConfig::new(4.0, 0.0)
+            .unwrap()
+            .with_output_dimensions(50.0, 100.0)
+            .unwrap();
+        let pixels = vec![PixelData {
+            x: 10.0,
+            y: 0.0,
+            color: Rgba([0, 0, 0, 255]),
+            brightness: 0.0,
+            dot_size: 4.0,
+            grid_position: None,
+        }];
+        let first_move_y = |config: &PixelatorConfig| {
+            let gcode = crate::GcodeGenerator::new(config).generate_gcode(&pixels, 50, 100).unwrap();
+            let line = gcode.lines().find(|l| l.starts_with("G0 ")).unwrap().to_string();
+            line.split(' ').find(|w| w.starts_with('Y')).unwrap()[1..].parse::<f32>().unwrap()
+        };
+        
+        assert_eq!(first_move_y(&config), 0.0);
+        assert_eq!(first_move_y(&config.clone().with_y_up(true)), 100.0);
+    }
+    
+    #[test]
+    fn test_fm_halftone_density_follows_darkness() {
+        use crate::config::{HalftoneCurve, RenderMode};
+        use crate::dither::fm_screen;
+        
+        let cell = |x: f32, brightness: f32| PixelData {
+            x,
+            y: 8.0,
+            color: Rgba([0, 0, 0, 255]),
+            brightness,
+            dot_size: 1.0,
+            grid_position: None,
+        };
+        let count = |brightness: f32| fm_screen(&[cell(8.0, brightness)], 16.0, 1.0, &HalftoneCurve::Linear).len();
+        
+        assert_eq!(count(1.0), 0);
+        assert!(count(0.2) > count(0.5));
+        assert!(count(0.5) > count(0.8));
+        assert!(count(0.8) > 0);
+        // A mid-grey cell is covered by about half of its 256 slots
+        assert!((count(0.5) as i32 - 128).abs() < 16);
+        
+        let config = PixelatorConfig::new(16.0, 0.0)
+            .unwrap()
+            .with_render_mode(RenderMode::FmHalftone { dot_size: 1.0 });
+        let pixels = vec![cell(8.0, 0.2), cell(24.0, 0.8)];
+        let svg = crate::svg_generator::SvgGenerator::new(&config).generate_svg(&pixels, 32, 16).unwrap();
+        let radii = attr_values(&svg, "r");
+        assert_eq!(radii.len(), count(0.2) + count(0.8));
+        assert!(radii.iter().all(|&r| r == 0.5));
+    }
+    
+    #[test]
+    fn test_presets() {
+        use crate::config::{RenderMode, HalftoneStyle};
+        
+        let newspaper = PixelatorConfig::preset_newspaper();
+        assert!(matches!(newspaper.render_mode, RenderMode::Halftone(HalftoneStyle::BlackOnWhite)));
+        let spacing = newspaper.get_total_spacing();
+        assert!((2.0..=20.0).contains(&spacing));
+        assert!(newspaper.min_dot_size < newspaper.max_dot_size);
+        assert!(!newspaper.dots_overlap(newspaper.largest_dot_size()));
+        
+        assert!(matches!(PixelatorConfig::preset_pen_plotter().render_mode, RenderMode::Halftone(_)));
+        assert_eq!(PixelatorConfig::preset_poster().palette_size, Some(12));
+        
+        // Presets stay adjustable through the regular builders
+        let resized = PixelatorConfig::preset_newspaper().with_circle_size(4.0, 1.0).unwrap();
+        assert_eq!(resized.get_total_spacing(), 5.0);
+        assert!(PixelatorConfig::preset_poster().with_circle_size(0.0, 1.0).is_err());
+    }
+    
+    #[test]
+    fn test_background_pattern_def() {
+        use crate::config::PatternKind;
+        
+        let pixels = vec![PixelData {
+            x: 18.0,
+            y: 6.0,
+            color: Rgba([10, 20, 30, 255]),
+            brightness: 0.1,
+            dot_size: 10.0,
+            grid_position: Some((0, 0)),
+        }];
+        for kind in [PatternKind::Dots, PatternKind::Grid, PatternKind::Diagonal] {
+            let config = PixelatorConfig::new(10.0, 2.0).unwrap().with_background_pattern(kind);
+            let svg = crate::svg_generator::SvgGenerator::new(&config).generate_svg(&pixels, 24, 24).unwrap();
+            
+            assert!(svg.contains("<pattern"));
+            assert!(svg.contains("id=\"background-pattern\""));
+            assert!(svg.contains("<rect fill=\"url(#background-pattern)\""));
+            // The background is drawn before the dots
+            assert!(svg.find("url(#background-pattern)").unwrap() < svg.find("<circle cx=\"18\"").unwrap());
+        }
+    }
+    
+    #[test]
+    fn test_size_mask_scales_dots() {
+        let dir = TempDir::new("mask");
+        let mask_path = dir.join("mask.png");
+        
+        // Radial mask at half the source resolution: white center fading to black edges
+        let mask = image::GrayImage::from_fn(30, 30, |x, y| {
+            let d = ((x as f32 - 15.0).powi(2) + (y as f32 - 15.0).powi(2)).sqrt() / 15.0;
+            image::Luma([(255.0 * (1.0 - d).max(0.0)) as u8])
+        });
+        mask.save(&mask_path).unwrap();
+        
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_pixel(60, 60, Rgba([90, 90, 90, 255])));
+        let config = PixelatorConfig::new(6.0, 0.0).unwrap().with_size_mask(&mask_path);
+        let pixels = ImageProcessor::new(&config).sample_image(&img).unwrap();
+        
+        let size_at = |col: usize, row: usize| {
+            pixels.iter().find(|p| p.grid_position == Some((col, row))).unwrap().dot_size
+        };
+        assert!(size_at(5, 5) > 5.0);
+        assert!(size_at(5, 5) > size_at(2, 5));
+        assert!(size_at(2, 5) > size_at(0, 0));
+        assert!(pixels.iter().all(|p| p.dot_size <= 6.0));
+    }
+    
+    #[test]
+    fn test_tiny_image_reports_error() {
+        use crate::PixelatorError;
+        
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_pixel(3, 3, Rgba([90, 90, 90, 255])));
+        for mode in [SampleMode::Grid, SampleMode::Hexagonal] {
+            let config = PixelatorConfig::new(10.0, 2.0).unwrap().with_sample_mode(mode);
+            match ImageProcessor::new(&config).sample_image(&img) {
+                Err(PixelatorError::Processing(message)) => assert!(message.contains("too small")),
+                other => panic!("expected a processing error, got {:?}", other.map(|p| p.len())),
+            }
+        }
+        
+        // A spacing that fits the image still samples normally
+        let config = PixelatorConfig::new(2.0, 0.0).unwrap();
+        assert!(!ImageProcessor::new(&config).sample_image(&img).unwrap().is_empty());
+    }
+    
+    #[test]
+    fn test_normalized_coordinates() {
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_pixel(120, 80, Rgba([90, 90, 90, 255])));
+        let config = PixelatorConfig::new(10.0, 2.0).unwrap().with_normalized_coordinates(true);
+        let pixels = ImageProcessor::new(&config).sample_image(&img).unwrap();
+        let svg = crate::svg_generator::SvgGenerator::new(&config).generate_svg(&pixels, 120, 80).unwrap();
+        
+        let xs = attr_values(&svg, "cx");
+        let ys = attr_values(&svg, "cy");
+        assert_eq!(xs.len(), pixels.len());
+        assert!(xs.iter().chain(&ys).all(|v| (0.0..=1.0).contains(v)));
+        assert!(attr_values(&svg, "r").iter().all(|&r| (r - 5.0 / 120.0).abs() < 1e-6));
+        assert!(svg.contains("preserveAspectRatio=\"xMidYMid meet\""));
+        // The physical size still follows the source image
+        assert!(svg.contains("width=\"120mm\""));
+        
+        let square = crate::svg_generator::SvgGenerator::new(&config).generate_svg(&pixels, 80, 80).unwrap();
+        assert!(square.contains("viewBox=\"0 0 1 1\""));
+    }
+    
+    #[test]
+    fn test_indexed_png_palette_reused() {
+        let dir = TempDir::new("indexed");
+        let input = dir.join("indexed.png");
+        
+        // 40x40 indexed PNG made of four 20x20 quadrants, one per palette entry
+        let palette: [[u8; 3]; 4] = [[200, 30, 40], [20, 140, 200], [250, 220, 60], [30, 40, 70]];
+        let indices: Vec<u8> = (0..40u32 * 40)
+            .map(|i| ((i % 40) / 20 + 2 * ((i / 40) / 20)) as u8)
+            .collect();
+        {
+            let file = std::fs::File::create(&input).unwrap();
+            let mut encoder = png::Encoder::new(std::io::BufWriter::new(file), 40, 40);
+            encoder.set_color(png::ColorType::Indexed);
+            encoder.set_depth(png::BitDepth::Eight);
+            encoder.set_palette(palette.concat());
+            encoder.write_header().unwrap().write_image_data(&indices).unwrap();
+        }
+        
+        let config = PixelatorConfig::new(6.0, 2.0).unwrap().with_palette_size(8).unwrap();
+        let dots = Pixelator::new(config).sample(&input).unwrap();
+        
+        let mut seen: Vec<[u8; 3]> = Vec::new();
+        for dot in &dots.dots {
+            let rgb = [dot.color[0], dot.color[1], dot.color[2]];
+            assert!(palette.contains(&rgb), "color {:?} is not in the source palette", rgb);
+            if !seen.contains(&rgb) {
+                seen.push(rgb);
+            }
+        }
+        assert_eq!(seen.len(), palette.len());
+    }
+    
+    #[test]
+    fn test_clip_mask_drops_outside_dots() {
+        let dir = TempDir::new("clip");
+        let mask_path = dir.join("circle.png");
+        
+        // White disc on black, at a different resolution than the source
+        let mask = image::GrayImage::from_fn(50, 50, |x, y| {
+            let inside = (x as f32 - 25.0).powi(2) + (y as f32 - 25.0).powi(2) < 20.0 * 20.0;
+            image::Luma([if inside { 255 } else { 0 }])
+        });
+        mask.save(&mask_path).unwrap();
+        
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_pixel(100, 100, Rgba([90, 90, 90, 255])));
+        let unclipped = PixelatorConfig::new(8.0, 2.0).unwrap();
+        let all = ImageProcessor::new(&unclipped).sample_image(&img).unwrap();
+        let clipped = unclipped.clone().with_clip_mask(&mask_path);
+        let pixels = ImageProcessor::new(&clipped).sample_image(&img).unwrap();
+        
+        let has = |col: usize, row: usize| pixels.iter().any(|p| p.grid_position == Some((col, row)));
+        assert!(pixels.len() < all.len());
+        assert!(has(4, 4) && has(5, 5));
+        assert!(!has(0, 0) && !has(9, 0) && !has(0, 9) && !has(9, 9));
+    }
+    
+    #[test]
+    fn test_paint_order_darkest_last() {
+        use crate::config::PaintOrder;
+        
+        let shades = [120u8, 10, 250, 60, 180];
+        let pixels: Vec<PixelData> = shades
+            .iter()
+            .enumerate()
+            .map(|(i, &v)| PixelData {
+                x: 5.0 + i as f32 * 4.0,
+                y: 5.0,
+                color: Rgba([v, v, v, 255]),
+                brightness: v as f32 / 255.0,
+                dot_size: 10.0,
+                grid_position: None,
+            })
+            .collect();
+        let emitted_x = |order: PaintOrder| {
+            let config = PixelatorConfig::new(10.0, 0.0).unwrap().with_paint_order(order);
+            let svg = crate::svg_generator::SvgGenerator::new(&config).generate_svg(&pixels, 30, 10).unwrap();
+            attr_values(&svg, "cx")
+        };
+        let darkness = |xs: Vec<f32>| -> Vec<u8> {
+            xs.iter().map(|x| 255 - shades[((x - 5.0) / 4.0) as usize]).collect()
+        };
+        
+        // Darkness ascends through the document, so the darkest dot is painted on top
+        assert_eq!(darkness(emitted_x(PaintOrder::DarkestLast)), vec![5, 75, 135, 195, 245]);
+        assert_eq!(darkness(emitted_x(PaintOrder::LightestLast)), vec![245, 195, 135, 75, 5]);
+        assert_eq!(emitted_x(PaintOrder::AsSampled), vec![5.0, 9.0, 13.0, 17.0, 21.0]);
+    }
+    
+    #[test]
+    fn test_number_formatting_trims_zeros() {
+        use crate::format::format_number;
+        
+        assert_eq!(format_number(10.0, 3), "10");
+        assert_eq!(format_number(2.5, 3), "2.5");
+        assert_eq!(format_number(0.1 + 0.2, 3), "0.3");
+        assert_eq!(format_number(-0.0001, 3), "0");
+        assert_eq!(format_number(1234.5678, 2), "1234.57");
+        
+        let pixels = vec![PixelData {
+            x: 10.0,
+            y: 4.0 / 3.0,
+            color: Rgba([0, 0, 0, 255]),
+            brightness: 0.0,
+            dot_size: 10.0,
+            grid_position: None,
+        }];
+        let config = PixelatorConfig::new(10.0, 0.0).unwrap();
+        let svg = crate::svg_generator::SvgGenerator::new(&config).generate_svg(&pixels, 20, 20).unwrap();
+        assert!(svg.contains("cx=\"10\""));
+        assert!(svg.contains("cy=\"1.333\""));
+        assert!(!svg.contains("10.0"));
+    }
+    
+    #[test]
+    fn test_line_screen_width_follows_darkness() {
+        use crate::config::RenderMode;
+        
+        // Dark left half, light right half
+        let img = RgbaImage::from_fn(80, 40, |x, _| {
+            if x < 40 { Rgba([30, 30, 30, 255]) } else { Rgba([220, 220, 220, 255]) }
+        });
+        let config = PixelatorConfig::new(8.0, 0.0)
+            .unwrap()
+            .with_render_mode(RenderMode::LineScreen { angle: 0.0, frequency: 1.0 });
+        let pixels = ImageProcessor::new(&config).sample_image(&DynamicImage::ImageRgba8(img)).unwrap();
+        let svg = crate::svg_generator::SvgGenerator::new(&config).generate_svg(&pixels, 80, 40).unwrap();
+        
+        assert!(!svg.contains("<circle"));
+        let starts = attr_values(&svg, "x1");
+        let widths = attr_values(&svg, "stroke-width");
+        assert_eq!(starts.len(), widths.len());
+        assert!(!widths.is_empty());
+        
+        // Average stroke width of segments starting within [from, to)
+        let average = |from: f32, to: f32| {
+            let side: Vec<f32> = starts
+                .iter()
+                .zip(&widths)
+                .filter(|(x, _)| (from..to).contains(*x))
+                .map(|(_, w)| *w)
+                .collect();
+            side.iter().sum::<f32>() / side.len() as f32
+        };
+        assert!(average(0.0, 36.0) > 2.0 * average(44.0, 80.0));
+    }
+    
+    #[test]
+    fn test_svg_bytes_match_string() {
+        let img = RgbaImage::from_fn(40, 30, |x, y| Rgba([(x * 6) as u8, (y * 8) as u8, 90, 255]));
+        let config = PixelatorConfig::new(6.0, 1.0).unwrap().with_path_merge_by_color(true);
+        let pixels = ImageProcessor::new(&config).sample_image(&DynamicImage::ImageRgba8(img)).unwrap();
+        let generator = crate::svg_generator::SvgGenerator::new(&config);
+        
+        let text = generator.generate_svg(&pixels, 40, 30).unwrap();
+        let bytes = generator.generate_svg_bytes(&pixels, 40, 30).unwrap();
+        assert_eq!(String::from_utf8(bytes).unwrap(), text);
+    }
+    
+    #[test]
+    fn test_quantized_dot_sizes() {
+        use crate::config::{RenderMode, HalftoneStyle};
+        
+        let img = RgbaImage::from_fn(120, 60, |x, _| {
+            let v = (x * 2) as u8;
+            Rgba([v, v, v, 255])
+        });
+        let img = DynamicImage::ImageRgba8(img);
+        let distinct_radii = |config: &PixelatorConfig| {
+            let pixels = ImageProcessor::new(config).sample_image(&img).unwrap();
+            let svg = crate::svg_generator::SvgGenerator::new(config).generate_svg(&pixels, 120, 60).unwrap();
+            let mut radii: Vec<String> = attr_values(&svg, "r").iter().map(|r| r.to_string()).collect();
+            radii.sort();
+            radii.dedup();
+            radii.len()
+        };
+        
+        let config = PixelatorConfig::new(6.0, 0.0)
+            .unwrap()
+            .with_render_mode(RenderMode::Halftone(HalftoneStyle::BlackOnWhite))
+            .with_halftone_range(0.5, 6.0)
+            .unwrap();
+        assert!(distinct_radii(&config) > 5);
+        
+        let quantized = config.clone().with_quantized_dot_sizes(5).unwrap();
+        assert!(distinct_radii(&quantized) <= 5);
+        assert!(distinct_radii(&quantized) > 1);
+        
+        // Color-mode sizes are left as sampled
+        let color = PixelatorConfig::new(6.0, 0.0).unwrap();
+        let unsnapped = ImageProcessor::new(&color).sample_image(&img).unwrap();
+        let leveled = color.with_quantized_dot_sizes(5).unwrap();
+        let pixels = ImageProcessor::new(&leveled).sample_image(&img).unwrap();
+        assert!(pixels.iter().zip(&unsnapped).all(|(a, b)| a.dot_size == b.dot_size));
+        assert!(config.with_quantized_dot_sizes(0).is_err());
+    }
+    
+    #[test]
+    fn test_composite_images_at_offsets() {
+        let dir = TempDir::new("composite");
+        let red = dir.join("red.png");
+        let blue = dir.join("blue.png");
+        RgbaImage::from_pixel(24, 24, Rgba([255, 0, 0, 255])).save(&red).unwrap();
+        RgbaImage::from_pixel(24, 12, Rgba([0, 0, 255, 255])).save(&blue).unwrap();
+        
+        let config = PixelatorConfig::new(10.0, 2.0).unwrap();
+        let svg = Pixelator::new(config)
+            .process_images_to_svg(&[(red, (0.0, 0.0)), (blue, (40.0, 30.0))])
+            .unwrap();
+        
+        // Bounding box spans both images
+        assert!(svg.contains("viewBox=\"0 0 64 42\""));
+        assert_eq!(svg.matches("rgb(255,0,0)").count(), 4);
+        assert_eq!(svg.matches("rgb(0,0,255)").count(), 2);
+        assert!(svg.contains("cx=\"45\" cy=\"35\""));
+        // The later image is drawn on top
+        assert!(svg.find("rgb(0,0,255)").unwrap() > svg.rfind("rgb(255,0,0)").unwrap());
+    }
+    
+    #[test]
+    fn test_composite_renders_with_resolved_config() {
+        let dir = TempDir::new("composite_resolved");
+        let wide = dir.join("wide.png");
+        let narrow = dir.join("narrow.png");
+        RgbaImage::from_pixel(48, 24, Rgba([255, 0, 0, 255])).save(&wide).unwrap();
+        RgbaImage::from_pixel(24, 24, Rgba([0, 0, 255, 255])).save(&narrow).unwrap();
+        
+        // The element cap widens the spacing to fit each image
+        let config = PixelatorConfig::new(2.0, 0.0).unwrap().with_max_elements(50).unwrap();
+        let pixelator = Pixelator::new(config);
+        let composite = pixelator.process_images_to_svg(&[(wide.clone(), (0.0, 0.0))]).unwrap();
+        assert_eq!(composite, pixelator.process_image(&wide).unwrap());
+        
+        // The narrow image resolves to a different spacing
+        let mixed = pixelator.process_images_to_svg(&[(wide, (0.0, 0.0)), (narrow, (48.0, 0.0))]);
+        assert!(matches!(mixed, Err(crate::PixelatorError::InvalidConfig(_))));
+    }
+    
+    #[test]
+    fn test_alpha_in_brightness() {
+        use crate::config::{RenderMode, HalftoneStyle};
+        
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_pixel(24, 24, Rgba([0, 0, 0, 0])));
+        let brightness = |config: &PixelatorConfig| {
+            ImageProcessor::new(config).sample_image(&img).unwrap()[0].brightness
+        };
+        
+        let config = PixelatorConfig::new(10.0, 2.0)
+            .unwrap()
+            .with_render_mode(RenderMode::Halftone(HalftoneStyle::BlackOnWhite));
+        assert_eq!(brightness(&config), 0.0);
+        assert_eq!(brightness(&config.clone().with_alpha_in_brightness(true)), 1.0);
+        
+        let on_black = config
+            .with_render_mode(RenderMode::Halftone(HalftoneStyle::WhiteOnBlack))
+            .with_alpha_in_brightness(true);
+        assert_eq!(brightness(&on_black), 0.0);
+    }
+    
+    #[test]
+    fn test_palette_listing_two_colors() {
+        let dir = TempDir::new("listing");
+        let input = dir.join("two.png");
+        RgbaImage::from_fn(48, 24, |x, _| if x < 36 { Rgba([255, 0, 0, 255]) } else { Rgba([0, 0, 255, 255]) })
+            .save(&input)
+            .unwrap();
+        
+        let config = PixelatorConfig::new(10.0, 2.0).unwrap().with_palette_size(2).unwrap();
+        let dots = Pixelator::new(config).sample(&input).unwrap();
+        
+        let listing = dots.palette_listing();
+        let lines: Vec<&str> = listing.lines().collect();
+        assert_eq!(lines, vec!["#ff0000 6", "#0000ff 2"]);
+    }
+    
+    #[test]
+    fn test_color_by_number_outlines_labels_and_legend() {
+        let config = PixelatorConfig::new(10.0, 0.0)
+            .unwrap()
+            .with_render_mode(crate::config::RenderMode::ColorByNumber);
+        let pixels: Vec<PixelData> = [(5.0, Rgba([255, 0, 0, 255])), (15.0, Rgba([0, 0, 255, 255])), (25.0, Rgba([255, 0, 0, 255]))]
+            .iter()
+            .map(|&(x, color)| PixelData { x, y: 5.0, color, brightness: 0.5, dot_size: 10.0, grid_position: None })
+            .collect();
+        
+        let svg = crate::svg_generator::SvgGenerator::new(&config).generate_svg(&pixels, 30, 10).unwrap();
+        
+        // Cells are outlined rather than filled, one per dot
+        assert!(svg.contains("class=\"cells\" fill=\"none\""));
+        assert_eq!(svg.matches("<polygon").count(), 3);
+        
+        // Each cell is labeled with its color's number
+        let labels = svg.split("class=\"labels\"").nth(1).unwrap().split("</g>").next().unwrap();
+        let numbers: Vec<&str> = labels.split("</text>").filter_map(|t| t.rsplit('>').next()).map(str::trim).filter(|t| !t.is_empty()).collect();
+        assert_eq!(numbers, vec!["1", "2", "1"]);
+        
+        // The legend lists both colors with their numbers
+        let legend = svg.split("class=\"legend\"").nth(1).unwrap();
+        assert!(legend.contains("fill=\"rgb(255,0,0)\""));
+        assert!(legend.contains("fill=\"rgb(0,0,255)\""));
+        assert!(legend.contains("1\n</text>") && legend.contains("2\n</text>"));
+    }
+    
+    #[test]
+    fn test_min_gap_widens_spacing_between_dot_edges() {
+        use crate::config::{HalftoneStyle, RenderMode};
+        
+        let config = PixelatorConfig::new(10.0, 1.0)
+            .unwrap()
+            .with_halftone_range(2.0, 14.0)
+            .unwrap()
+            .with_render_mode(RenderMode::Halftone(HalftoneStyle::BlackOnWhite))
+            .with_min_gap(3.0)
+            .unwrap();
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_pixel(120, 60, Rgba([0, 0, 0, 255])));
+        let pixels = ImageProcessor::new(&config).sample_image(&img).unwrap();
+        
+        let largest = pixels.iter().map(|p| p.dot_size).fold(0.0, f32::max);
+        let first_row: Vec<f32> = pixels.iter().filter(|p| p.y == pixels[0].y).map(|p| p.x).collect();
+        assert!(first_row.len() > 1);
+        for pair in first_row.windows(2) {
+            assert!(pair[1] - pair[0] - largest >= 3.0 - 1e-4);
+        }
+        
+        // A gap already provided by the spacing is left alone
+        let loose = PixelatorConfig::new(10.0, 8.0).unwrap().with_min_gap(3.0).unwrap();
+        assert_eq!(loose.get_total_spacing(), 18.0);
+        assert!(PixelatorConfig::default().with_min_gap(-1.0).is_err());
+    }
+    
+    #[test]
+    fn test_preview_has_fewer_dots_in_same_coordinate_space() {
+        let dir = TempDir::new("preview");
+        let input = dir.join("gradient.png");
+        RgbaImage::from_fn(240, 120, |x, _| Rgba([(x % 256) as u8, 80, 160, 255])).save(&input).unwrap();
+        
+        let pixelator = Pixelator::new(PixelatorConfig::new(4.0, 2.0).unwrap());
+        let full = pixelator.process_image(&input).unwrap();
+        let preview = pixelator.process_image_preview(&input, 4).unwrap();
+        assert!(pixelator.process_image_preview(&input, 0).is_err());
+        // Other pixel lengths, like the outline stroke, scale with the dots
+        let outlined = Pixelator::new(PixelatorConfig::new(4.0, 2.0).unwrap().with_outline_only(0.5).unwrap());
+        let outlined_full = outlined.process_image(&input).unwrap();
+        let outlined_preview = outlined.process_image_preview(&input, 4).unwrap();
+        
+        let stroke_widths = |svg: &str| attr_values(svg, "stroke-width");
+        let full_widths = stroke_widths(&outlined_full);
+        assert_eq!(full_widths.len(), 40 * 20);
+        assert!(full_widths.iter().all(|&width| width == 0.5));
+        let preview_widths = stroke_widths(&outlined_preview);
+        assert_eq!(preview_widths.len(), 10 * 5);
+        assert!(preview_widths.iter().all(|&width| width == 2.0));
+        
+        let (full_dots, preview_dots) = (full.matches("<circle").count(), preview.matches("<circle").count());
+        assert_eq!(full_dots, 40 * 20);
+        assert_eq!(preview_dots, 10 * 5);
+        assert_eq!(full_dots / preview_dots, 16);
+        
+        let view_box = |svg: &str| svg.split("viewBox=\"").nth(1).unwrap().split('"').next().unwrap().to_string();
+        assert_eq!(view_box(&full), view_box(&preview));
+    }
+    
+    #[test]
+    fn test_global_seed_reproduces_stipple_and_jitter() {
+        use crate::config::RenderMode;
+        
+        let dir = TempDir::new("seed");
+        let input = dir.join("gradient.png");
+        RgbaImage::from_fn(60, 40, |x, _| {
+            let v = (x * 4) as u8;
+            Rgba([v, v, v, 255])
+        })
+        .save(&input)
+        .unwrap();
+        
+        let render = |seed: u64| {
+            let config = PixelatorConfig::new(6.0, 2.0)
+                .unwrap()
+                .with_render_mode(RenderMode::Stipple { dot_size: 1.0, seed: None })
+                .with_jitter(1.5)
+                .unwrap()
+                .with_seed(seed);
+            Pixelator::new(config).process_image_bytes(&input).unwrap()
+        };
+        let (first, second, other) = (render(7), render(7), render(8));
+        
+        assert!(String::from_utf8_lossy(&first).contains("<circle"));
+        assert_eq!(first, second);
+        assert_ne!(first, other);
+    }
+    
+    #[test]
+    fn test_feature_seed_overrides_global_seed() {
+        let config = |global: u64| {
+            PixelatorConfig::new(6.0, 2.0)
+                .unwrap()
+                .with_jitter(2.0)
+                .unwrap()
+                .with_seed(global)
+                .with_jitter_seed(99)
+        };
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_pixel(48, 24, Rgba([90, 90, 90, 255])));
+        let a = ImageProcessor::new(&config(1)).sample_image(&img).unwrap();
+        let b = ImageProcessor::new(&config(2)).sample_image(&img).unwrap();
+        
+        let positions = |pixels: &[PixelData]| pixels.iter().map(|p| (p.x, p.y)).collect::<Vec<_>>();
+        assert_eq!(positions(&a), positions(&b));
+        
+        // Offsets stay within the jitter amount on each axis
+        let still = ImageProcessor::new(&PixelatorConfig::new(6.0, 2.0).unwrap()).sample_image(&img).unwrap();
+        for (moved, fixed) in a.iter().zip(&still) {
+            assert!((moved.x - fixed.x).abs() <= 2.0 && (moved.y - fixed.y).abs() <= 2.0);
+        }
+        assert!(PixelatorConfig::default().with_jitter(-1.0).is_err());
+    }
+    
+    #[test]
+    fn test_display_p3_image_converted_to_srgb() {
+        // Minimal ICC profile carrying only a v2 description tag
+        let description = b"Display P3\0";
+        let mut icc = vec![0u8; 128];
+        icc.extend_from_slice(&1u32.to_be_bytes());
+        icc.extend_from_slice(b"desc");
+        icc.extend_from_slice(&144u32.to_be_bytes());
+        icc.extend_from_slice(&(12 + description.len() as u32).to_be_bytes());
+        icc.extend_from_slice(b"desc\0\0\0\0");
+        icc.extend_from_slice(&(description.len() as u32).to_be_bytes());
+        icc.extend_from_slice(description);
+        assert_eq!(crate::icc::identify_profile(&icc), Some(crate::icc::ColorProfile::DisplayP3));
+        let mut bogus_count = icc[..132].to_vec();
+        bogus_count[128..].copy_from_slice(&u32::MAX.to_be_bytes());
+        assert_eq!(crate::icc::identify_profile(&bogus_count), None);
+        
+        let dir = TempDir::new("icc");
+        let input = dir.join("p3.png");
+        let mut info = png::Info::with_size(24, 24);
+        info.color_type = png::ColorType::Rgba;
+        info.bit_depth = png::BitDepth::Eight;
+        info.icc_profile = Some(std::borrow::Cow::Owned(icc));
+        let file = std::io::BufWriter::new(std::fs::File::create(&input).unwrap());
+        let mut writer = png::Encoder::with_info(file, info).unwrap().write_header().unwrap();
+        writer.write_image_data(&[200, 80, 60, 255].repeat(24 * 24)).unwrap();
+        writer.finish().unwrap();
+        
+        let sample = |assume_srgb: bool| {
+            let config = PixelatorConfig::new(10.0, 2.0).unwrap().with_assume_srgb(assume_srgb);
+            Pixelator::new(config).sample(&input).unwrap().dots[0].color
+        };
+        let (converted, raw) = (sample(false), sample(true));
+        
+        // sRGB rendition of P3 (200, 80, 60) is about (216, 69, 50)
+        let reference = [216i32, 69, 50];
+        let distance = |c: Rgba<u8>| (0..3).map(|i| (c[i] as i32 - reference[i]).abs()).max().unwrap();
+        assert_eq!(raw, Rgba([200, 80, 60, 255]));
+        assert!(distance(converted) <= 1);
+        assert!(distance(converted) < distance(raw));
+    }
+    
+    #[test]
+    fn test_cancel_flag_aborts_processing() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+        
+        let dir = TempDir::new("cancel");
+        let input = dir.join("input.png");
+        RgbaImage::from_pixel(60, 40, Rgba([10, 120, 200, 255])).save(&input).unwrap();
+        
+        let pixelator = Pixelator::new(PixelatorConfig::default());
+        let cancel = AtomicBool::new(false);
+        let completed = pixelator.process_image_cancellable(&input, &cancel).unwrap();
+        assert_eq!(completed, pixelator.process_image(&input).unwrap());
+        
+        cancel.store(true, Ordering::Relaxed);
+        let result = pixelator.process_image_cancellable(&input, &cancel);
+        assert!(matches!(result, Err(crate::PixelatorError::Processing(ref msg)) if msg == "cancelled"));
+        
+        // The processor stops on its own when the flag is already set
+        let img = image::open(&input).unwrap();
+        let config = PixelatorConfig::default();
+        let sampled = ImageProcessor::new(&config).with_cancel_flag(&cancel).sample_image(&img);
+        assert!(matches!(sampled, Err(crate::PixelatorError::Processing(ref msg)) if msg == "cancelled"));
+    }
+    
+    #[test]
+    fn test_coverage_sampling_blends_boundary_pixels() {
+        // Black left half, white right half; the middle dot straddles the edge
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_fn(20, 4, |x, _| {
+            if x < 10 { Rgba([0, 0, 0, 255]) } else { Rgba([255, 255, 255, 255]) }
+        }));
+        let middle = |coverage: bool| {
+            let config = PixelatorConfig::new(4.0, 0.0).unwrap().with_coverage_sampling(coverage);
+            let pixels = ImageProcessor::new(&config).sample_image(&img).unwrap();
+            let dot = pixels.iter().find(|p| p.x == 10.0).unwrap();
+            dot.color[0] as i32
+        };
+        
+        // The dot is split exactly in half, so the ideal blend is mid gray
+        let (weighted, binary) = (middle(true), middle(false));
+        assert!((weighted - 128).abs() <= 2, "coverage-weighted value {}", weighted);
+        assert!((binary - 128).abs() > (weighted - 128).abs());
+    }
+    
+    #[test]
+    fn test_scanline_groups_alternate_direction() {
+        let config = PixelatorConfig::new(10.0, 2.0).unwrap().with_scanline_grouping(true);
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_pixel(48, 36, Rgba([40, 90, 140, 255])));
+        let pixels = ImageProcessor::new(&config).sample_image(&img).unwrap();
+        let svg = crate::svg_generator::SvgGenerator::new(&config).generate_svg(&pixels, 48, 36).unwrap();
+        
+        let groups: Vec<Vec<f32>> = svg
+            .split("<g class=\"scanline\">")
+            .skip(1)
+            .map(|group| attr_values(group.split("</g>").next().unwrap(), "cx"))
+            .collect();
+        assert_eq!(groups.len(), 3);
+        for (row, xs) in groups.iter().enumerate() {
+            assert_eq!(xs.len(), 4);
+            let ascending = xs.windows(2).all(|w| w[0] < w[1]);
+            let descending = xs.windows(2).all(|w| w[0] > w[1]);
+            assert!(if row % 2 == 0 { ascending } else { descending }, "row {} order {:?}", row, xs);
+        }
+    }
+    
+    #[test]
+    fn test_svg_render_to_writer_matches_string_output() {
+        use crate::Render;
+        
+        let dir = TempDir::new("writer");
+        let input = dir.join("input.png");
+        RgbaImage::from_pixel(36, 24, Rgba([200, 40, 90, 255])).save(&input).unwrap();
+        
+        let config = PixelatorConfig::default();
+        let pixelator = Pixelator::new(config.clone());
+        let mut buffer: Vec<u8> = Vec::new();
+        pixelator
+            .process_image_to_writer(&crate::SvgGenerator::new(&config), &input, &mut buffer)
+            .unwrap();
+        let expected = pixelator.process_image(&input).unwrap();
+        
+        // G-code goes through the same trait
+        let mut gcode: Vec<u8> = Vec::new();
+        pixelator.process_image_to_writer(&crate::GcodeGenerator::new(&config), &input, &mut gcode).unwrap();
+        
+        assert_eq!(String::from_utf8(buffer).unwrap(), expected);
+        assert!(String::from_utf8(gcode).unwrap().starts_with("; Generated by pixelator"));
+        
+        let pixels = vec![PixelData { x: 5.0, y: 5.0, color: Rgba([0, 0, 0, 255]), brightness: 0.0, dot_size: 10.0, grid_position: None }];
+        let mut direct = Vec::new();
+        crate::SvgGenerator::new(&config).render(&mut direct, &pixels, 10, 10).unwrap();
+        assert!(String::from_utf8(direct).unwrap().contains("<circle"));
+    }
+    
+    #[test]
+    fn test_structure_hatching_follows_diagonal_edge() {
+        use crate::config::{HatchOrientation, RenderMode};
+        
+        // Dark above the anti-diagonal, light below it: the edge runs at 135
+        // degrees, unlike the 45 degrees used in flat areas
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_fn(120, 120, |x, y| {
+            if x + y < 120 { Rgba([0, 0, 0, 255]) } else { Rgba([255, 255, 255, 255]) }
+        }));
+        let config = PixelatorConfig::new(10.0, 0.0)
+            .unwrap()
+            .with_render_mode(RenderMode::Hatch(HatchOrientation::FollowStructure));
+        let pixels = ImageProcessor::new(&config).sample_image(&img).unwrap();
+        let svg = crate::svg_generator::SvgGenerator::new(&config).generate_svg(&pixels, 120, 120).unwrap();
+        
+        let (x1, y1) = (attr_values(&svg, "x1"), attr_values(&svg, "y1"));
+        let (x2, y2) = (attr_values(&svg, "x2"), attr_values(&svg, "y2"));
+        let (mut near_edge, mut flat) = (0, 0);
+        for i in 0..x1.len() {
+            let (mid_x, mid_y) = ((x1[i] + x2[i]) / 2.0, (y1[i] + y2[i]) / 2.0);
+            let angle = (y2[i] - y1[i]).atan2(x2[i] - x1[i]).to_degrees().rem_euclid(180.0);
+            let from_edge = (mid_x + mid_y - 120.0).abs();
+            if from_edge <= 10.0 {
+                near_edge += 1;
+                assert!((angle - 135.0).abs() < 10.0, "hatch at ({}, {}) runs at {} degrees", mid_x, mid_y, angle);
+            } else if from_edge > 40.0 {
+                flat += 1;
+                assert!((angle - 45.0).abs() < 1e-3, "flat hatch at ({}, {}) runs at {} degrees", mid_x, mid_y, angle);
+            }
+        }
+        assert!(near_edge > 0);
+        assert!(flat > 0);
+        
+        // A fixed orientation ignores the edge
+        let fixed = config.clone().with_render_mode(RenderMode::Hatch(HatchOrientation::Fixed(0.0)));
+        let svg = crate::svg_generator::SvgGenerator::new(&fixed).generate_svg(&pixels, 120, 120).unwrap();
+        assert_eq!(attr_values(&svg, "y1"), attr_values(&svg, "y2"));
+    }
+    
+    #[test]
+    fn test_mm_dimensions_rounded_to_two_decimals() {
+        let config = PixelatorConfig::default().with_output_dimensions(149.99997, 100.0 / 3.0).unwrap();
+        let pixels = vec![PixelData { x: 5.0, y: 5.0, color: Rgba([0, 0, 0, 255]), brightness: 0.0, dot_size: 10.0, grid_position: None }];
+        let svg = crate::svg_generator::SvgGenerator::new(&config).generate_svg(&pixels, 30, 20).unwrap();
+        
+        let attr = |name: &str| svg.split(&format!(" {}=\"", name)).nth(1).unwrap().split('"').next().unwrap().to_string();
+        assert_eq!(attr("width"), "150mm");
+        assert_eq!(attr("height"), "33.33mm");
+        let decimals = attr("height").trim_end_matches("mm").split('.').nth(1).map_or(0, str::len);
+        assert!(decimals <= 2);
+        
+        let precise = config.with_mm_decimals(4);
+        let svg = crate::svg_generator::SvgGenerator::new(&precise).generate_svg(&pixels, 30, 20).unwrap();
+        assert!(svg.contains("height=\"33.3333mm\""));
+    }
+    
+    #[test]
+    fn test_point_cloud_emits_bare_markers() {
+        let config = PixelatorConfig::new(10.0, 2.0)
+            .unwrap()
+            .with_background_color("white".to_string())
+            .with_render_mode(crate::config::RenderMode::PointCloud);
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_pixel(48, 36, Rgba([200, 30, 30, 255])));
+        let pixels = ImageProcessor::new(&config).sample_image(&img).unwrap();
+        let svg = crate::svg_generator::SvgGenerator::new(&config).generate_svg(&pixels, 48, 36).unwrap();
+        
+        assert_eq!(svg.matches("<circle").count(), pixels.len());
+        assert_eq!(attr_values(&svg, "r"), vec![0.1; pixels.len()]);
+        assert!(!svg.contains("fill"));
+        assert!(!svg.contains("rgb("));
+        assert!(!svg.contains("background"));
+    }
+    
+    #[test]
+    fn test_lpi_sets_total_spacing_from_output_size() {
+        assert!(PixelatorConfig::default().with_lpi(50.0).is_err());
+        
+        // 50 lines per inch over 254 mm (10 inches) is 500 lines across 1000 px
+        let config = PixelatorConfig::new(1.5, 3.0)
+            .unwrap()
+            .with_output_dimensions(254.0, 127.0)
+            .unwrap()
+            .with_lpi(50.0)
+            .unwrap();
+        let resolved = config.resolve_for_image(1000, 500);
+        assert!((resolved.get_total_spacing() - 2.0).abs() < 1e-4);
+        assert_eq!(resolved.circle_diameter, 1.5);
+        assert_eq!(resolved.dot_count(1000, 500), 500 * 250);
+    }
+    
+    #[test]
+    fn test_dot_size_normalization_spans_full_range() {
+        use crate::config::{HalftoneStyle, RenderMode};
+        
+        // A low-contrast gradient between two mid grays
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_fn(120, 24, |x, _| {
+            let v = 110 + (x / 8) as u8;
+            Rgba([v, v, v, 255])
+        }));
+        let sizes = |normalize: bool| {
+            let config = PixelatorConfig::new(10.0, 2.0)
+                .unwrap()
+                .with_halftone_range(1.0, 10.0)
+                .unwrap()
+                .with_render_mode(RenderMode::Halftone(HalftoneStyle::BlackOnWhite))
+                .with_dot_size_normalization(normalize);
+            let pixels = ImageProcessor::new(&config).sample_image(&img).unwrap();
+            let sizes: Vec<f32> = pixels.iter().map(|p| p.dot_size).collect();
+            let low = sizes.iter().cloned().fold(f32::MAX, f32::min);
+            let high = sizes.iter().cloned().fold(f32::MIN, f32::max);
+            (low, high)
+        };
+        
+        let (low, high) = sizes(false);
+        assert!(high - low < 2.0);
+        let (low, high) = sizes(true);
+        assert!((low - 1.0).abs() < 1e-4 && (high - 10.0).abs() < 1e-4);
+    }
+    
+    #[test]
+    fn test_tile_image_placed_once_per_cell() {
+        let config = PixelatorConfig::new(10.0, 2.0).unwrap().with_tile_image("sprite.png");
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_fn(48, 24, |x, _| {
+            if x < 24 { Rgba([255, 0, 0, 255]) } else { Rgba([0, 0, 255, 255]) }
+        }));
+        let pixels = ImageProcessor::new(&config).sample_image(&img).unwrap();
+        let svg = crate::svg_generator::SvgGenerator::new(&config).generate_svg(&pixels, 48, 24).unwrap();
+        
+        // One embedded sprite, instanced once per cell
+        assert_eq!(svg.matches("<image").count(), 1);
+        assert!(svg.contains("href=\"sprite.png\""));
+        assert_eq!(svg.matches("<use").count(), pixels.len());
+        assert!(!svg.contains("<circle"));
+        
+        // One tint filter per distinct color
+        assert_eq!(svg.matches("<feColorMatrix").count(), 2);
+        assert_eq!(svg.matches("filter=\"url(#tint0)\"").count(), 4);
+        assert_eq!(svg.matches("filter=\"url(#tint1)\"").count(), 4);
+    }
+    
+    #[test]
+    fn test_edge_mode_skip_drops_overhanging_dots() {
+        use crate::config::{EdgeMode, GridFit};
+        
+        // A red border around a white interior
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_fn(50, 26, |x, y| {
+            if x < 2 || y < 2 || x >= 48 || y >= 24 { Rgba([255, 0, 0, 255]) } else { Rgba([255, 255, 255, 255]) }
+        }));
+        let sample = |mode: EdgeMode| {
+            let config = PixelatorConfig::new(10.0, 2.0).unwrap().with_grid_fit(GridFit::Ceil).with_edge_mode(mode);
+            ImageProcessor::new(&config).sample_image(&img).unwrap()
+        };
+        
+        let clamped = sample(EdgeMode::Clamp);
+        assert_eq!(clamped.len(), 5 * 3);
+        
+        // The last column and row overhang the border and are removed
+        let skipped = sample(EdgeMode::Skip);
+        assert_eq!(skipped.len(), 4 * 2);
+        assert!(skipped.iter().all(|p| p.grid_position.is_some_and(|(col, row)| col < 4 && row < 2)));
+        
+        // Mirroring sees a full window instead of one cut at the border
+        let mirrored = sample(EdgeMode::Mirror);
+        let corner = |pixels: &[PixelData]| pixels.iter().find(|p| p.grid_position == Some((4, 2))).unwrap().color;
+        assert_ne!(corner(&mirrored), corner(&clamped));
+    }
+    
+    #[test]
+    fn test_contours_trace_concentric_levels() {
+        // Dark center fading to white at the corners
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_fn(120, 120, |x, y| {
+            let distance = ((x as f32 - 60.0).powi(2) + (y as f32 - 60.0).powi(2)).sqrt();
+            let v = (distance / 85.0 * 255.0).min(255.0) as u8;
+            Rgba([v, v, v, 255])
+        }));
+        let config = PixelatorConfig::new(4.0, 0.0)
+            .unwrap()
+            .with_render_mode(crate::config::RenderMode::Contours { levels: 4 });
+        let pixels = ImageProcessor::new(&config).sample_image(&img).unwrap();
+        let svg = crate::svg_generator::SvgGenerator::new(&config).generate_svg(&pixels, 120, 120).unwrap();
+        
+        // One group and one path per level
+        assert_eq!(svg.matches("class=\"contour-level\"").count(), 4);
+        assert_eq!(svg.matches("<path").count(), 4);
+        
+        // Each level is a single ring around the center, inside the one before
+        let mut previous_width = f32::INFINITY;
+        for path in svg.split("<path").skip(1) {
+            let d = path.split("d=\"").nth(1).unwrap().split('"').next().unwrap();
+            assert_eq!(d.matches('M').count(), 1);
+            let xs: Vec<f32> = d
+                .split([' ', 'M', 'L', 'Z'])
+                .filter_map(|point| point.split(',').next()?.parse().ok())
+                .collect();
+            let (min, max) = xs.iter().fold((f32::MAX, f32::MIN), |(lo, hi), &x| (lo.min(x), hi.max(x)));
+            assert!(((min + max) / 2.0 - 60.0).abs() < 3.0);
+            assert!(max - min < previous_width);
+            previous_width = max - min;
+        }
+    }
+    
+    #[test]
+    fn test_svg_y_up_wraps_content_in_flip_group() {
+        // Black top row, white below
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_fn(40, 40, |_, y| {
+            if y < 10 { Rgba([0, 0, 0, 255]) } else { Rgba([255, 255, 255, 255]) }
+        }));
+        let config = PixelatorConfig::new(10.0, 0.0)
+            .unwrap()
+            .with_render_mode(crate::config::RenderMode::Halftone(crate::config::HalftoneStyle::BlackOnWhite))
+            .with_svg_y_up(true);
+        let pixels = ImageProcessor::new(&config).sample_image(&img).unwrap();
+        let svg = crate::svg_generator::SvgGenerator::new(&config).generate_svg(&pixels, 40, 40).unwrap();
+        
+        assert!(svg.contains("transform=\"translate(0,40) scale(1,-1)\""));
+        
+        // The large dots of the black top row sit near y = 40 in y-up
+        // coordinates, which the wrapper maps back to the top of the display
+        let ys = attr_values(&svg, "cy");
+        let radii = attr_values(&svg, "r");
+        let largest = radii.iter().cloned().fold(0.0, f32::max);
+        let top_row: Vec<f32> = ys.iter().zip(&radii).filter(|(_, &r)| r == largest).map(|(&y, _)| y).collect();
+        assert_eq!(top_row.len(), 4);
+        for y in top_row {
+            assert_eq!(y, 35.0);
+            assert_eq!(40.0 - y, 5.0);
+        }
+        
+        let plain = PixelatorConfig::new(10.0, 0.0).unwrap();
+        let svg = crate::svg_generator::SvgGenerator::new(&plain).generate_svg(&pixels, 40, 40).unwrap();
+        assert!(!svg.contains("scale(1,-1)"));
+    }
+    
+    #[test]
+    fn test_failed_write_leaves_no_partial_output() {
+        use std::io::Write;
+        
+        // Accepts a little data, then fails like a full disk
+        struct FailingWriter<'a> {
+            inner: &'a mut dyn Write,
+            remaining: usize,
+        }
+        impl Write for FailingWriter<'_> {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                if self.remaining == 0 {
+                    return Err(std::io::Error::other("no space left on device"));
+                }
+                let n = buf.len().min(self.remaining);
+                self.remaining -= n;
+                self.inner.write(&buf[..n])
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                self.inner.flush()
+            }
+        }
+        
+        let dir = TempDir::new("atomic");
+        let output = dir.join("out.svg");
+        
+        let result = crate::write_atomically(&output, |writer| {
+            let mut failing = FailingWriter { inner: writer, remaining: 64 };
+            failing.write_all(&[b'x'; 1024])?;
+            Ok(())
+        });
+        assert!(result.is_err());
+        
+        // Neither the destination nor the temporary file is left behind
+        assert!(!output.exists());
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+        
+        // A successful write lands in place
+        crate::write_atomically(&output, |writer| Ok(writer.write_all(b"<svg/>")?)).unwrap();
+        assert_eq!(std::fs::read_to_string(&output).unwrap(), "<svg/>");
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+    }
+    
+    #[test]
+    fn test_min_visible_radius_clamps_color_dots() {
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_pixel(20, 20, Rgba([200, 40, 40, 255])));
+        let config = PixelatorConfig::new(1.0, 9.0).unwrap().with_min_visible_radius(1.5).unwrap();
+        let pixels = ImageProcessor::new(&config).sample_image(&img).unwrap();
+        let svg = crate::svg_generator::SvgGenerator::new(&config).generate_svg(&pixels, 20, 20).unwrap();
+        
+        // A 1px diameter would give r = 0.5; every dot is raised to the floor
+        let radii = attr_values(&svg, "r");
+        assert!(!radii.is_empty());
+        assert!(radii.iter().all(|&r| r == 1.5));
+        
+        // Radii already above the floor are kept
+        let config = PixelatorConfig::new(8.0, 2.0).unwrap().with_min_visible_radius(1.5).unwrap();
+        let pixels = ImageProcessor::new(&config).sample_image(&img).unwrap();
+        let svg = crate::svg_generator::SvgGenerator::new(&config).generate_svg(&pixels, 20, 20).unwrap();
+        assert!(attr_values(&svg, "r").iter().all(|&r| r == 4.0));
+        
+        assert!(PixelatorConfig::default().with_min_visible_radius(-1.0).is_err());
+    }
+    
+    #[test]
+    fn test_glow_filter_applied_to_dot_group() {
+        use crate::config::{HalftoneStyle, RenderMode};
+        
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_pixel(20, 20, Rgba([240, 240, 240, 255])));
+        let config = PixelatorConfig::new(4.0, 1.0)
+            .unwrap()
+            .with_render_mode(RenderMode::Halftone(HalftoneStyle::WhiteOnBlack))
+            .with_glow(2.0)
+            .unwrap();
+        let pixels = ImageProcessor::new(&config).sample_image(&img).unwrap();
+        let svg = crate::svg_generator::SvgGenerator::new(&config).generate_svg(&pixels, 20, 20).unwrap();
+        
+        // One shared filter, referenced once by the group holding every dot
+        assert_eq!(svg.matches("<filter").count(), 1);
+        assert!(svg.contains("<filter id=\"glow\">"));
+        assert!(svg.contains("<feGaussianBlur in=\"SourceGraphic\" result=\"blur\" stdDeviation=\"2\"/>"));
+        assert_eq!(svg.matches("filter=\"url(#glow)\"").count(), 1);
+        let group = svg.split("<g class=\"glow\" filter=\"url(#glow)\">").nth(1).unwrap();
+        assert_eq!(group.matches("<circle").count(), pixels.len());
+        
+        // Modes drawing dark marks don't glow
+        let config = config.with_render_mode(RenderMode::Halftone(HalftoneStyle::BlackOnWhite));
+        let svg = crate::svg_generator::SvgGenerator::new(&config).generate_svg(&pixels, 20, 20).unwrap();
+        assert!(!svg.contains("<filter"));
+    }
+    
+    #[test]
+    fn test_fit_to_page_centers_without_distortion() {
+        // Landscape image on a portrait A-ish page
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_pixel(60, 30, Rgba([0, 0, 0, 255])));
+        let config = PixelatorConfig::new(5.0, 1.0).unwrap().with_fit_to_page(100.0, 150.0, 10.0).unwrap();
+        let resolved = config.resolve_for_image(60, 30);
+        
+        // Width fills the space inside the margins; height keeps the 2:1 ratio
+        assert_eq!(resolved.output_width_mm, Some(80.0));
+        assert_eq!(resolved.output_height_mm, Some(40.0));
+        
+        let pixels = ImageProcessor::new(&resolved).sample_image(&img).unwrap();
+        let svg = crate::svg_generator::SvgGenerator::new(&resolved).generate_svg(&pixels, 60, 30).unwrap();
+        assert!(svg.contains("width=\"100mm\""));
+        assert!(svg.contains("height=\"150mm\""));
+        
+        // 10mm side margins and (150 - 40) / 2 = 55mm above and below, in
+        // viewBox units of 0.75 pixels per mm: the same scale on both axes
+        assert!(svg.contains("viewBox=\"-7.5 -41.25 75 112.5\""));
+        
+        assert!(PixelatorConfig::default().with_fit_to_page(100.0, 150.0, 50.0).is_err());
+    }
+    
+    #[test]
+    fn test_output_digest_is_stable_and_config_sensitive() {
+        let dir = TempDir::new("digest");
+        let input = dir.join("input.png");
+        let img = RgbaImage::from_fn(40, 30, |x, y| Rgba([(x * 6) as u8, (y * 8) as u8, 120, 255]));
+        img.save(&input).unwrap();
+        
+        let pixelator = Pixelator::new(PixelatorConfig::new(6.0, 1.0).unwrap());
+        let first = pixelator.output_digest(&input).unwrap();
+        let second = pixelator.output_digest(&input).unwrap();
+        let changed = Pixelator::new(PixelatorConfig::new(6.0, 2.0).unwrap()).output_digest(&input).unwrap();
+        
+        assert_eq!(first.len(), 64);
+        assert!(first.chars().all(|c| c.is_ascii_hexdigit()));
+        assert_eq!(first, second);
+        assert_ne!(first, changed);
+    }
+    
+    #[test]
+    fn test_background_gradient_fills_background_rect() {
+        let stops = vec![(0.0, "skyblue".to_string()), (0.6, "white".to_string()), (1.0, "#8b4513".to_string())];
+        let config = PixelatorConfig::new(5.0, 1.0).unwrap().with_background_gradient(stops, 90.0).unwrap();
+        let pixels = vec![PixelData { x: 3.0, y: 3.0, color: Rgba([0, 0, 0, 255]), brightness: 0.0, dot_size: 5.0, grid_position: None }];
+        let svg = crate::svg_generator::SvgGenerator::new(&config).generate_svg(&pixels, 20, 10).unwrap();
+        
+        // Top to bottom, with each stop in order
+        assert!(svg.contains("<linearGradient id=\"background-gradient\" x1=\"0.5\" x2=\"0.5\" y1=\"0\" y2=\"1\">"));
+        let stops: Vec<&str> = svg.match_indices("<stop").map(|(i, _)| &svg[i..i + svg[i..].find("/>").unwrap()]).collect();
+        assert_eq!(stops.len(), 3);
+        assert!(stops[0].contains("offset=\"0\"") && stops[0].contains("stop-color=\"skyblue\""));
+        assert!(stops[1].contains("offset=\"0.6\"") && stops[1].contains("stop-color=\"white\""));
+        assert!(stops[2].contains("offset=\"1\"") && stops[2].contains("stop-color=\"#8b4513\""));
+        
+        // The background rect references it, below the dots
+        let rect = svg.find("<rect fill=\"url(#background-gradient)\" height=\"10\" width=\"20\" x=\"0\" y=\"0\"/>").unwrap();
+        assert!(rect < svg.find("<circle").unwrap());
+        
+        // Offsets must be in range and sorted
+        let unsorted = vec![(0.8, "red".to_string()), (0.2, "blue".to_string())];
+        assert!(PixelatorConfig::default().with_background_gradient(unsorted, 0.0).is_err());
+        let out_of_range = vec![(0.0, "red".to_string()), (1.5, "blue".to_string())];
+        assert!(PixelatorConfig::default().with_background_gradient(out_of_range, 0.0).is_err());
+    }
+    
+    #[test]
+    fn test_sample_iter_matches_sample_image() {
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_fn(57, 43, |x, y| {
+            Rgba([(x * 4) as u8, (y * 5) as u8, ((x + y) * 2) as u8, 255])
+        }));
+        let configs = [
+            PixelatorConfig::new(6.0, 1.5).unwrap(),
+            PixelatorConfig::new(5.0, 2.0).unwrap().with_sample_mode(SampleMode::Hexagonal),
+            PixelatorConfig::new(6.0, 1.0).unwrap().with_jitter(1.5).unwrap().with_seed(7),
+        ];
+        for config in &configs {
+            let processor = ImageProcessor::new(config);
+            let collected = processor.sample_image(&img).unwrap();
+            let streamed: Vec<PixelData> = processor.sample_iter(&img).unwrap().collect();
+            
+            assert_eq!(streamed.len(), collected.len());
+            for (a, b) in streamed.iter().zip(&collected) {
+                assert_eq!((a.x, a.y, a.color, a.dot_size, a.grid_position), (b.x, b.y, b.color, b.dot_size, b.grid_position));
+            }
+        }
+        
+        // Whole-image passes can't be streamed
+        let config = PixelatorConfig::new(6.0, 1.5).unwrap().with_palette_size(4).unwrap();
+        assert!(ImageProcessor::new(&config).sample_iter(&img).is_err());
+    }
+    
+    #[test]
+    fn test_streamed_svg_matches_generate_svg() {
+        use crate::config::{RenderMode, HalftoneStyle};
+        use crate::svg_generator::SvgGenerator;
+        
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_fn(57, 43, |x, y| {
+            Rgba([(x * 4) as u8, (y * 5) as u8, ((x + y) * 2) as u8, 255])
+        }));
+        let config = PixelatorConfig::new(6.0, 1.5).unwrap();
+        let processor = ImageProcessor::new(&config);
+        let generator = SvgGenerator::new(&config);
+        let expected = generator.generate_svg(&processor.sample_image(&img).unwrap(), 57, 43).unwrap();
+        let mut streamed = Vec::new();
+        generator.write_svg_streamed(&mut streamed, processor.sample_iter(&img).unwrap(), 57, 43).unwrap();
+        assert_eq!(String::from_utf8(streamed).unwrap(), expected);
+        
+        // Halftone output isn't streamed
+        let halftone = PixelatorConfig::new(6.0, 1.5).unwrap().with_render_mode(RenderMode::Halftone(HalftoneStyle::BlackOnWhite));
+        let processor = ImageProcessor::new(&halftone);
+        let pixels = processor.sample_iter(&img).unwrap();
+        let result = SvgGenerator::new(&halftone).write_svg_streamed(&mut Vec::new(), pixels, 57, 43);
+        assert!(matches!(result, Err(crate::PixelatorError::InvalidConfig(_))));
+    }
+    
+    #[test]
+    fn test_saturation_scale_mutes_and_boosts_colors() {
+        // A muted teal
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_pixel(24, 24, Rgba([90, 140, 130, 255])));
+        let spread = |scale: f32| {
+            let config = PixelatorConfig::new(6.0, 2.0).unwrap().with_saturation_scale(scale).unwrap();
+            let pixels = ImageProcessor::new(&config).sample_image(&img).unwrap();
+            pixels
+                .iter()
+                .map(|p| p.color[0].max(p.color[1]).max(p.color[2]) - p.color[0].min(p.color[1]).min(p.color[2]))
+                .max()
+                .unwrap()
+        };
+        
+        assert_eq!(spread(0.0), 0);
+        assert_eq!(spread(1.0), 50);
+        assert!(spread(2.0) > 90);
+        
+        assert!(PixelatorConfig::default().with_saturation_scale(-0.5).is_err());
+    }
+    
+    #[test]
+    fn test_html_viewer_embeds_svg() {
+        let dir = TempDir::new("html");
+        let input = dir.join("input.png");
+        RgbaImage::from_pixel(30, 20, Rgba([10, 120, 200, 255])).save(&input).unwrap();
+        let output = dir.join("viewer.html");
+        
+        let pixelator = Pixelator::new(PixelatorConfig::new(5.0, 1.0).unwrap());
+        pixelator.process_image_to_html(&input, &output).unwrap();
+        let html = std::fs::read_to_string(&output).unwrap();
+        let svg = pixelator.process_image(&input).unwrap();
+        
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains(&svg));
+        assert_eq!(html.matches("<svg").count(), 1);
+        assert!(html.contains("<script id=\"pixelator-viewer\">"));
+        assert!(html.find("<svg").unwrap() < html.find("<script").unwrap());
+    }
+    
+    #[test]
+    fn test_flat_top_hexagons_stagger_columns() {
+        use crate::config::HexOrientation;
+        
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_pixel(60, 40, Rgba([100, 100, 100, 255])));
+        let sample = |orientation: HexOrientation| {
+            let config = PixelatorConfig::new(6.0, 2.0)
+                .unwrap()
+                .with_sample_mode(SampleMode::Hexagonal)
+                .with_hex_orientation(orientation);
+            ImageProcessor::new(&config).sample_image(&img).unwrap()
+        };
+        let at = |pixels: &[PixelData], position: (usize, usize)| {
+            let p = pixels.iter().find(|p| p.grid_position == Some(position)).unwrap();
+            (p.x, p.y)
+        };
+        
+        // Pointy-top: row 1 is shifted right by half a cell, rows 8 * 0.866 apart
+        let pointy = sample(HexOrientation::PointyTop);
+        assert_eq!(at(&pointy, (0, 0)), (3.0, 3.0));
+        assert_eq!(at(&pointy, (0, 1)), (7.0, 3.0 + 6.928));
+        assert_eq!(at(&pointy, (1, 0)), (11.0, 3.0));
+        
+        // Flat-top: column 1 is shifted down by half a cell, columns 8 * 0.866 apart
+        let flat = sample(HexOrientation::FlatTop);
+        assert_eq!(at(&flat, (0, 0)), (3.0, 3.0));
+        assert_eq!(at(&flat, (1, 0)), (3.0 + 6.928, 7.0));
+        assert_eq!(at(&flat, (0, 1)), (3.0, 11.0));
+        
+        // Columns of a flat-top pattern each share one x coordinate; the
+        // ninth is centered at 58.4, inside the 60 pixel width
+        let xs: std::collections::BTreeSet<u32> = flat.iter().map(|p| (p.x * 1000.0) as u32).collect();
+        assert_eq!(xs.len(), 9);
+        // Both orientations happen to give 45 dots here, at different positions
+        let positions = |pixels: &[PixelData]| -> std::collections::BTreeSet<(u32, u32)> {
+            pixels.iter().map(|p| ((p.x * 1000.0) as u32, (p.y * 1000.0) as u32)).collect()
+        };
+        assert_ne!(positions(&pointy), positions(&flat));
+    }
+    
+    #[test]
+    fn test_dot_gain_shrinks_midtones_most() {
+        use crate::config::{HalftoneStyle, RenderMode};
+        
+        // Highlight, midtone and shadow stripes
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_fn(30, 10, |x, _| match x / 10 {
+            0 => Rgba([235, 235, 235, 255]),
+            1 => Rgba([128, 128, 128, 255]),
+            _ => Rgba([20, 20, 20, 255]),
+        }));
+        let sizes = |gain: Option<f32>| {
+            let mut config = PixelatorConfig::new(10.0, 0.0)
+                .unwrap()
+                .with_render_mode(RenderMode::Halftone(HalftoneStyle::BlackOnWhite))
+                .with_halftone_range(0.5, 11.2)
+                .unwrap();
+            if let Some(percent) = gain {
+                config = config.with_dot_gain(percent).unwrap();
+            }
+            let pixels = ImageProcessor::new(&config).sample_image(&img).unwrap();
+            pixels.iter().map(|p| p.dot_size).collect::<Vec<f32>>()
+        };
+        
+        let plain = sizes(None);
+        let compensated = sizes(Some(15.0));
+        let shrink: Vec<f32> = plain.iter().zip(&compensated).map(|(a, b)| a - b).collect();
+        assert!(shrink.iter().all(|&s| s >= 0.0));
+        assert!(shrink[1] > shrink[0]);
+        assert!(shrink[1] > shrink[2]);
+        
+        assert!(PixelatorConfig::default().with_dot_gain(60.0).is_err());
+    }
+    
+    #[test]
+    fn test_source_dpi_sets_pixel_dot_size() {
+        let dir = TempDir::new("dpi");
+        let input = dir.join("tagged.png");
+        
+        // A 120x120 image tagged at 254 DPI (10000 pixels per meter, 10 per mm)
+        let file = std::fs::File::create(&input).unwrap();
+        let mut encoder = png::Encoder::new(std::io::BufWriter::new(file), 120, 120);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.set_pixel_dims(Some(png::PixelDimensions { xppu: 10000, yppu: 10000, unit: png::Unit::Meter }));
+        encoder.write_header().unwrap().write_image_data(&[90u8; 120 * 120 * 3]).unwrap();
+        
+        let dpi = crate::dpi::read_dpi(&input).unwrap();
+        assert!((dpi - 254.0).abs() < 0.01);
+        
+        let config = PixelatorConfig::new(4.0, 1.0)
+            .unwrap()
+            .with_physical_dot_size(1.5, 0.5)
+            .unwrap()
+            .with_respect_source_dpi(true);
+        let pixelator = Pixelator::new(config);
+        let dots = pixelator.sample(&input).unwrap();
+        assert!(pixelator.take_warnings().is_empty());
+        
+        // 1.5mm at 10 pixels per mm, with 0.5mm spacing: a 20 pixel pitch
+        assert!((dots.config.circle_diameter - 15.0).abs() < 0.01);
+        assert!((dots.config.circle_spacing - 5.0).abs() < 0.01);
+        assert_eq!(dots.dots.len(), 6 * 6);
+        
+        // Without DPI metadata the pixel sizes are kept and the fallback reported
+        let untagged = dir.join("untagged.png");
+        RgbaImage::from_pixel(40, 40, Rgba([90, 90, 90, 255])).save(&untagged).unwrap();
+        let dots = pixelator.sample(&untagged).unwrap();
+        assert_eq!(dots.config.circle_diameter, 4.0);
+        let warnings = pixelator.take_warnings();
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("no DPI metadata"));
+    }
+    
+    #[test]
+    fn test_palette_dither_mixes_fixed_colors_on_gradient() {
+        // Horizontal gray gradient, one dot per 4x4 cell
+        let img = DynamicImage::ImageRgba8(image::ImageBuffer::from_fn(128, 32, |x, _| {
+            let v = (x * 255 / 127) as u8;
+            Rgba([v, v, v, 255])
+        }));
+        let palette = vec![
+            Rgba([0, 0, 0, 255]),
+            Rgba([85, 85, 85, 255]),
+            Rgba([170, 170, 170, 255]),
+            Rgba([255, 255, 255, 255]),
+        ];
+        let config = PixelatorConfig::new(4.0, 0.0)
+            .unwrap()
+            .with_palette_dither(palette.clone())
+            .unwrap();
+        let pixels = ImageProcessor::new(&config).sample_image(&img).unwrap();
+        
+        // Every dot lands on the palette and all four entries are used
+        for entry in &palette {
+            assert!(pixels.iter().any(|p| p.color == *entry));
+        }
+        assert!(pixels.iter().all(|p| palette.contains(&p.color)));
+        
+        // Between two palette levels a column mixes both neighbors rather
+        // than banding to the nearest one
+        let column: Vec<Rgba<u8>> = pixels
+            .iter()
+            .filter(|p| p.grid_position.map(|(col, _)| col) == Some(6))
+            .map(|p| p.color)
+            .collect();
+        assert!(column.contains(&palette[0]) && column.contains(&palette[1]));
+        
+        assert!(PixelatorConfig::default().with_palette_dither(Vec::new()).is_err());
+    }
+    
+    #[test]
+    fn test_build_document_allows_extra_elements() {
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_pixel(40, 40, Rgba([128, 128, 128, 255])));
+        let config = PixelatorConfig::new(8.0, 2.0).unwrap();
+        let pixels = ImageProcessor::new(&config).sample_image(&img).unwrap();
+        let generator = crate::svg_generator::SvgGenerator::new(&config);
+        
+        let plain = generator.generate_svg(&pixels, 40, 40).unwrap();
+        assert_eq!(generator.build_document(&pixels, 40, 40).unwrap().to_string(), plain);
+        
+        let watermark = svg::node::element::Text::new()
+            .set("class", "watermark")
+            .add(svg::node::Text::new("pixelator"));
+        let document = generator.build_document(&pixels, 40, 40).unwrap().add(watermark);
+        let output = document.to_string();
+        let start = output.find(r#"<text class="watermark">"#).unwrap();
+        assert!(output[start..].contains("pixelator"));
+        assert_eq!(output.matches("<circle").count(), plain.matches("<circle").count());
+    }
+    
+    #[test]
+    fn test_color_merge_tolerance_collapses_near_colors() {
+        let dot = |x: f32, color: Rgba<u8>| PixelData {
+            x,
+            y: 5.0,
+            color,
+            brightness: 0.5,
+            dot_size: 8.0,
+            grid_position: None,
+        };
+        let pixels = vec![
+            dot(5.0, Rgba([100, 150, 200, 255])),
+            dot(15.0, Rgba([102, 149, 203, 255])),
+            dot(25.0, Rgba([10, 20, 30, 255])),
+        ];
+        let fills = |config: &PixelatorConfig| {
+            let svg = crate::svg_generator::SvgGenerator::new(config).generate_svg(&pixels, 30, 10).unwrap();
+            let mut fills: Vec<String> = svg
+                .split(r#"fill="rgb("#)
+                .skip(1)
+                .map(|rest| format!("rgb({}", &rest[..rest.find('"').unwrap()]))
+                .collect();
+            fills.sort();
+            fills.dedup();
+            fills
+        };
+        
+        let exact = PixelatorConfig::new(8.0, 2.0).unwrap();
+        assert_eq!(fills(&exact).len(), 3);
+        
+        let merged = exact.clone().with_color_merge_tolerance(5);
+        assert_eq!(fills(&merged), vec!["rgb(10,20,30)".to_string(), "rgb(100,150,200)".to_string()]);
+        assert_eq!(fills(&merged.with_path_merge_by_color(true)).len(), 2);
+    }
+    
+    #[test]
+    fn test_outline_only_strokes_unfilled_dots() {
+        use crate::config::{RenderMode, HalftoneStyle};
+        
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_pixel(40, 40, Rgba([60, 90, 120, 255])));
+        let config = PixelatorConfig::new(8.0, 2.0).unwrap().with_outline_only(0.5).unwrap();
+        let pixels = ImageProcessor::new(&config).sample_image(&img).unwrap();
+        
+        let svg = crate::svg_generator::SvgGenerator::new(&config).generate_svg(&pixels, 40, 40).unwrap();
+        let circles: Vec<&str> = svg.split("<circle").skip(1).collect();
+        assert_eq!(circles.len(), pixels.len());
+        for circle in &circles {
+            assert!(circle.contains(r#"fill="none""#));
+            assert!(circle.contains(r#"stroke="rgb(60,90,120)""#));
+            assert!(circle.contains(r#"stroke-width="0.5""#));
+            assert!(!circle.contains("fill-opacity"));
+        }
+        
+        let halftone = config.clone().with_render_mode(RenderMode::Halftone(HalftoneStyle::BlackOnWhite));
+        let svg = crate::svg_generator::SvgGenerator::new(&halftone).generate_svg(&pixels, 40, 40).unwrap();
+        assert!(svg.contains(r#"fill="none""#) && svg.contains(r#"stroke="black""#));
+        
+        assert!(PixelatorConfig::default().with_outline_only(0.0).is_err());
+    }
+    
+    #[test]
+    fn test_edge_fade_tapers_border_dots() {
+        use crate::config::{RenderMode, HalftoneStyle};
+        
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_pixel(100, 100, Rgba([60, 60, 60, 255])));
+        let center = |p: &PixelData| (p.x - 50.0).abs() < 10.0 && (p.y - 50.0).abs() < 10.0;
+        let corner = |p: &PixelData| p.x < 10.0 && p.y < 10.0;
+        
+        let halftone = PixelatorConfig::new(8.0, 2.0)
+            .unwrap()
+            .with_render_mode(RenderMode::Halftone(HalftoneStyle::BlackOnWhite))
+            .with_edge_fade(20.0)
+            .unwrap();
+        let pixels = ImageProcessor::new(&halftone).sample_image(&img).unwrap();
+        let inner = pixels.iter().find(|p| center(p)).unwrap();
+        let outer = pixels.iter().find(|p| corner(p)).unwrap();
+        assert!(outer.dot_size < inner.dot_size);
+        assert!((outer.dot_size - inner.dot_size * 4.0 / 20.0).abs() < 1e-3);
+        
+        let color = PixelatorConfig::new(8.0, 2.0).unwrap().with_edge_fade(20.0).unwrap();
+        let pixels = ImageProcessor::new(&color).sample_image(&img).unwrap();
+        let inner = pixels.iter().find(|p| center(p)).unwrap();
+        let outer = pixels.iter().find(|p| corner(p)).unwrap();
+        assert_eq!(inner.color[3], 255);
+        assert!(outer.color[3] < inner.color[3]);
+        assert_eq!(outer.dot_size, inner.dot_size);
+    }
+    
+    #[test]
+    fn test_append_to_svg_fills_named_group() {
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_pixel(40, 40, Rgba([128, 128, 128, 255])));
+        let config = PixelatorConfig::new(8.0, 2.0).unwrap();
+        let pixels = ImageProcessor::new(&config).sample_image(&img).unwrap();
+        let generator = crate::svg_generator::SvgGenerator::new(&config);
+        
+        let template = r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 200 200">
+<rect id="frame" x="0" y="0" width="200" height="200" fill="none" stroke="black"/>
+<text x="10" y="190">Title</text>
+<g id="art" transform="translate(20,20)"><g class="nested"></g></g>
+<g id="after"></g>
+</svg>"#;
+        let merged = generator.append_to_svg(template, "art", &pixels, 40, 40).unwrap();
+        
+        let start = merged.find(r#"<g id="art""#).unwrap();
+        let end = merged.find(r#"<g id="after">"#).unwrap();
+        assert_eq!(merged[start..end].matches("<circle").count(), pixels.len());
+        assert_eq!(merged.matches("<circle").count(), pixels.len());
+        assert!(merged[..start].contains(r#"<rect id="frame""#));
+        assert!(merged.contains("<text x=\"10\" y=\"190\">Title</text>"));
+        let group = merged[start..end].trim_end();
+        assert!(group.find(r#"<g class="nested"></g>"#).unwrap() < group.find("<circle").unwrap());
+        assert!(group.ends_with("\n</g>"));
+        
+        // A self-closing group is reopened to hold the dots
+        let empty = r#"<svg xmlns="http://www.w3.org/2000/svg"><g id="art"/></svg>"#;
+        let merged = generator.append_to_svg(empty, "art", &pixels, 40, 40).unwrap();
+        assert!(merged.starts_with(r#"<svg xmlns="http://www.w3.org/2000/svg"><g id="art">"#));
+        assert!(merged.ends_with("</g></svg>"));
+        assert_eq!(merged.matches("<circle").count(), pixels.len());
+        
+        assert!(generator.append_to_svg(template, "missing", &pixels, 40, 40).is_err());
+        
+        // Comments, CDATA sections and attribute values don't count as the group
+        let tricky = r#"<svg xmlns="http://www.w3.org/2000/svg">
+<!-- <g id="art"></g> -->
+<style><![CDATA[ g > circle { stroke: none } /* <g id="art"> */ ]]></style>
+<g id="frame" data-note='a > b id="art"' data-id="art"></g>
+<g id='art'></g>
+</svg>"#;
+        let merged = generator.append_to_svg(tricky, "art", &pixels, 40, 40).unwrap();
+        let start = merged.find("<g id='art'>").unwrap();
+        assert_eq!(merged[start..].matches("<circle").count(), pixels.len());
+        assert!(merged[start..].trim_end().ends_with("</g>\n</svg>"));
+        let commented = r#"<svg xmlns="http://www.w3.org/2000/svg"><!-- <g id="art"/> --></svg>"#;
+        assert!(generator.append_to_svg(commented, "art", &pixels, 40, 40).is_err());
+    }
+    
+    #[test]
+    fn test_packed_color_cache_keeps_output_and_counts_colors() {
+        // Thousands of distinct colors, with rows repeating every 40 pixels
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_fn(200, 200, |x, y| {
+            Rgba([(x * 7 % 256) as u8, (y % 40 * 6) as u8, ((x + y) % 256) as u8, 255])
+        }));
+        let config = PixelatorConfig::new(2.0, 0.0).unwrap();
+        let pixels = ImageProcessor::new(&config).sample_image(&img).unwrap();
+        let svg = crate::svg_generator::SvgGenerator::new(&config).generate_svg(&pixels, 200, 200).unwrap();
+        
+        // Every dot is emitted with the exact rgb() fill of its color
+        let fills: Vec<&str> = svg
+            .split(r#"fill="rgb("#)
+            .skip(1)
+            .map(|rest| &rest[..rest.find('"').unwrap()])
+            .collect();
+        assert_eq!(fills.len(), pixels.len());
+        for (fill, pixel) in fills.iter().zip(&pixels) {
+            assert_eq!(*fill, format!("{},{},{})", pixel.color[0], pixel.color[1], pixel.color[2]));
+        }
+        
+        let distinct_colors: std::collections::HashSet<_> = pixels.iter().map(|p| (p.color[0], p.color[1], p.color[2])).collect();
+        let distinct_fills: std::collections::HashSet<_> = fills.iter().collect();
+        assert_eq!(distinct_fills.len(), distinct_colors.len());
+        assert!(distinct_colors.len() > 1000);
+        
+        // Benchmark-style: a million lookups over 65536 colors spread across
+        // every table page, in well under the budget even in debug builds
+        let mut cache = crate::svg_generator::ColorCache::default();
+        let start = std::time::Instant::now();
+        for i in 0..1u32 << 20 {
+            let key = (i.wrapping_mul(40_503) & 0xffff) as u16;
+            let [hi, lo] = key.to_be_bytes();
+            assert!(cache.fill((hi, lo, hi ^ lo)).starts_with("rgb("));
+        }
+        assert_eq!(cache.colors(), 1 << 16);
+        assert!(start.elapsed() < std::time::Duration::from_secs(5), "took {:?}", start.elapsed());
+    }
+    
+    #[test]
+    fn test_distortion_policy_rejects_mismatched_aspect_ratio() {
+        use crate::config::DistortionPolicy;
+        
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_pixel(100, 50, Rgba([128, 128, 128, 255])));
+        let base = PixelatorConfig::new(8.0, 2.0).unwrap();
+        // Library callers opt in to warnings
+        assert_eq!(base.distortion_policy, DistortionPolicy::Allow);
+        let pixels = ImageProcessor::new(&base).sample_image(&img).unwrap();
+        let generate = |config: &PixelatorConfig| {
+            crate::svg_generator::SvgGenerator::new(config).generate_svg(&pixels, 100, 50)
+        };
+        
+        let squashed = base.clone().with_output_dimensions(100.0, 100.0).unwrap();
+        let rejecting = squashed.clone().with_distortion_policy(DistortionPolicy::Reject);
+        assert!(matches!(generate(&rejecting), Err(crate::PixelatorError::InvalidConfig(_))));
+        assert!(crate::GcodeGenerator::new(&rejecting).generate_gcode(&pixels, 100, 50).is_err());
+        assert!(generate(&squashed).is_ok());
+        assert!(generate(&squashed.with_distortion_policy(DistortionPolicy::Allow)).is_ok());
+        
+        // Matching ratios, within rounding, pass under Reject
+        let matching = base
+            .with_output_dimensions(200.0, 100.5)
+            .unwrap()
+            .with_distortion_policy(DistortionPolicy::Reject);
+        assert!(generate(&matching).is_ok());
+    }
+    
+    #[test]
+    fn test_bead_sizes_snap_halftone_dots() {
+        use crate::config::{RenderMode, HalftoneStyle};
+        
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_fn(120, 120, |x, _| {
+            let v = (x * 2) as u8;
+            Rgba([v, v, v, 255])
+        }));
+        let beads = [2.5, 5.0, 9.0];
+        let config = PixelatorConfig::new(10.0, 0.0)
+            .unwrap()
+            .with_render_mode(RenderMode::Halftone(HalftoneStyle::BlackOnWhite))
+            .with_bead_sizes(vec![9.0, 2.5, 5.0])
+            .unwrap();
+        assert_eq!(config.bead_sizes.as_deref(), Some(&beads[..]));
+        
+        let pixels = ImageProcessor::new(&config).sample_image(&img).unwrap();
+        let svg = crate::svg_generator::SvgGenerator::new(&config).generate_svg(&pixels, 120, 120).unwrap();
+        let radii = attr_values(&svg, "r");
+        assert_eq!(radii.len(), pixels.len());
+        for radius in &radii {
+            assert!(beads.iter().any(|bead| (bead / 2.0 - radius).abs() < 1e-3), "radius {}", radius);
+        }
+        for bead in beads {
+            assert!(radii.iter().any(|radius| (bead / 2.0 - radius).abs() < 1e-3));
+        }
+        
+        // Color-mode sizes are left as sampled
+        let color = PixelatorConfig::new(10.0, 0.0).unwrap();
+        let unsnapped = ImageProcessor::new(&color).sample_image(&img).unwrap();
+        let beaded = color.clone().with_bead_sizes(beads.to_vec()).unwrap();
+        let pixels = ImageProcessor::new(&beaded).sample_image(&img).unwrap();
+        assert!(pixels.iter().zip(&unsnapped).all(|(a, b)| a.dot_size == b.dot_size));
+        assert!(pixels.iter().any(|p| !beads.contains(&p.dot_size)));
+        
+        assert!(PixelatorConfig::default().with_bead_sizes(vec![]).is_err());
+        assert!(PixelatorConfig::default().with_bead_sizes(vec![3.0, -1.0]).is_err());
+    }
+    
+    #[test]
+    fn test_guide_grid_lines_every_n_cells() {
+        // 100x60 pixels at a 10 pixel pitch: 10 columns and 6 rows of dots
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_pixel(100, 60, Rgba([128, 128, 128, 255])));
+        let config = PixelatorConfig::new(8.0, 2.0)
+            .unwrap()
+            .with_guide_grid(5, "#dddddd".to_string())
+            .unwrap();
+        let pixels = ImageProcessor::new(&config).sample_image(&img).unwrap();
+        let svg = crate::svg_generator::SvgGenerator::new(&config).generate_svg(&pixels, 100, 60).unwrap();
+        
+        let start = svg.find(r##"<g class="guide-grid" stroke="#dddddd""##).unwrap();
+        let end = start + svg[start..].find("</g>").unwrap();
+        let grid = &svg[start..end];
+        let lines: Vec<&str> = grid.split("<line").skip(1).collect();
+        let vertical = lines.iter().filter(|l| attr_values(l, "x1") == attr_values(l, "x2")).count();
+        let horizontal = lines.iter().filter(|l| attr_values(l, "y1") == attr_values(l, "y2")).count();
+        
+        // Columns 0, 5 and 10 and rows 0 and 5, midway between dot centers
+        // (the first dot sits at x = 4)
+        assert_eq!(vertical, 3);
+        assert_eq!(horizontal, 2);
+        assert_eq!(attr_values(grid, "x1")[..3], [-1.0, 49.0, 99.0]);
+        
+        // Drawn behind the dots
+        assert!(start < svg.find("<circle").unwrap());
+        
+        let hex = config.with_sample_mode(crate::config::SampleMode::Hexagonal);
+        assert!(crate::svg_generator::SvgGenerator::new(&hex).generate_svg(&pixels, 100, 60).is_err());
+    }
+    
+    #[test]
+    fn test_threshold_mode_places_dots_in_dark_half() {
+        use crate::config::RenderMode;
+        
+        // Dark on the left, light on the right
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_fn(100, 20, |x, _| {
+            let v = (x * 255 / 99) as u8;
+            Rgba([v, v, v, 255])
+        }));
+        let config = PixelatorConfig::new(8.0, 2.0)
+            .unwrap()
+            .with_render_mode(RenderMode::Threshold { level: 0.5 });
+        let pixels = ImageProcessor::new(&config).sample_image(&img).unwrap();
+        assert!(pixels.iter().all(|p| p.dot_size == 0.0 || p.dot_size == 8.0));
+        
+        let svg = crate::svg_generator::SvgGenerator::new(&config).generate_svg(&pixels, 100, 20).unwrap();
+        let xs = attr_values(&svg, "cx");
+        assert_eq!(xs.len(), pixels.iter().filter(|p| p.brightness < 0.5).count());
+        assert!(!xs.is_empty());
+        assert!(xs.iter().all(|&x| x < 50.0));
+        assert!(attr_values(&svg, "r").iter().all(|&r| r == 4.0));
+        assert!(svg.contains(r#"fill="black""#));
+    }
+    
+    #[test]
+    fn test_parallel_chunk_rows_keeps_output() {
+        use crate::config::SampleMode;
+        
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_fn(97, 83, |x, y| {
+            Rgba([(x * 2) as u8, (y * 3) as u8, ((x * y) % 256) as u8, 255])
+        }));
+        for mode in [SampleMode::Grid, SampleMode::Hexagonal] {
+            let base = PixelatorConfig::new(6.0, 1.0).unwrap().with_sample_mode(mode);
+            let expected = ImageProcessor::new(&base).sample_image(&img).unwrap();
+            for rows in [1, 3, 7, 1000] {
+                let config = base.clone().with_parallel_chunk_rows(rows).unwrap();
+                let pixels = ImageProcessor::new(&config).sample_image(&img).unwrap();
+                assert_eq!(serde_json::to_string(&pixels).unwrap(), serde_json::to_string(&expected).unwrap());
+            }
+        }
+        assert!(PixelatorConfig::default().with_parallel_chunk_rows(0).is_err());
+    }
+    
+    #[test]
+    fn test_coverage_fraction_matches_dot_area() {
+        // A 10x5 grid of 8 pixel dots on a 10 pixel pitch over 100x50 pixels
+        let dots: Vec<PixelData> = (0..50)
+            .map(|i| PixelData {
+                x: (i % 10) as f32 * 10.0 + 5.0,
+                y: (i / 10) as f32 * 10.0 + 5.0,
+                color: Rgba([0, 0, 0, 255]),
+                brightness: 0.0,
+                dot_size: 8.0,
+                grid_position: Some((i % 10, i / 10)),
+            })
+            .collect();
+        let mut file = crate::DotFile { config: PixelatorConfig::new(8.0, 2.0).unwrap(), width: 100, height: 50, dots };
+        
+        // Each dot covers pi * 4^2 of its 10x10 cell
+        let per_cell = std::f32::consts::PI * 16.0 / 100.0;
+        assert!((file.coverage_fraction() - per_cell).abs() < 1e-4);
+        
+        // Scaling to millimeters keeps the fraction; a margin adds blank page
+        file.config = file.config.clone().with_output_dimensions(200.0, 100.0).unwrap();
+        assert!((file.coverage_fraction() - per_cell).abs() < 1e-4);
+        file.config = file.config.clone().with_margin(25.0).unwrap();
+        assert!((file.coverage_fraction() - per_cell * 200.0 * 100.0 / (250.0 * 150.0)).abs() < 1e-4);
+    }
+    
+    #[test]
+    fn test_importance_dots_cluster_in_detail() {
+        // Flat gray on the left half, fine black and white stripes on the right
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_fn(200, 100, |x, _| {
+            let v = if x < 100 { 128 } else if (x / 5) % 2 == 0 { 0 } else { 255 };
+            Rgba([v, v, v, 255])
+        }));
+        let config = PixelatorConfig::new(4.0, 1.0)
+            .unwrap()
+            .with_seed(7)
+            .with_importance_dots(60)
+            .unwrap();
+        let pixels = ImageProcessor::new(&config).sample_image(&img).unwrap();
+        assert_eq!(pixels.len(), 60);
+        
+        let detailed = pixels.iter().filter(|p| p.x >= 95.0).count();
+        assert!(detailed >= 55, "only {} of 60 dots in the detailed half", detailed);
+        
+        // The same seed picks the same dots
+        let again = ImageProcessor::new(&config).sample_image(&img).unwrap();
+        assert_eq!(serde_json::to_string(&again).unwrap(), serde_json::to_string(&pixels).unwrap());
+    }
+    
+    #[test]
+    fn test_grayscale_input_uses_gray_fill_path() {
+        use crate::svg_generator::ColorCache;
+        
+        let dir = TempDir::new("gray");
+        let input = dir.join("scan.png");
+        image::GrayImage::from_fn(60, 40, |x, _| image::Luma([(x * 4) as u8])).save(&input).unwrap();
+        
+        let decoded = image::open(&input).unwrap();
+        assert!(matches!(decoded, DynamicImage::ImageLuma8(_)));
+        let svg = Pixelator::new(PixelatorConfig::new(8.0, 2.0).unwrap()).process_image(&input).unwrap();
+        
+        let fills: Vec<&str> = svg
+            .split(r#"fill="rgb("#)
+            .skip(1)
+            .map(|rest| &rest[..rest.find(')').unwrap()])
+            .collect();
+        assert!(!fills.is_empty());
+        for fill in fills {
+            let channels: Vec<&str> = fill.split(',').collect();
+            assert!(channels.len() == 3 && channels[0] == channels[1] && channels[1] == channels[2], "{}", fill);
+        }
+        
+        // Grays bypass the paged table, other colors go through it
+        let mut cache = ColorCache::default();
+        assert_eq!(cache.fill((42, 42, 42)), "rgb(42,42,42)");
+        assert_eq!(cache.fill((42, 42, 42)), "rgb(42,42,42)");
+        assert_eq!(cache.paged_colors(), 0);
+        assert_eq!(cache.fill((42, 43, 42)), "rgb(42,43,42)");
+        assert_eq!(cache.paged_colors(), 1);
+    }
+    
+    #[test]
+    fn test_reference_layer_embeds_source_png() {
+        let dir = TempDir::new("reference");
+        let path = dir.join("source.png");
+        RgbaImage::from_pixel(20, 20, Rgba([90, 90, 90, 255])).save(&path).unwrap();
+        
+        let plain = Pixelator::new(PixelatorConfig::new(8.0, 2.0).unwrap()).process_image(&path).unwrap();
+        assert!(!plain.contains("reference-layer"));
+        
+        let config = PixelatorConfig::new(8.0, 2.0).unwrap().with_reference_layer(0.3).unwrap();
+        let svg = Pixelator::new(config).process_image(&path).unwrap();
+        
+        assert!(svg.contains("id=\"reference-layer\""));
+        assert!(svg.contains("<image"));
+        assert!(svg.contains("data:image/png;base64,"));
+        assert!(svg.find("<image").unwrap() < svg.find("<circle").unwrap());
+        assert!(PixelatorConfig::default().with_reference_layer(0.0).is_err());
+    }
+    
+    #[test]
+    fn test_snap_to_grid_rounds_centers_to_pitch() {
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_pixel(100, 60, Rgba([40, 40, 40, 255])));
+        // 7 pixel cells on a 50mm wide output, so centers start off the 2.5mm grid
+        let config = PixelatorConfig::new(5.0, 2.0)
+            .unwrap()
+            .with_output_dimensions(50.0, 30.0)
+            .unwrap()
+            .with_snap_to_grid(2.5)
+            .unwrap();
+        let pixels = ImageProcessor::new(&config).sample_image(&img).unwrap();
+        let svg = crate::svg_generator::SvgGenerator::new(&config).generate_svg(&pixels, 100, 60).unwrap();
+        
+        // 0.5mm per viewBox unit, so the pitch is 5 units
+        let centers: Vec<f32> = attr_values(&svg, "cx").into_iter().chain(attr_values(&svg, "cy")).collect();
+        assert!(!centers.is_empty());
+        for value in centers {
+            let steps = value / 5.0;
+            assert!((steps - steps.round()).abs() < 1e-3, "{} is off the grid", value);
+        }
+        assert!(PixelatorConfig::default().with_snap_to_grid(0.0).is_err());
+    }
+    
+    #[test]
+    fn test_reveal_animation_staggers_dots() {
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_pixel(40, 40, Rgba([200, 50, 50, 255])));
+        let config = PixelatorConfig::new(8.0, 2.0).unwrap().with_reveal_animation(4.0).unwrap();
+        let pixels = ImageProcessor::new(&config).sample_image(&img).unwrap();
+        let generator = crate::svg_generator::SvgGenerator::new(&config);
+        let svg = generator.generate_svg(&pixels, 40, 40).unwrap();
+        
+        assert!(svg.contains("<animate"));
+        // The growing animation of each dot, skipping the ones holding it hidden
+        let begins: Vec<f32> = svg
+            .split("<animate ")
+            .skip(1)
+            .filter(|element| !element.contains("values=\"0;0\""))
+            .map(|element| {
+                let start = element.find("begin=\"").unwrap() + 7;
+                element[start..].split('s').next().unwrap().parse().unwrap()
+            })
+            .collect();
+        assert_eq!(begins.len(), pixels.len());
+        assert_eq!(begins[0], 0.0);
+        assert!(begins.windows(2).all(|w| w[1] > w[0]));
+        assert!(*begins.last().unwrap() < 4.0);
+        assert!(generator.take_warnings().is_empty());
+        
+        // Past the dot limit the dots stay static and the skip is reported
+        let dense = PixelatorConfig::new(1.0, 1.0).unwrap().with_reveal_animation(4.0).unwrap();
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_pixel(202, 202, Rgba([200, 50, 50, 255])));
+        let pixels = ImageProcessor::new(&dense).sample_image(&img).unwrap();
+        let generator = crate::svg_generator::SvgGenerator::new(&dense);
+        let svg = generator.generate_svg(&pixels, 202, 202).unwrap();
+        assert!(pixels.len() > 10_000);
+        assert!(!svg.contains("<animate"));
+        let warnings = generator.take_warnings();
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("reveal animation skipped"));
+        assert!(PixelatorConfig::default().with_reveal_animation(0.0).is_err());
+    }
+    
+    #[test]
+    fn test_physical_dot_size_in_output_millimeters() {
+        // 200 pixels across 100mm of output is 2 pixels per mm
+        let config = PixelatorConfig::default()
+            .with_output_dimensions(100.0, 50.0)
+            .unwrap()
+            .with_physical_dot_size(1.0, 0.5)
+            .unwrap();
+        let resolved = config.resolve_for_image(200, 100);
+        assert!((resolved.circle_diameter - 2.0).abs() < 1e-5);
+        assert!((resolved.circle_spacing - 1.0).abs() < 1e-5);
+        
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_pixel(200, 100, Rgba([0, 0, 0, 255])));
+        let pixels = ImageProcessor::new(&resolved).sample_image(&img).unwrap();
+        assert_eq!(pixels.len(), 66 * 33);
+        
+        // The output size may also be given after the dot size
+        let later = PixelatorConfig::default()
+            .with_physical_dot_size(1.0, 0.5)
+            .unwrap()
+            .with_output_dimensions(100.0, 50.0)
+            .unwrap();
+        assert!(later.check_physical_dot_size().is_ok());
+        assert!((later.resolve_for_image(200, 100).circle_diameter - 2.0).abs() < 1e-5);
+        
+        assert!(matches!(
+            PixelatorConfig::default().with_physical_dot_size(1.0, 0.5).unwrap().check_physical_dot_size(),
+            Err(crate::PixelatorError::InvalidConfig(_))
+        ));
+    }
+    
+    #[test]
+    fn test_hexagonal_rows_reach_bottom_edge() {
+        // Rows are 12 * 0.866 = 10.392 apart, so 100 pixels isn't a whole
+        // number of rows
+        let height = 100;
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_pixel(60, height, Rgba([80, 80, 80, 255])));
+        let config = PixelatorConfig::new(10.0, 2.0).unwrap().with_sample_mode(SampleMode::Hexagonal);
+        let pixels = ImageProcessor::new(&config).sample_image(&img).unwrap();
+        
+        let lowest = pixels.iter().map(|p| p.y).fold(0.0f32, f32::max);
+        assert!(lowest < height as f32);
+        // Grid mode leaves at most one cell uncovered below the last row
+        assert!(height as f32 - lowest <= 12.0 * 0.866, "lowest row at {}", lowest);
+        assert_eq!(config.grid_dimensions(60, height).1, 10);
+    }
+    
+    #[test]
+    fn test_alpha_size_source_follows_opacity() {
+        use crate::config::{HalftoneStyle, RenderMode, SizeSource};
+        
+        // Left half transparent black, right half opaque white
+        let img = RgbaImage::from_fn(40, 20, |x, _| if x < 20 { Rgba([0, 0, 0, 0]) } else { Rgba([255, 255, 255, 255]) });
+        let img = DynamicImage::ImageRgba8(img);
+        for style in [HalftoneStyle::BlackOnWhite, HalftoneStyle::WhiteOnBlack] {
+            let config = PixelatorConfig::new(8.0, 2.0)
+                .unwrap()
+                .with_render_mode(RenderMode::Halftone(style.clone()))
+                .with_size_source(SizeSource::Alpha);
+            let pixels = ImageProcessor::new(&config).sample_image(&img).unwrap();
+            for pixel in &pixels {
+                let expected = if pixel.x < 20.0 { config.min_dot_size } else { config.max_dot_size };
+                assert!((pixel.dot_size - expected).abs() < 1e-4, "{:?} dot at x {} is {}", style, pixel.x, pixel.dot_size);
+            }
+        }
+    }
+    
+    #[test]
+    fn test_registration_marks_at_corners() {
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_pixel(100, 60, Rgba([40, 40, 40, 255])));
+        let marks = |config: &PixelatorConfig| -> Vec<(f32, f32)> {
+            let pixels = ImageProcessor::new(config).sample_image(&img).unwrap();
+            let svg = crate::svg_generator::SvgGenerator::new(config).generate_svg(&pixels, 100, 60).unwrap();
+            svg.split("class=\"registration-mark\"")
+                .skip(1)
+                .map(|mark| {
+                    // The vertical arm's x and the horizontal arm's y give the center
+                    let group = &mark[..mark.find("</g>").unwrap()];
+                    (attr_values(group, "x1")[1], attr_values(group, "y1")[0])
+                })
+                .collect()
+        };
+        
+        // 10 pixel cells: without a margin the marks sit inside the corners
+        let config = PixelatorConfig::new(8.0, 2.0).unwrap().with_registration_marks(true);
+        assert_eq!(marks(&config), vec![(5.0, 5.0), (95.0, 5.0), (5.0, 55.0), (95.0, 55.0)]);
+        
+        // A margin of 20 units (20mm at 1mm per pixel) holds them outside the field
+        let config = config.with_margin(20.0).unwrap();
+        assert_eq!(marks(&config), vec![(-10.0, -10.0), (110.0, -10.0), (-10.0, 70.0), (110.0, 70.0)]);
+        
+        assert!(!crate::svg_generator::SvgGenerator::new(&PixelatorConfig::default())
+            .generate_svg(&[], 100, 60)
+            .unwrap()
+            .contains("registration-mark"));
+    }
+    
+    #[test]
+    fn test_overlapping_dots_detected() {
+        use crate::config::{HalftoneStyle, OverlapPolicy, RenderMode};
+        
+        // Library callers opt in to warnings
+        let config = PixelatorConfig::new(10.0, 0.0).unwrap();
+        assert_eq!(config.overlap_policy, OverlapPolicy::Allow);
+        
+        // Touching dots at zero spacing don't overlap
+        assert!(!config.dots_overlap(10.0));
+        assert!(config.dots_overlap(10.5));
+        assert!(config.check_overlap().is_ok());
+        
+        // A halftone maximum beyond the 10 pixel pitch overlaps
+        let config = config
+            .with_render_mode(RenderMode::Halftone(HalftoneStyle::BlackOnWhite))
+            .with_halftone_range(1.0, 14.0)
+            .unwrap()
+            .with_overlap_policy(OverlapPolicy::Reject);
+        assert!(config.dots_overlap(config.largest_dot_size()));
+        assert!(matches!(config.check_overlap(), Err(crate::PixelatorError::InvalidConfig(_))));
+        let svg = crate::svg_generator::SvgGenerator::new(&config).generate_svg(&[], 100, 100);
+        assert!(svg.is_err());
+        
+        assert!(config.with_overlap_policy(OverlapPolicy::Allow).check_overlap().is_ok());
+    }
+    
+    #[cfg(feature = "svg-input")]
+    #[test]
+    fn test_svg_input_rasterized_before_sampling() {
+        let dir = TempDir::new("svg_input");
+        let path = dir.join("logo.svg");
+        std::fs::write(
+            &path,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="20" height="10"><rect width="10" height="10" fill="black"/><rect x="10" width="10" height="10" fill="white"/></svg>"#,
+        )
+        .unwrap();
+        
+        // 192 DPI doubles the 20x10 unit document to 40x20 pixels
+        let config = PixelatorConfig::new(8.0, 2.0).unwrap().with_svg_input_dpi(192.0).unwrap();
+        let dots = Pixelator::new(config).sample(&path).unwrap();
+        
+        assert_eq!((dots.width, dots.height), (40, 20));
+        assert_eq!(dots.dots.len(), 4 * 2);
+        assert!(dots.dots.iter().filter(|p| p.x < 20.0).all(|p| p.brightness < 0.1));
+        assert!(dots.dots.iter().filter(|p| p.x > 20.0).all(|p| p.brightness > 0.9));
+    }
+    
+    #[test]
+    fn test_svg_input_dpi_validation() {
+        assert!(PixelatorConfig::default().with_svg_input_dpi(0.0).is_err());
+        assert!(crate::vector::is_svg_path("logo.SVG"));
+        assert!(!crate::vector::is_svg_path("photo.png"));
+    }
+    
+    #[test]
+    fn test_premultiplied_alpha_avoids_dark_halo() {
+        // Opaque white on the left, transparent black on the right
+        let img = RgbaImage::from_fn(30, 10, |x, _| if x < 15 { Rgba([255, 255, 255, 255]) } else { Rgba([0, 0, 0, 0]) });
+        let img = DynamicImage::ImageRgba8(img);
+        // Touching 10 pixel dots; the middle one straddles the edge
+        let config = PixelatorConfig::new(10.0, 0.0).unwrap();
+        let edge = |config: &PixelatorConfig| {
+            let pixels = ImageProcessor::new(config).sample_image(&img).unwrap();
+            pixels.iter().find(|p| (p.x - 15.0).abs() < 1.0).unwrap().color
+        };
+        
+        let color = edge(&config);
+        assert_eq!(&color.0[..3], &[255, 255, 255]);
+        assert!(color[3] > 0 && color[3] < 255);
+        
+        let straight = edge(&config.clone().with_premultiplied_alpha(false));
+        assert!(straight[0] < 200);
+        assert_eq!(straight[3], color[3]);
+    }
+    
+    #[test]
+    fn test_morph_shape_rounds_light_cells() {
+        use crate::config::RenderMode;
+        
+        // Black left half, white right half
+        let img = RgbaImage::from_fn(40, 20, |x, _| if x < 20 { Rgba([0, 0, 0, 255]) } else { Rgba([255, 255, 255, 255]) });
+        let img = DynamicImage::ImageRgba8(img);
+        let config = PixelatorConfig::new(8.0, 2.0).unwrap().with_render_mode(RenderMode::MorphShape);
+        let pixels = ImageProcessor::new(&config).sample_image(&img).unwrap();
+        let svg = crate::svg_generator::SvgGenerator::new(&config).generate_svg(&pixels, 40, 20).unwrap();
+        
+        let xs = attr_values(&svg, "x");
+        let widths = attr_values(&svg, "width");
+        let radii = attr_values(&svg, "rx");
+        assert_eq!(radii.len(), pixels.len());
+        // The document width comes first among the widths
+        for ((x, side), rx) in xs.iter().zip(&widths[1..]).zip(&radii) {
+            assert_eq!(*side, 8.0);
+            if *x < 20.0 {
+                assert!(*rx < 0.01, "dark cell at {} has rx {}", x, rx);
+            } else {
+                assert!((rx - side / 2.0).abs() < 0.01, "light cell at {} has rx {}", x, rx);
+            }
+        }
+    }
+    
+    #[test]
+    fn test_fill_interstices_adds_midpoint_dots() {
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_pixel(40, 30, Rgba([120, 60, 30, 255])));
+        let config = PixelatorConfig::new(10.0, 0.0).unwrap().with_fill_interstices(true);
+        let pixels = ImageProcessor::new(&config).sample_image(&img).unwrap();
+        
+        // A 4x3 grid of touching dots has 3x2 gaps between them
+        let (grid, extra): (Vec<&PixelData>, Vec<&PixelData>) = pixels.iter().partition(|p| p.grid_position.is_some());
+        assert_eq!(grid.len(), 12);
+        assert_eq!(extra.len(), 6);
+        for dot in &extra {
+            // Gaps sit at the 10 pixel lattice's cell corners
+            assert_eq!(((dot.x - 10.0) % 10.0, (dot.y - 10.0) % 10.0), (0.0, 0.0));
+            assert!((dot.dot_size - 10.0 * (2.0f32.sqrt() - 1.0)).abs() < 1e-4);
+            assert_eq!(dot.color, Rgba([120, 60, 30, 255]));
+        }
+        
+        let hex = config.with_sample_mode(SampleMode::Hexagonal);
+        assert!(ImageProcessor::new(&hex).sample_image(&img).is_err());
+    }
+    
+    #[test]
+    fn test_output_dpi_from_pitch_and_size() {
+        // 2540 pixels at a 10 pixel pitch is 254 dots over 254mm, 10 inches
+        let config = PixelatorConfig::new(8.0, 2.0).unwrap();
+        assert!((config.output_dpi(2540, 254.0) - 25.4).abs() < 1e-3);
+        
+        // A configuration resolved for a screen frequency reports it back
+        let resolved = PixelatorConfig::default()
+            .with_output_dimensions(200.0, 100.0)
+            .unwrap()
+            .with_lpi(30.0)
+            .unwrap()
+            .resolve_for_image(1000, 500);
+        assert!((resolved.output_dpi(1000, 200.0) - 30.0).abs() < 1e-3);
+    }
+    
+    #[test]
+    fn test_halftone_dot_size_clamped_for_bad_brightness() {
+        use crate::config::{HalftoneStyle, RenderMode};
+        
+        for style in [HalftoneStyle::BlackOnWhite, HalftoneStyle::WhiteOnBlack] {
+            let config = PixelatorConfig::new(10.0, 2.0)
+                .unwrap()
+                .with_render_mode(RenderMode::Halftone(style))
+                .with_halftone_range(2.0, 9.0)
+                .unwrap();
+            // Heavy dot gain would shrink the smallest dots below the range
+            let gained = config.clone().with_dot_gain(45.0).unwrap();
+            for config in [&config, &gained] {
+                let processor = ImageProcessor::new(config);
+                for brightness in [-3.0, -0.01, 0.0, 0.5, 1.0, 1.01, 40.0, f32::NAN, f32::INFINITY, f32::NEG_INFINITY] {
+                    let size = processor.calculate_dot_size(brightness);
+                    assert!((2.0..=9.0).contains(&size), "brightness {} gave dot size {}", brightness, size);
+                }
+                assert_eq!(processor.calculate_dot_size(f32::NAN), 2.0);
+            }
+        }
+    }
+    
+    #[test]
+    fn test_spot_color_bands_split_dots_by_tone() {
+        // Dark, middle and light stripes
+        let img = RgbaImage::from_fn(60, 20, |x, _| {
+            let level = [20, 128, 235][(x / 20) as usize];
+            Rgba([level, level, level, 255])
+        });
+        let img = DynamicImage::ImageRgba8(img);
+        let inks = vec!["#112233".to_string(), "#cc3300".to_string(), "#ffcc00".to_string()];
+        let config = PixelatorConfig::new(8.0, 2.0).unwrap().with_spot_color_bands(inks.clone()).unwrap();
+        let pixels = ImageProcessor::new(&config).sample_image(&img).unwrap();
+        let svg = crate::svg_generator::SvgGenerator::new(&config).generate_svg(&pixels, 60, 20).unwrap();
+        
+        let layers: Vec<&str> = svg.split("class=\"spot-layer\"").skip(1).map(|l| &l[..l.find("</g>").unwrap()]).collect();
+        assert_eq!(layers.len(), 3);
+        let mut total = 0;
+        for (k, layer) in layers.iter().enumerate() {
+            let xs = attr_values(layer, "cx");
+            assert!(!xs.is_empty());
+            // Each layer holds one stripe, all in its own ink
+            assert!(xs.iter().all(|x| (*x / 20.0) as usize == k));
+            assert_eq!(layer.matches("fill=").count(), xs.len());
+            assert_eq!(layer.matches(&format!("fill=\"{}\"", inks[k])).count(), xs.len());
+            total += xs.len();
+        }
+        assert_eq!(total, pixels.len());
+        assert!(PixelatorConfig::default().with_spot_color_bands(Vec::new()).is_err());
+    }
 }
\ No newline at end of file
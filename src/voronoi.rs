@@ -0,0 +1,80 @@
+use rayon::prelude::*;
+
+/// An axis-aligned clipping rectangle `(min_x, min_y, max_x, max_y)`
+pub type Bounds = (f32, f32, f32, f32);
+
+/// Computes the Voronoi cell of every point, clipped to `bounds`
+///
+/// Each cell is returned as a convex polygon in counter-clockwise order (in
+/// y-down coordinates), at the same index as its point. Cells are built by
+/// clipping the bounds against the bisector of each neighbour, nearest first,
+/// stopping once no farther neighbour can cut the cell.
+pub fn voronoi_cells(points: &[(f32, f32)], bounds: Bounds) -> Vec<Vec<(f32, f32)>> {
+    let (min_x, min_y, max_x, max_y) = bounds;
+    let rect = vec![(min_x, min_y), (max_x, min_y), (max_x, max_y), (min_x, max_y)];
+
+    points
+        .par_iter()
+        .enumerate()
+        .map(|(i, &site)| {
+            let mut neighbours: Vec<(f32, (f32, f32))> = points
+                .iter()
+                .enumerate()
+                .filter(|&(j, other)| j != i && *other != site)
+                .map(|(_, &other)| (distance_sq(site, other), other))
+                .collect();
+            neighbours.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+            let mut cell = rect.clone();
+            for (dist_sq, other) in neighbours {
+                // A neighbour farther than twice the cell radius cannot clip it
+                let reach = cell.iter().map(|&v| distance_sq(site, v)).fold(0.0, f32::max);
+                if dist_sq > 4.0 * reach {
+                    break;
+                }
+                cell = clip_to_bisector(&cell, site, other);
+                if cell.is_empty() {
+                    break;
+                }
+            }
+            cell
+        })
+        .collect()
+}
+
+/// Area of a simple polygon (always non-negative)
+pub fn polygon_area(polygon: &[(f32, f32)]) -> f32 {
+    let mut twice_area = 0.0;
+    for (i, &(x1, y1)) in polygon.iter().enumerate() {
+        let (x2, y2) = polygon[(i + 1) % polygon.len()];
+        twice_area += x1 * y2 - x2 * y1;
+    }
+    (twice_area / 2.0).abs()
+}
+
+fn distance_sq(a: (f32, f32), b: (f32, f32)) -> f32 {
+    (a.0 - b.0).powi(2) + (a.1 - b.1).powi(2)
+}
+
+/// Keeps the part of a convex polygon that is closer to `site` than to `other`
+fn clip_to_bisector(polygon: &[(f32, f32)], site: (f32, f32), other: (f32, f32)) -> Vec<(f32, f32)> {
+    let mid = ((site.0 + other.0) / 2.0, (site.1 + other.1) / 2.0);
+    let normal = (other.0 - site.0, other.1 - site.1);
+    // Negative values lie on the site's side of the bisector
+    let side = |p: (f32, f32)| (p.0 - mid.0) * normal.0 + (p.1 - mid.1) * normal.1;
+
+    let mut clipped = Vec::with_capacity(polygon.len() + 1);
+    for (i, &current) in polygon.iter().enumerate() {
+        let next = polygon[(i + 1) % polygon.len()];
+        let (s_current, s_next) = (side(current), side(next));
+
+        if s_current <= 0.0 {
+            clipped.push(current);
+        }
+        if (s_current < 0.0 && s_next > 0.0) || (s_current > 0.0 && s_next < 0.0) {
+            let t = s_current / (s_current - s_next);
+            clipped.push((current.0 + t * (next.0 - current.0), current.1 + t * (next.1 - current.1)));
+        }
+    }
+    clipped
+}
\ No newline at end of file
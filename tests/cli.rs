@@ -0,0 +1,51 @@
+use image::{Rgba, RgbaImage};
+use std::path::PathBuf;
+use std::process::Command;
+
+// Scratch directory that is removed when dropped, even if the test fails
+struct TempDir(PathBuf);
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+fn temp_dir(name: &str) -> TempDir {
+    let dir = std::env::temp_dir().join(format!("pixelator_cli_{}_{}", name, std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    TempDir(dir)
+}
+
+#[test]
+fn test_print_palette_lists_colors_on_stderr() {
+    let dir = temp_dir("palette");
+    let input = dir.0.join("two.png");
+    let output = dir.0.join("two.svg");
+    RgbaImage::from_fn(48, 24, |x, _| if x < 36 { Rgba([255, 0, 0, 255]) } else { Rgba([0, 0, 255, 255]) })
+        .save(&input)
+        .unwrap();
+
+    let result = Command::new(env!("CARGO_BIN_EXE_pixelator"))
+        .arg(&input)
+        .arg(&output)
+        .args(["-d", "10", "-s", "2", "--palette", "2", "--print-palette"])
+        .output()
+        .unwrap();
+
+    assert!(result.status.success(), "{}", String::from_utf8_lossy(&result.stderr));
+    assert!(output.exists());
+    let stderr = String::from_utf8(result.stderr).unwrap();
+    let listing: Vec<(String, usize)> = stderr
+        .lines()
+        .filter_map(|line| {
+            let (color, count) = line.split_once(' ')?;
+            let hex = color.strip_prefix('#')?;
+            if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+                return None;
+            }
+            Some((color.to_string(), count.parse().ok()?))
+        })
+        .collect();
+    assert_eq!(listing, vec![("#ff0000".to_string(), 6), ("#0000ff".to_string(), 2)]);
+}